@@ -1,5 +1,12 @@
+use crate::jwks::{JWKS_REFETCH_COOLDOWN, JWKS_TTL, JwksCache};
+use crate::local_auth::LocalValidator;
 use anyhow::{Result, anyhow};
-use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, jwk::JwkSet};
+#[cfg(feature = "test-support")]
+use futures_util::future;
+use futures_util::future::BoxFuture;
+#[cfg(feature = "test-support")]
+use jsonwebtoken::DecodingKey;
+use jsonwebtoken::{Algorithm, Validation, decode};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
@@ -7,6 +14,8 @@ use std::sync::Arc;
 pub struct AuthResult {
     pub user_id: String,
     pub is_host: bool,
+    /// When the token stops being valid, in epoch millis. None if it never does.
+    pub expires_at_ms: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,34 +30,44 @@ struct Claims {
 }
 
 pub trait JwtValidator: Send + Sync {
-    fn validate(&self, token: &str) -> Result<AuthResult>;
+    fn validate<'a>(&'a self, token: &'a str) -> BoxFuture<'a, Result<AuthResult>>;
 }
 
-/// Production validator that fetches JWKS from Cognito
+/// Production validator that checks tokens against Cognito's JWKS, cached in memory
 pub struct CognitoValidator {
     pub region: String,
     pub user_pool_id: String,
     pub client_id: String,
+    jwks: JwksCache,
 }
 
 impl CognitoValidator {
     pub fn new(region: String, user_pool_id: String, client_id: String) -> Self {
+        let jwks_url = format!(
+            "https://cognito-idp.{region}.amazonaws.com/{user_pool_id}/.well-known/jwks.json"
+        );
+        let jwks = JwksCache::new(jwks_url, JWKS_TTL, JWKS_REFETCH_COOLDOWN);
+        Self::with_jwks(region, user_pool_id, client_id, jwks)
+    }
+
+    /// Validate against keys from a given cache, e.g. one serving a local stand-in for
+    /// Cognito in tests
+    pub fn with_jwks(
+        region: String,
+        user_pool_id: String,
+        client_id: String,
+        jwks: JwksCache,
+    ) -> Self {
         Self {
             region,
             user_pool_id,
             client_id,
+            jwks,
         }
     }
 }
 
 impl CognitoValidator {
-    fn jwks_url(&self) -> String {
-        format!(
-            "https://cognito-idp.{}.amazonaws.com/{}/.well-known/jwks.json",
-            self.region, self.user_pool_id
-        )
-    }
-
     fn expected_issuer(&self) -> String {
         format!(
             "https://cognito-idp.{}.amazonaws.com/{}",
@@ -56,35 +75,7 @@ impl CognitoValidator {
         )
     }
 
-    fn fetch_jwks(&self) -> Result<JwkSet> {
-        let response = reqwest::blocking::get(self.jwks_url())
-            .map_err(|e| anyhow!("Failed to fetch JWKS: {}", e))?;
-
-        if !response.status().is_success() {
-            return Err(anyhow!(
-                "JWKS request failed with status: {}",
-                response.status()
-            ));
-        }
-
-        response
-            .json::<JwkSet>()
-            .map_err(|e| anyhow!("Failed to parse JWKS: {}", e))
-    }
-
-    fn find_decoding_key(&self, jwks: &JwkSet, kid: &str) -> Result<DecodingKey> {
-        let jwk = jwks
-            .keys
-            .iter()
-            .find(|k| k.common.key_id.as_deref() == Some(kid))
-            .ok_or_else(|| anyhow!("No matching key found for kid: {}", kid))?;
-
-        DecodingKey::from_jwk(jwk).map_err(|e| anyhow!("Failed to create decoding key: {}", e))
-    }
-}
-
-impl JwtValidator for CognitoValidator {
-    fn validate(&self, token: &str) -> Result<AuthResult> {
+    async fn validate_token(&self, token: &str) -> Result<AuthResult> {
         // Decode header to get the key ID (kid)
         let header = jsonwebtoken::decode_header(token)
             .map_err(|e| anyhow!("Failed to decode token header: {}", e))?;
@@ -93,9 +84,8 @@ impl JwtValidator for CognitoValidator {
             .kid
             .ok_or_else(|| anyhow!("Token missing kid in header"))?;
 
-        // Fetch JWKS and find the matching key
-        let jwks = self.fetch_jwks()?;
-        let decoding_key = self.find_decoding_key(&jwks, &kid)?;
+        // Find the matching key, fetching the JWKS if it isn't cached
+        let decoding_key = self.jwks.decoding_key(&kid).await?;
 
         // Set up validation
         let mut validation = Validation::new(Algorithm::RS256);
@@ -123,10 +113,17 @@ impl JwtValidator for CognitoValidator {
         Ok(AuthResult {
             user_id: claims.sub,
             is_host,
+            expires_at_ms: Some(claims.exp * 1000),
         })
     }
 }
 
+impl JwtValidator for CognitoValidator {
+    fn validate<'a>(&'a self, token: &'a str) -> BoxFuture<'a, Result<AuthResult>> {
+        Box::pin(self.validate_token(token))
+    }
+}
+
 /// Test validator that uses a known RSA key pair
 #[cfg(feature = "test-support")]
 pub struct TestValidator {
@@ -160,7 +157,14 @@ impl TestValidator {
 
 #[cfg(feature = "test-support")]
 impl JwtValidator for TestValidator {
-    fn validate(&self, token: &str) -> Result<AuthResult> {
+    fn validate<'a>(&'a self, token: &'a str) -> BoxFuture<'a, Result<AuthResult>> {
+        Box::pin(future::ready(self.validate_token(token)))
+    }
+}
+
+#[cfg(feature = "test-support")]
+impl TestValidator {
+    fn validate_token(&self, token: &str) -> Result<AuthResult> {
         let mut validation = Validation::new(Algorithm::RS256);
         validation.set_issuer(&[&self.expected_issuer]);
         validation.set_required_spec_claims(&["exp", "sub", "iss"]);
@@ -183,12 +187,35 @@ impl JwtValidator for TestValidator {
         Ok(AuthResult {
             user_id: claims.sub,
             is_host,
+            expires_at_ms: Some(claims.exp * 1000),
         })
     }
 }
 
+/// Whether `AUTH_MODE=local` chose self-hosted tokens (see `local_auth`) over Cognito
+pub fn is_local_auth() -> bool {
+    std::env::var("AUTH_MODE").is_ok_and(|mode| mode == "local")
+}
+
+/// Whether `ALLOW_QUERY_TOKEN=true` lets clients send their token as `?token=` in the URL,
+/// for compatibility with clients that predate the other ways of sending it
+pub fn allow_query_token_from_env() -> bool {
+    std::env::var("ALLOW_QUERY_TOKEN").is_ok_and(|allow| allow == "true")
+}
+
 /// Helper to create a validator from environment variables
 pub fn create_validator_from_env() -> Arc<dyn JwtValidator> {
+    match std::env::var("AUTH_MODE").as_deref() {
+        Ok("local") => match LocalValidator::from_env() {
+            Ok(validator) => Arc::new(validator),
+            Err(e) => panic!("AUTH_MODE=local but local auth isn't configured: {e}"),
+        },
+        Ok("cognito") | Err(_) => create_cognito_validator_from_env(),
+        Ok(other) => panic!("Unknown AUTH_MODE={other}, expected cognito or local"),
+    }
+}
+
+fn create_cognito_validator_from_env() -> Arc<dyn JwtValidator> {
     match (
         std::env::var("COGNITO_USER_POOL_ID"),
         std::env::var("COGNITO_CLIENT_ID"),
@@ -0,0 +1,124 @@
+//! Expiry of the token a host-side connection authenticated with.
+//!
+//! Tokens are only checked when a connection opens, so each connection remembers when its
+//! token expires. Shortly before then the client is asked for a fresh one (`AuthExpiring`);
+//! once it passes, the connection is read-only (`AuthExpired`) until a `Reauthenticate`
+//! succeeds. The game carries on either way.
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::game_timer::now_ms;
+use crate::model::server_message::ServerMessage;
+
+/// How long before its token expires a connection is asked to reauthenticate
+pub const REAUTH_WARNING: Duration = Duration::from_secs(5 * 60);
+
+pub struct AuthExpiry {
+    expires_at_ms: Option<u64>,
+    warned: bool,
+    expired: bool,
+}
+
+impl AuthExpiry {
+    /// Track a token that expires at `expires_at_ms`, or never if None
+    pub fn new(expires_at_ms: Option<u64>) -> Self {
+        Self {
+            expires_at_ms,
+            warned: false,
+            expired: false,
+        }
+    }
+
+    /// Whether the token has expired, leaving the connection read-only
+    pub fn is_expired(&self) -> bool {
+        self.expired
+    }
+
+    /// Track a fresh token instead, restoring full access
+    pub fn renew(&mut self, expires_at_ms: Option<u64>) {
+        *self = Self::new(expires_at_ms);
+    }
+
+    /// When `poll` next has something to send, if ever
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let expires_at_ms = self.expires_at_ms?;
+        let due_ms = if !self.warned {
+            expires_at_ms.saturating_sub(REAUTH_WARNING.as_millis() as u64)
+        } else if !self.expired {
+            expires_at_ms
+        } else {
+            return None;
+        };
+        Some(Instant::now() + Duration::from_millis(due_ms.saturating_sub(now_ms())))
+    }
+
+    /// The warning or expiry notice that's now due, each sent once per token
+    pub fn poll(&mut self) -> Option<ServerMessage> {
+        self.poll_at(now_ms())
+    }
+
+    fn poll_at(&mut self, now_ms: u64) -> Option<ServerMessage> {
+        let expires_at_ms = self.expires_at_ms?;
+        if !self.expired && now_ms >= expires_at_ms {
+            self.warned = true;
+            self.expired = true;
+            return Some(ServerMessage::AuthExpired);
+        }
+        if !self.warned && now_ms + REAUTH_WARNING.as_millis() as u64 >= expires_at_ms {
+            self.warned = true;
+            return Some(ServerMessage::AuthExpiring { expires_at_ms });
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPIRES_AT_MS: u64 = 10_000_000;
+
+    #[test]
+    fn warns_then_expires_once() {
+        let mut expiry = AuthExpiry::new(Some(EXPIRES_AT_MS));
+        assert!(expiry.poll_at(0).is_none());
+
+        let warning_ms = EXPIRES_AT_MS - REAUTH_WARNING.as_millis() as u64;
+        assert!(matches!(
+            expiry.poll_at(warning_ms),
+            Some(ServerMessage::AuthExpiring {
+                expires_at_ms: EXPIRES_AT_MS
+            })
+        ));
+        assert!(expiry.poll_at(warning_ms + 1).is_none());
+        assert!(!expiry.is_expired());
+
+        assert!(matches!(
+            expiry.poll_at(EXPIRES_AT_MS),
+            Some(ServerMessage::AuthExpired)
+        ));
+        assert!(expiry.is_expired());
+        assert!(expiry.poll_at(EXPIRES_AT_MS + 1).is_none());
+        assert!(expiry.next_deadline().is_none());
+    }
+
+    #[test]
+    fn renew_restores_access() {
+        let mut expiry = AuthExpiry::new(Some(EXPIRES_AT_MS));
+        expiry.poll_at(EXPIRES_AT_MS);
+        assert!(expiry.is_expired());
+
+        expiry.renew(Some(EXPIRES_AT_MS * 2));
+        assert!(!expiry.is_expired());
+        assert!(expiry.poll_at(EXPIRES_AT_MS).is_none());
+    }
+
+    #[test]
+    fn tokens_without_expiry_never_expire() {
+        let mut expiry = AuthExpiry::new(None);
+        assert!(expiry.poll_at(u64::MAX).is_none());
+        assert!(expiry.next_deadline().is_none());
+    }
+}
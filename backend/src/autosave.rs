@@ -0,0 +1,234 @@
+//! Debounced game saves.
+//!
+//! Every state-changing action schedules a save of its game. The first change is saved
+//! right away; changes arriving after that are coalesced so a game is written at most once
+//! per `SAVE_DEBOUNCE`, with a trailing save for the last of them. Failed saves are retried
+//! with backoff, and a game whose saves keep failing stays scheduled until one succeeds.
+//!
+//! A save appends the events logged since the last one. A full snapshot is written on a
+//! game's first save, after every `SNAPSHOT_EVERY` events, and by `save_now`.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, Weak};
+use std::time::Duration;
+
+use anyhow::Result;
+use log::{error, info, warn};
+use tokio::sync::{Mutex, Notify};
+
+use crate::game_timer::now_ms;
+use crate::model::error::ErrorCode;
+use crate::model::game::Game;
+use crate::model::server_message::{ServerMessage, send_msg};
+use crate::persistence::PersistenceClient;
+use crate::server::SharedGame;
+
+/// Shortest gap between two saves of the same game
+pub const SAVE_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Events between periodic snapshots
+pub const SNAPSHOT_EVERY: u64 = 50;
+
+/// Delays before each retry of a failed save
+const RETRY_BACKOFF: [Duration; 3] = [
+    Duration::from_millis(250),
+    Duration::from_secs(1),
+    Duration::from_secs(3),
+];
+
+/// Save state for one game
+#[derive(Default)]
+struct GameSaver {
+    /// Holds a permit while the game has changes that haven't been saved
+    dirty: Notify,
+    /// The event sequence number of the last snapshot written, if any. Held for the whole
+    /// of each save, so saves of one game never overlap or reorder.
+    last_snapshot: Mutex<Option<u64>>,
+    /// Set when the game is deleted; checked under `last_snapshot` before every save
+    stopped: AtomicBool,
+}
+
+pub struct Autosave {
+    persistence: Arc<PersistenceClient>,
+    debounce: Duration,
+    savers: StdMutex<HashMap<String, Arc<GameSaver>>>,
+}
+
+impl Autosave {
+    pub fn new(persistence: Arc<PersistenceClient>, debounce: Duration) -> Self {
+        Autosave {
+            persistence,
+            debounce,
+            savers: StdMutex::new(HashMap::new()),
+        }
+    }
+
+    /// Mark a game as changed. Call after releasing the game's lock.
+    pub fn schedule(self: &Arc<Self>, game_code: &str, game: &SharedGame) {
+        let mut savers = self.savers.lock().unwrap();
+        let saver = match savers.get(game_code) {
+            Some(saver) => saver.clone(),
+            None => {
+                let saver = Arc::new(GameSaver::default());
+                savers.insert(game_code.to_string(), saver.clone());
+                tokio::spawn(self.clone().run(saver.clone(), Arc::downgrade(game)));
+                saver
+            }
+        };
+        saver.dirty.notify_one();
+    }
+
+    /// Save a game's pending events and a snapshot now, waiting for any save already in
+    /// progress to finish first
+    pub async fn save_now(&self, game: &SharedGame) -> Result<()> {
+        let game_code = game.lock().await.game_code.clone();
+        let saver = self.savers.lock().unwrap().get(&game_code).cloned();
+        match saver {
+            Some(saver) => {
+                let mut last_snapshot = saver.last_snapshot.lock().await;
+                self.save(game, &mut last_snapshot, true).await
+            }
+            None => self.save(game, &mut None, true).await,
+        }
+    }
+
+    /// Stop saving a game that's being deleted, waiting for any save in progress to finish.
+    /// Call after removing the game from the app state, so nothing schedules it again.
+    pub async fn stop(&self, game_code: &str) {
+        let saver = self.savers.lock().unwrap().remove(game_code);
+        if let Some(saver) = saver {
+            let _last_snapshot = saver.last_snapshot.lock().await;
+            saver.stopped.store(true, Ordering::Relaxed);
+            // Wake the worker so it sees the flag and exits
+            saver.dirty.notify_one();
+        }
+    }
+
+    /// Save a game each time it's marked changed, no more often than the debounce allows.
+    /// Ends once the game is dropped or stopped.
+    async fn run(self: Arc<Self>, saver: Arc<GameSaver>, game: Weak<Mutex<Game>>) {
+        let mut failing = false;
+        loop {
+            saver.dirty.notified().await;
+            let Some(game) = game.upgrade() else {
+                return;
+            };
+
+            let result = {
+                let mut last_snapshot = saver.last_snapshot.lock().await;
+                if saver.stopped.load(Ordering::Relaxed) {
+                    return;
+                }
+                self.save_with_retry(&game, &mut last_snapshot).await
+            };
+            match result {
+                Ok(()) => {
+                    if failing {
+                        info!(
+                            "Saving game {} succeeded again",
+                            game.lock().await.game_code
+                        );
+                    }
+                    failing = false;
+                }
+                Err(e) => {
+                    let game = game.lock().await;
+                    error!("Giving up on saving game {} for now: {e}", game.game_code);
+                    // Tell the host once per run of failures, not on every attempt
+                    if !failing && let Some(host_tx) = &game.host_tx {
+                        send_msg(
+                            host_tx,
+                            ServerMessage::error(
+                                ErrorCode::SaveFailed,
+                                format!("Failed to save game state: {e}"),
+                            ),
+                        );
+                    }
+                    failing = true;
+                    // Stay scheduled so the latest state is saved once storage recovers
+                    saver.dirty.notify_one();
+                }
+            }
+            drop(game);
+
+            // Changes during the save or this pause leave a permit for the trailing save
+            tokio::time::sleep(self.debounce).await;
+        }
+    }
+
+    async fn save_with_retry(
+        &self,
+        game: &SharedGame,
+        last_snapshot: &mut Option<u64>,
+    ) -> Result<()> {
+        let mut backoff = RETRY_BACKOFF.iter();
+        loop {
+            match self.save(game, last_snapshot, false).await {
+                Ok(()) => return Ok(()),
+                Err(e) => match backoff.next() {
+                    Some(delay) => {
+                        warn!("Failed to save game state, retrying in {delay:?}: {e}");
+                        tokio::time::sleep(*delay).await;
+                    }
+                    None => return Err(e),
+                },
+            }
+        }
+    }
+
+    /// Append a game's pending events, then write a snapshot if one is due or forced.
+    /// Events whose append fails are put back to be retried.
+    async fn save(
+        &self,
+        game: &SharedGame,
+        last_snapshot: &mut Option<u64>,
+        force_snapshot: bool,
+    ) -> Result<()> {
+        let (user_id, game_code, events, summary, snapshot) = {
+            let mut game = game.lock().await;
+            let snapshot_due = force_snapshot
+                || last_snapshot.is_none_or(|seq| game.event_seq >= seq + SNAPSHOT_EVERY);
+            let events = game.take_pending_events();
+            // The summary changes with every event; a game with none yet still needs one
+            let summary = (!events.is_empty() || game.event_seq == 0).then(|| {
+                game.summary(Some(events.last().map_or_else(now_ms, |event| event.at_ms)))
+            });
+            (
+                game.host_user_id.clone(),
+                game.game_code.clone(),
+                events,
+                summary,
+                snapshot_due.then(|| (game.to_game_state(), game.event_seq, game.created_at_ms)),
+            )
+        };
+
+        if let Err(e) = self
+            .persistence
+            .append_events(&user_id, &game_code, &events)
+            .await
+        {
+            game.lock().await.requeue_events(events);
+            return Err(e);
+        }
+
+        if let Some(summary) = summary
+            && let Err(e) = self
+                .persistence
+                .save_summary(&user_id, &game_code, &summary)
+                .await
+        {
+            // Appending the same batch again replaces it, so the retry rewrites both
+            game.lock().await.requeue_events(events);
+            return Err(e);
+        }
+
+        if let Some((state, event_seq, created_at_ms)) = snapshot {
+            self.persistence
+                .save_game_state(&user_id, &game_code, &state, event_seq, created_at_ms)
+                .await?;
+            *last_snapshot = Some(event_seq);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,12 @@
+//! Writes the protocol JSON Schema to `schema/protocol.schema.json`.
+
+use std::path::Path;
+
+use backend::schema::{SCHEMA_PATH, protocol_schema};
+
+fn main() -> std::io::Result<()> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(SCHEMA_PATH);
+    std::fs::write(&path, protocol_schema())?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
@@ -1,126 +1,193 @@
+use crate::model::error::{ActionError, ErrorCode};
 use crate::model::game::Game;
-use crate::model::server_message::{ServerMessage, send_msg};
+use crate::model::server_message::TimerEventKind;
 use crate::server::AppState;
 use log::error;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Current server time in milliseconds since the Unix epoch.
+/// Timer deadlines sent to clients are expressed in this clock.
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
 
-/// Broadcast a TimerTick to all connected clients (host + all teams)
-fn broadcast_timer_tick(game: &Game, seconds_remaining: u32) {
-    let msg = ServerMessage::TimerTick { seconds_remaining };
-    if let Some(host_tx) = &game.host_tx {
-        send_msg(host_tx, msg.clone());
-    }
-    for team_tx in game.teams_tx.values() {
-        send_msg(team_tx, msg.clone());
-    }
+/// Whole seconds left until `deadline_ms`, rounded up so the display never hits 0 early.
+pub fn seconds_until(deadline_ms: u64) -> u32 {
+    let remaining_ms = deadline_ms.saturating_sub(now_ms());
+    remaining_ms.div_ceil(1000) as u32
 }
 
-/// Start/resume timer and spawn tick task. Called while holding game lock.
-/// Does not broadcast - caller should broadcast after releasing lock.
+/// Start/resume timer and spawn the expiry task. Called while holding game lock.
+/// Sends a `Started`/`Resumed` timer event; caller should broadcast state after releasing lock.
+/// Clients count down locally from the deadline included in the broadcast state.
 pub fn start_timer(game: &mut Game, app_state: &Arc<AppState>, game_code: &str) {
     // Cancel existing timer if running
     if let Some(handle) = game.timer_abort_handle.take() {
         handle.abort();
     }
 
+    // Freeze the remaining time before restarting so a running timer resumes where it was
+    let now = now_ms();
+    game.timer_seconds_remaining = game.seconds_remaining();
+    game.timer_expired_at_ms = None;
+    game.end_timer_run(now);
+
     // Set timer value: use current remaining time if > 0, otherwise use question's timer_duration
     if game.timer_seconds_remaining.is_none() || game.timer_seconds_remaining == Some(0) {
         game.timer_seconds_remaining = Some(game.current_question().timer_duration);
+        // A fresh countdown measures latency from zero again
+        game.timer_elapsed_ms = 0;
+    }
+
+    let remaining = game.timer_seconds_remaining.unwrap_or(0);
+    if remaining == 0 {
+        // Nothing to count down (zero-length question)
+        game.timer_running = false;
+        game.timer_deadline_ms = None;
+        return;
     }
 
     // Start timer (opens submissions)
+    let resumed = game.timer_elapsed_ms > 0;
     game.timer_running = true;
+    game.timer_deadline_ms = Some(now + remaining as u64 * 1000);
+    game.timer_run_started_at_ms = Some(now);
+    spawn_expiry_task(game, app_state, game_code, None);
+
+    game.broadcast_timer_event(if resumed {
+        TimerEventKind::Resumed
+    } else {
+        TimerEventKind::Started
+    });
+}
 
-    // Spawn timer tick task if there's time remaining
-    if game.timer_seconds_remaining.unwrap_or(0) > 0 {
-        let app_state2 = app_state.clone();
-        let game_code2 = game_code.to_string();
-
-        let task = tokio::spawn(async move {
-            loop {
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-
-                // Lock, decrement, broadcast tick, check if done
-                let (should_continue, tick_msg, final_state) = {
-                    let mut games_map = app_state2.games.lock().await;
-                    let Some(game) = games_map.get_mut(&game_code2) else {
-                        error!("Tried to tick game timer, but game no longer exists!");
-                        break; // Game no longer exists
-                    };
+/// Spawn the task that sends countdown warnings (and ticks, to version 1 clients) and closes
+/// submissions once the deadline passes, replacing any existing one. Called while holding
+/// game lock. A warning fires when its threshold is crossed between wakes; `prev_remaining_ms`
+/// seeds the remaining time before the first wake, so a threshold skipped by an adjustment
+/// still fires.
+fn spawn_expiry_task(
+    game: &mut Game,
+    app_state: &Arc<AppState>,
+    game_code: &str,
+    mut prev_remaining_ms: Option<u64>,
+) {
+    if let Some(handle) = game.timer_abort_handle.take() {
+        handle.abort();
+    }
 
-                    if !game.timer_running {
-                        error!("Tried to tick game timer, but timer shouldn't be running!");
-                        break; // Timer was paused
-                    }
+    let app_state2 = app_state.clone();
+    let game_code2 = game_code.to_string();
 
-                    let Some(remaining) = game.timer_seconds_remaining else {
-                        error!("Tried to tick game timer, but timer_seconds_remaining was None!");
-                        break; // No timer set
-                    };
+    let task = tokio::spawn(async move {
+        loop {
+            // Sleep until the next warning or the deadline, then re-check under the lock
+            let sleep_ms = {
+                let Some(game) = app_state2.game(&game_code2).await else {
+                    error!("Tried to expire game timer, but game no longer exists!");
+                    break;
+                };
+                let mut game = game.lock().await;
 
-                    if remaining == 0 {
-                        error!("Tried to tick game timer, but it was already at zero!");
-                        break; // Already at 0
-                    }
+                if !game.timer_running {
+                    error!("Tried to expire game timer, but timer shouldn't be running!");
+                    break;
+                }
 
-                    let new_remaining = remaining - 1;
-                    game.timer_seconds_remaining = Some(new_remaining);
-
-                    if new_remaining == 0 {
-                        // Timer expired - close submissions
-                        game.timer_running = false;
-                        game.timer_abort_handle = None;
-                        let final_state = game.to_game_state();
-                        (false, None, Some(final_state))
-                    } else {
-                        // Continue ticking
-                        let tick_msg = ServerMessage::TimerTick {
-                            seconds_remaining: new_remaining,
-                        };
-                        (true, Some(tick_msg), None)
-                    }
+                let Some(deadline) = game.timer_deadline_ms else {
+                    error!("Tried to expire game timer, but timer_deadline_ms was None!");
+                    break;
                 };
-                // Lock released
 
-                // Broadcast tick or final state
-                if let Some(ServerMessage::TimerTick { seconds_remaining }) = tick_msg {
-                    let games_map = app_state2.games.lock().await;
-                    if let Some(game) = games_map.get(&game_code2) {
-                        broadcast_timer_tick(game, seconds_remaining);
-                    }
+                let now = now_ms();
+                if now >= deadline {
+                    // Timer expired - close submissions
+                    game.timer_running = false;
+                    game.timer_seconds_remaining = Some(0);
+                    game.timer_deadline_ms = None;
+                    game.timer_expired_at_ms = Some(deadline);
+                    game.end_timer_run(deadline);
+                    game.timer_abort_handle = None;
+                    game.update_timer_window();
+                    game.broadcast_timer_event(TimerEventKind::Expired);
+                    game.broadcast_game_state();
+                    break;
                 }
 
-                if final_state.is_some() {
-                    let games_map = app_state2.games.lock().await;
-                    if let Some(game) = games_map.get(&game_code2) {
-                        game.broadcast_game_state();
+                let remaining_ms = deadline - now;
+                let warnings = game.game_settings.timer_warning_seconds.clone();
+                if let Some(prev_remaining_ms) = prev_remaining_ms {
+                    for threshold_seconds in &warnings {
+                        let threshold_ms = *threshold_seconds as u64 * 1000;
+                        if remaining_ms <= threshold_ms && threshold_ms < prev_remaining_ms {
+                            game.broadcast_timer_event(TimerEventKind::Warning {
+                                threshold_seconds: *threshold_seconds,
+                            });
+                        }
                     }
                 }
-
-                if !should_continue {
-                    break;
+                prev_remaining_ms = Some(remaining_ms);
+
+                let next_warning_ms = warnings
+                    .iter()
+                    .map(|seconds| *seconds as u64 * 1000)
+                    .filter(|threshold_ms| *threshold_ms < remaining_ms)
+                    .max()
+                    .unwrap_or(0);
+                let sleep_ms = remaining_ms - next_warning_ms;
+
+                // Version 1 clients count down from a tick each time the seconds change
+                if game.has_legacy_connections() {
+                    game.send_timer_tick();
+                    let next_tick_ms = match remaining_ms % 1000 {
+                        0 => 1000,
+                        ms => ms,
+                    };
+                    sleep_ms.min(next_tick_ms)
+                } else {
+                    sleep_ms
                 }
-            }
-        });
+            };
+            // Lock released
 
-        // Store abort handle
-        game.timer_abort_handle = Some(task.abort_handle());
-    }
+            tokio::time::sleep(tokio::time::Duration::from_millis(sleep_ms)).await;
+        }
+    });
+
+    // Store abort handle
+    game.timer_abort_handle = Some(task.abort_handle());
 }
 
-/// Pause timer: stop timer task and close submissions. Called while holding game lock.
-/// Does not broadcast - caller should broadcast after releasing lock.
-pub fn pause_timer(game: &mut Game) {
+/// Stop the expiry task and freeze the remaining time, closing submissions.
+fn halt_timer(game: &mut Game) {
     // Cancel timer task if running
     if let Some(handle) = game.timer_abort_handle.take() {
         handle.abort();
     }
 
-    // Close submissions
+    // Freeze the remaining time and close submissions
+    game.timer_seconds_remaining = game.seconds_remaining();
+    game.end_timer_run(now_ms());
+    game.timer_deadline_ms = None;
     game.timer_running = false;
 }
 
-/// Reset timer: stop timer task, reset to current question's duration, close submissions.
+/// Pause timer: stop expiry task and close submissions. Called while holding game lock.
+/// Sends a `Paused` timer event if the timer was running; caller should broadcast state
+/// after releasing lock.
+pub fn pause_timer(game: &mut Game) {
+    let was_running = game.timer_running;
+    halt_timer(game);
+    if was_running {
+        game.broadcast_timer_event(TimerEventKind::Paused);
+    }
+}
+
+/// Reset timer: stop expiry task, reset to current question's duration, close submissions.
 /// Called while holding game lock. Does not broadcast - caller should broadcast after releasing lock.
 pub fn reset_timer(game: &mut Game) {
     // Cancel timer task if running
@@ -130,5 +197,50 @@ pub fn reset_timer(game: &mut Game) {
 
     // Reset to current question's timer duration
     game.timer_seconds_remaining = Some(game.current_question().timer_duration);
+    game.timer_deadline_ms = None;
+    game.timer_expired_at_ms = None;
+    game.timer_elapsed_ms = 0;
+    game.timer_run_started_at_ms = None;
     game.timer_running = false;
 }
+
+/// Adjust the running timer's remaining time by `delta_seconds`. Submissions stay open.
+/// The result is clamped at zero, which expires the timer.
+/// Called while holding game lock. Sends an `Adjusted` timer event (plus `Expired` if it hit
+/// zero); caller should broadcast state after releasing lock.
+pub fn adjust_timer(
+    game: &mut Game,
+    app_state: &Arc<AppState>,
+    game_code: &str,
+    delta_seconds: i32,
+) -> Result<(), ActionError> {
+    let (true, Some(deadline)) = (game.timer_running, game.timer_deadline_ms) else {
+        return Err(ActionError::new(
+            ErrorCode::UnexpectedAction,
+            "Timer is not running",
+        ));
+    };
+
+    let new_deadline = deadline
+        .saturating_add_signed(delta_seconds as i64 * 1000)
+        .max(now_ms());
+
+    if seconds_until(new_deadline) == 0 {
+        // Timer expired - close submissions
+        halt_timer(game);
+        game.timer_seconds_remaining = Some(0);
+        game.timer_expired_at_ms = Some(now_ms());
+        game.update_timer_window();
+        game.broadcast_timer_event(TimerEventKind::Adjusted { delta_seconds });
+        game.broadcast_timer_event(TimerEventKind::Expired);
+    } else {
+        // Re-arm the expiry task so a shortened deadline isn't missed, and so warnings the
+        // adjustment skipped past still fire
+        let prev_remaining_ms = deadline.saturating_sub(now_ms());
+        game.timer_deadline_ms = Some(new_deadline);
+        game.update_timer_window();
+        spawn_expiry_task(game, app_state, game_code, Some(prev_remaining_ms));
+        game.broadcast_timer_event(TimerEventKind::Adjusted { delta_seconds });
+    }
+    Ok(())
+}
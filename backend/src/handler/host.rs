@@ -1,26 +1,155 @@
 use crate::{
-    game_timer::{pause_timer, reset_timer, start_timer},
+    auth::AuthResult,
+    auth_expiry::AuthExpiry,
+    game_timer::{adjust_timer, now_ms, pause_timer, reset_timer, start_timer},
     heartbeat::{HeartbeatState, PING_INTERVAL},
+    limits::{self, Verdict},
     model::{
-        client_message::{ClientMessage, HostAction},
+        client_message::{ClientEnvelope, ClientKind, ClientMessage, HostAction},
+        error::{ActionError, ErrorCode},
+        event::EventKind,
         game::Game,
-        server_message::{ServerMessage, send_msg},
-        types::GameSettings,
+        protocol::{self, Handshake},
+        server_message::{GameState, SavedGameSummary, ServerMessage, send_ack, send_msg},
     },
-    server::{AppState, Rx, Tx},
+    outbox,
+    persistence::ActiveGame,
+    server::{self, AppState, Rx, SharedGame, Tx},
 };
-use futures_util::{SinkExt, StreamExt};
+use futures_util::{SinkExt, StreamExt, future::join_all};
 use log::*;
-use std::sync::Arc;
-use tokio::{net::TcpStream, sync::mpsc};
-use tokio_tungstenite::{WebSocketStream, tungstenite::Message};
+use rand::Rng;
+use std::{cmp::Reverse, sync::Arc};
+use tokio::{net::TcpStream, sync::Mutex};
+use tokio_tungstenite::{
+    WebSocketStream,
+    tungstenite::{self, Error, Message, protocol::frame::coding::CloseCode},
+};
+
+fn generate_code() -> String {
+    rand::rng()
+        .sample_iter(&rand::distr::Alphabetic)
+        .take(4)
+        .map(|c| (c as char).to_ascii_uppercase())
+        .collect()
+}
+
+/// Serve a new host connection until it creates, reclaims or joins (as staff) a game,
+/// answering any saved-game requests (`ListGames`, `DeleteGame`) it sends first
+pub async fn start_host(
+    app_state: Arc<AppState>,
+    mut ws_stream: WebSocketStream<TcpStream>,
+    auth: AuthResult,
+    handshake: Handshake,
+    mut action: HostAction,
+    mut request_id: Option<String>,
+) -> tungstenite::Result<()> {
+    let encoding = handshake.encoding;
+    let mut limiter = app_state.limits.limiter_for(ClientKind::Host);
+    loop {
+        let reply = match action {
+            HostAction::CreateGame {
+                game_code,
+                copy_settings_from,
+            } => {
+                let code = game_code.unwrap_or_else(generate_code);
+                create_game(
+                    app_state,
+                    ws_stream,
+                    code,
+                    auth,
+                    copy_settings_from,
+                    handshake,
+                    request_id,
+                )
+                .await;
+                return Ok(());
+            }
+            HostAction::JoinAsStaff { game_code } => {
+                join_as_staff(app_state, ws_stream, game_code, auth, handshake, request_id).await;
+                return Ok(());
+            }
+            action @ (HostAction::ListGames | HostAction::DeleteGame { .. }) => {
+                saved_games_request(&app_state, &auth.user_id, action).await
+            }
+            action => {
+                warn!("Expected CreateGame from new Host connection, instead got: {action:?}");
+                let error_message = ServerMessage::error(
+                    ErrorCode::UnexpectedAction,
+                    "First action must be CreateGame or JoinAsStaff",
+                )
+                .for_request(request_id);
+                ws_stream.send(encoding.encode(&error_message)).await?;
+                return Ok(());
+            }
+        };
+        match reply {
+            Ok(reply) => {
+                ws_stream.send(encoding.encode(&reply)).await?;
+                if let Some(request_id) = request_id {
+                    let ack = ServerMessage::Ack { request_id };
+                    ws_stream.send(encoding.encode(&ack)).await?;
+                }
+            }
+            Err(e) => {
+                let error_message = ServerMessage::error(e.code, e.message).for_request(request_id);
+                ws_stream.send(encoding.encode(&error_message)).await?;
+            }
+        }
 
-pub async fn create_game(
+        // Wait for the next request, or the game to host
+        (action, request_id) = loop {
+            let Some(frame) = server::next_frame(&mut ws_stream).await? else {
+                return Ok(());
+            };
+            match limiter.check() {
+                Verdict::Allow => {}
+                Verdict::Reject => {
+                    let error_message = limits::rate_limited_error(&frame);
+                    ws_stream.send(encoding.encode(&error_message)).await?;
+                    continue;
+                }
+                Verdict::Disconnect => {
+                    warn!(
+                        "Disconnecting host {} before CreateGame: too many messages",
+                        auth.user_id
+                    );
+                    return Ok(());
+                }
+            }
+            match protocol::decode_client_message(&frame, handshake.protocol_version) {
+                Some(Ok(ClientEnvelope {
+                    message: ClientMessage::Host(action),
+                    request_id,
+                })) => break (action, request_id),
+                _ => {
+                    warn!("Expected a Host message before CreateGame");
+                    let error_message = ServerMessage::error(
+                        ErrorCode::InvalidMessage,
+                        "Unexpected message type: expected Host message",
+                    );
+                    ws_stream.send(encoding.encode(&error_message)).await?;
+                    return Ok(());
+                }
+            }
+        };
+    }
+}
+
+async fn create_game(
     app_state: Arc<AppState>,
     mut ws_stream: WebSocketStream<TcpStream>,
     game_code: String,
-    user_id: String,
+    auth: AuthResult,
+    copy_settings_from: Option<String>,
+    handshake: Handshake,
+    request_id: Option<String>,
 ) {
+    let AuthResult {
+        user_id,
+        expires_at_ms,
+        ..
+    } = auth;
     app_state
         .timer
         .lock()
@@ -29,19 +158,73 @@ pub async fn create_game(
         .await
         .unwrap_or_else(|e| error!("{e:?}"));
 
-    let (tx, rx) = mpsc::unbounded_channel::<Message>();
-    let mut games_map = app_state.games.lock().await;
+    // Index the game so a restarted server brings it back before the host returns. Codes
+    // are only unique per user in storage, so another user's active game keeps its code.
+    match app_state
+        .persistence
+        .add_active_game(&user_id, &game_code)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            info!("Cannot create game {game_code}: another user's active game has the code");
+            let error_msg = ServerMessage::error(
+                ErrorCode::GameCodeTaken,
+                format!("Game code '{}' already exists", game_code),
+            )
+            .for_request(request_id);
+            let _ = ws_stream.send(handshake.encoding.encode(&error_msg)).await;
+            return;
+        }
+        Err(e) => warn!("Failed to add game {game_code} to the active games index: {e}"),
+    }
+
+    let (tx, rx) = outbox::channel(handshake.encoding);
+
+    // A copy always starts a new game, so it skips reclaiming and restoring
+    if let Some(source_code) = copy_settings_from {
+        let source = match settings_to_copy(&app_state, &user_id, &source_code, &game_code).await {
+            Ok(source) => source,
+            Err(e) => {
+                info!("Cannot create game {game_code} from {source_code}: {e}");
+                let error_msg = ServerMessage::error(e.code, e.message).for_request(request_id);
+                let _ = ws_stream.send(handshake.encoding.encode(&error_msg)).await;
+                return;
+            }
+        };
+        info!("Creating game {game_code} with the settings of {source_code}");
+        let mut game = Game::new(game_code.clone(), tx.clone(), user_id);
+        game.copy_settings_from(&source);
+        let game = Arc::new(Mutex::new(game));
+        // Save it right away, so it's listed even if the host leaves without changing it
+        app_state.autosave.schedule(&game_code, &game);
+        host_game(
+            app_state,
+            ws_stream,
+            rx,
+            tx,
+            game,
+            expires_at_ms,
+            handshake,
+            request_id,
+        )
+        .await;
+        return;
+    }
 
     // Check if game exists in memory
-    if let Some(existing_game) = games_map.get_mut(&game_code) {
+    if let Some(existing_game) = app_state.game(&game_code).await {
+        let mut existing_game = existing_game.lock().await;
         // If game has a host, return error
         if existing_game.host_tx.is_some() {
             info!("Cannot create/reclaim game {game_code}: host already connected");
-            let error_msg =
-                ServerMessage::error(format!("Game '{}' already has an active host", game_code));
-            let msg = serde_json::to_string(&error_msg).unwrap();
-            drop(games_map);
-            let _ = ws_stream.send(Message::text(msg)).await;
+            let error_msg = ServerMessage::error(
+                ErrorCode::GameAlreadyHasHost,
+                format!("Game '{}' already has an active host", game_code),
+            )
+            .for_request(request_id);
+            drop(existing_game);
+            let _ = ws_stream.send(handshake.encoding.encode(&error_msg)).await;
             return;
         }
 
@@ -51,249 +234,577 @@ pub async fn create_game(
                 "User {user_id} cannot reclaim game {game_code}: owned by {}",
                 existing_game.host_user_id
             );
-            let error_msg =
-                ServerMessage::error(format!("Game code '{}' already exists", game_code));
-            let msg = serde_json::to_string(&error_msg).unwrap();
-            drop(games_map);
-            let _ = ws_stream.send(Message::text(msg)).await;
+            let error_msg = ServerMessage::error(
+                ErrorCode::GameCodeTaken,
+                format!("Game code '{}' already exists", game_code),
+            )
+            .for_request(request_id);
+            drop(existing_game);
+            let _ = ws_stream.send(handshake.encoding.encode(&error_msg)).await;
             return;
         }
 
         info!("Host reclaiming existing game: {game_code}");
         existing_game.set_host_tx(tx.clone());
-        let msg = ServerMessage::GameState {
-            state: existing_game.to_game_state(),
-        };
-        drop(games_map);
-        send_msg(&tx, msg);
-        handle_host(ws_stream, app_state, rx, tx, game_code, user_id).await;
+        existing_game.set_host_protocol(handshake.protocol_version);
+        existing_game.send_host_state();
+        drop(existing_game);
+        send_ack(&tx, request_id);
+        handle_host(
+            ws_stream,
+            app_state,
+            rx,
+            tx,
+            game_code,
+            user_id,
+            AuthExpiry::new(expires_at_ms),
+            handshake.protocol_version,
+        )
+        .await;
         return;
     }
 
-    // Game not in memory - try to restore from S3
-    drop(games_map); // Release lock while doing async S3 call
-
-    match app_state
-        .persistence
-        .load_game_state(&user_id, &game_code)
-        .await
-    {
-        Ok(Some(state)) => {
-            // Restore game from S3
-            info!("Restoring game {game_code} from S3 for user {user_id}");
-            let game =
-                Game::from_saved_state(user_id.clone(), game_code.clone(), tx.clone(), state);
-            let msg = ServerMessage::GameState {
-                state: game.to_game_state(),
-            };
-            app_state.games.lock().await.insert(game_code.clone(), game);
-            send_msg(&tx, msg);
-            handle_host(ws_stream, app_state, rx, tx, game_code, user_id).await;
+    // Game not in memory - try to restore it from storage
+    match app_state.persistence.load_game(&user_id, &game_code).await {
+        Ok(Some(saved)) => {
+            // Restore game from its snapshot and event log
+            info!(
+                "Restoring game {game_code} for user {user_id}, replaying {} events",
+                saved.events.len()
+            );
+            let game = Game::restore(user_id, game_code, Some(tx.clone()), saved);
+            let game = Arc::new(Mutex::new(game));
+            host_game(
+                app_state,
+                ws_stream,
+                rx,
+                tx,
+                game,
+                expires_at_ms,
+                handshake,
+                request_id,
+            )
+            .await;
         }
         Ok(None) => {
             // No saved state - create new game
             info!("Creating new game: {game_code}");
-            let game = Game::new(game_code.clone(), tx.clone(), user_id.clone());
-            let msg = ServerMessage::GameState {
-                state: game.to_game_state(),
-            };
-            app_state.games.lock().await.insert(game_code.clone(), game);
-            send_msg(&tx, msg);
-            handle_host(ws_stream, app_state, rx, tx, game_code, user_id).await;
+            let game = Game::new(game_code, tx.clone(), user_id);
+            let game = Arc::new(Mutex::new(game));
+            host_game(
+                app_state,
+                ws_stream,
+                rx,
+                tx,
+                game,
+                expires_at_ms,
+                handshake,
+                request_id,
+            )
+            .await;
         }
         Err(e) => {
             // Error loading (e.g., incompatible save format)
-            warn!("Error restoring game {game_code} from S3: {e}");
-            let error_msg = ServerMessage::error(e.to_string());
-            let msg = serde_json::to_string(&error_msg).unwrap();
-            let _ = ws_stream.send(Message::text(msg)).await;
+            warn!("Error restoring game {game_code} from storage: {e}");
+            let error_msg =
+                ServerMessage::error(ErrorCode::LoadFailed, e.to_string()).for_request(request_id);
+            let _ = ws_stream.send(handshake.encoding.encode(&error_msg)).await;
         }
     }
 }
 
-/// Process a host action that mutates game state.
-/// Returns Ok(should_persist) on success (state will be broadcast to all clients),
-/// or Err(message) on failure (error will be sent to host only).
-fn process_host_action(
-    action: HostAction,
-    game: &mut Game,
+/// Register a game that isn't in memory yet and serve its host until they disconnect
+#[allow(clippy::too_many_arguments)]
+async fn host_game(
+    app_state: Arc<AppState>,
+    ws_stream: WebSocketStream<TcpStream>,
+    rx: Rx,
+    tx: Tx,
+    game: SharedGame,
+    expires_at_ms: Option<u64>,
+    handshake: Handshake,
+    request_id: Option<String>,
+) {
+    let (user_id, game_code) = {
+        let mut game = game.lock().await;
+        game.set_host_protocol(handshake.protocol_version);
+        game.send_host_state();
+        (game.host_user_id.clone(), game.game_code.clone())
+    };
+    send_ack(&tx, request_id);
+    app_state.games.lock().await.insert(game_code.clone(), game);
+    handle_host(
+        ws_stream,
+        app_state,
+        rx,
+        tx,
+        game_code,
+        user_id,
+        AuthExpiry::new(expires_at_ms),
+        handshake.protocol_version,
+    )
+    .await;
+}
+
+/// Connect an invited user to a game in memory and serve them until they disconnect
+async fn join_as_staff(
+    app_state: Arc<AppState>,
+    mut ws_stream: WebSocketStream<TcpStream>,
+    game_code: String,
+    auth: AuthResult,
+    handshake: Handshake,
+    request_id: Option<String>,
+) {
+    let user_id = auth.user_id;
+    let Some(game) = app_state.game(&game_code).await else {
+        info!("User {user_id} cannot join game {game_code} as staff: not found");
+        let error_msg = ServerMessage::error(ErrorCode::GameNotFound, "Game code not found")
+            .for_request(request_id);
+        let _ = ws_stream.send(handshake.encoding.encode(&error_msg)).await;
+        return;
+    };
+
+    let (tx, rx) = outbox::channel(handshake.encoding);
+    {
+        let mut game = game.lock().await;
+        match game.add_staff_connection(&user_id, tx.clone(), handshake.protocol_version) {
+            Ok(role) => info!("User {user_id} joined game {game_code} as {role:?}"),
+            Err(e) => {
+                info!("User {user_id} cannot join game {game_code} as staff: {e}");
+                drop(game);
+                let error_msg = ServerMessage::error(e.code, e.message).for_request(request_id);
+                let _ = ws_stream.send(handshake.encoding.encode(&error_msg)).await;
+                return;
+            }
+        }
+        // A snapshot for the new connection, and the others see them connect
+        game.send_host_state();
+    }
+    send_ack(&tx, request_id);
+
+    app_state
+        .timer
+        .lock()
+        .await
+        .cancel_timer()
+        .await
+        .unwrap_or_else(|e| error!("{e:?}"));
+
+    handle_host(
+        ws_stream,
+        app_state,
+        rx,
+        tx,
+        game_code,
+        user_id,
+        AuthExpiry::new(auth.expires_at_ms),
+        handshake.protocol_version,
+    )
+    .await;
+}
+
+/// The game whose settings a new game copies: one of the host's games, live or saved.
+/// The new game's code must not be in use.
+async fn settings_to_copy(
     app_state: &Arc<AppState>,
+    user_id: &str,
+    source_code: &str,
     game_code: &str,
-) -> anyhow::Result<bool> {
-    match action {
-        HostAction::CreateGame { .. } => anyhow::bail!("Game already created"),
-        HostAction::StartTimer => {
-            start_timer(game, app_state, game_code);
-            Ok(false)
+) -> Result<GameState, ActionError> {
+    let code_taken = || {
+        ActionError::new(
+            ErrorCode::GameCodeTaken,
+            format!("Game code '{game_code}' already exists"),
+        )
+    };
+    if app_state.game(game_code).await.is_some() {
+        return Err(code_taken());
+    }
+    if app_state
+        .persistence
+        .list_games(user_id)
+        .await
+        .is_ok_and(|codes| codes.iter().any(|code| code == game_code))
+    {
+        return Err(code_taken());
+    }
+
+    // A live game is newer than its last save
+    if let Some(source) = app_state.game(source_code).await {
+        let source = source.lock().await;
+        if source.host_user_id == user_id {
+            return Ok(source.to_game_state());
         }
-        HostAction::PauseTimer => {
-            pause_timer(game);
-            Ok(false)
+    }
+    match app_state.persistence.load_game(user_id, source_code).await {
+        Ok(Some(saved)) => {
+            let source = Game::restore(user_id.to_string(), source_code.to_string(), None, saved);
+            Ok(source.to_game_state())
         }
-        HostAction::ResetTimer => {
-            reset_timer(game);
-            Ok(false)
+        Ok(None) => Err(ActionError::new(
+            ErrorCode::GameNotFound,
+            format!("No saved game '{source_code}'"),
+        )),
+        Err(e) => Err(ActionError::new(ErrorCode::LoadFailed, e.to_string())),
+    }
+}
+
+/// Answer a request about the host's saved games. Allowed whether or not the connection
+/// is hosting a game yet.
+async fn saved_games_request(
+    app_state: &Arc<AppState>,
+    user_id: &str,
+    action: HostAction,
+) -> Result<ServerMessage, ActionError> {
+    match action {
+        HostAction::ListGames => {
+            let games = list_saved_games(app_state, user_id).await?;
+            Ok(ServerMessage::SavedGames { games })
         }
-        HostAction::NextQuestion => {
-            game.next_question();
-            Ok(true)
+        HostAction::DeleteGame { game_code } => {
+            delete_game(app_state, user_id, &game_code).await?;
+            Ok(ServerMessage::GameDeleted { game_code })
         }
-        HostAction::PrevQuestion => {
-            game.prev_question()?;
-            Ok(true)
+        action => Err(ActionError::new(
+            ErrorCode::Internal,
+            format!("Not a saved game request: {action:?}"),
+        )),
+    }
+}
+
+/// Summaries of a user's saved games, most recently played first. Games saved without a
+/// summary are loaded to build one.
+/// Saves that fail to load are left out rather than failing the whole list.
+async fn list_saved_games(
+    app_state: &Arc<AppState>,
+    user_id: &str,
+) -> Result<Vec<SavedGameSummary>, ActionError> {
+    let codes = app_state
+        .persistence
+        .list_games(user_id)
+        .await
+        .map_err(|e| {
+            warn!("Failed to list saved games for {user_id}: {e}");
+            ActionError::new(ErrorCode::LoadFailed, "Failed to list saved games")
+        })?;
+
+    let loads = codes.into_iter().map(|game_code| async move {
+        match app_state
+            .persistence
+            .load_summary(user_id, &game_code)
+            .await
+        {
+            Ok(Some(summary)) => return Some(summary),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to load summary of {user_id}'s game {game_code}: {e}"),
         }
-        HostAction::ScoreAnswer {
-            question_number,
-            team_name,
-            score,
-        } => {
-            anyhow::ensure!(
-                game.score_answer(question_number, &team_name, score),
-                "Failed to score answer for team '{team_name}'"
-            );
-            Ok(false)
+        match app_state.persistence.load_game(user_id, &game_code).await {
+            Ok(Some(saved)) => {
+                let last_played_ms = saved.last_played_ms();
+                let game = Game::restore(user_id.to_string(), game_code, None, saved);
+                Some(game.summary(last_played_ms))
+            }
+            // Deleted since it was listed
+            Ok(None) => None,
+            Err(e) => {
+                warn!("Leaving game {game_code} out of {user_id}'s saved games: {e}");
+                None
+            }
         }
-        HostAction::OverrideTeamScore {
-            team_name,
-            override_points,
-        } => {
-            anyhow::ensure!(
-                game.override_team_score(&team_name, override_points),
-                "Team '{team_name}' not found"
-            );
-            Ok(false)
+    });
+    let mut games: Vec<_> = join_all(loads).await.into_iter().flatten().collect();
+    games.sort_by_key(|game| Reverse(game.last_played_ms));
+    Ok(games)
+}
+
+/// Delete one of a user's games. If it's in memory it's closed first: its teams and
+/// watchers are disconnected and it stops being saved. Games with a host or staff
+/// connected are refused.
+async fn delete_game(
+    app_state: &Arc<AppState>,
+    user_id: &str,
+    game_code: &str,
+) -> Result<(), ActionError> {
+    let mut closed = false;
+    if let Some(game) = app_state.game(game_code).await {
+        let game = game.lock().await;
+        if game.host_user_id == user_id {
+            if game.has_host_connection() {
+                return Err(ActionError::new(
+                    ErrorCode::GameAlreadyHasHost,
+                    format!("Game '{game_code}' has a host connected"),
+                ));
+            }
+            game.close_all_connections("Game deleted");
+            app_state.games.lock().await.remove(game_code);
+            drop(game);
+            app_state.autosave.stop(game_code).await;
+            closed = true;
         }
-        HostAction::UpdateGameSettings {
-            default_timer_duration,
-            default_question_points,
-            default_bonus_increment,
-            default_question_type,
-            default_mc_config,
-            speed_bonus_enabled,
-            speed_bonus_num_teams,
-            speed_bonus_first_place_points,
-        } => {
-            let settings = GameSettings {
-                default_timer_duration,
-                default_question_points,
-                default_bonus_increment,
-                default_question_type,
-                default_mc_config,
-                speed_bonus_enabled,
-                speed_bonus_num_teams,
-                speed_bonus_first_place_points,
-            };
-            game.update_game_settings(settings);
-            Ok(false)
+    }
+
+    let entry = ActiveGame {
+        user_id: user_id.to_string(),
+        game_code: game_code.to_string(),
+    };
+    if let Err(e) = app_state.persistence.remove_active_games(&[entry]).await {
+        warn!("Failed to remove game {game_code} from the active games index: {e}");
+    }
+
+    match app_state.persistence.delete_game(user_id, game_code).await {
+        Ok(deleted) if deleted || closed => {
+            info!("Deleted game {game_code} for user {user_id}");
+            Ok(())
         }
-        HostAction::UpdateQuestionSettings {
-            question_number,
-            timer_duration,
-            question_points,
-            bonus_increment,
-            question_type,
-            speed_bonus_enabled,
-        } => {
-            game.update_question_settings(
-                question_number,
-                timer_duration,
-                question_points,
-                bonus_increment,
-                question_type,
-                speed_bonus_enabled,
-            )?;
-            Ok(false)
+        Ok(_) => Err(ActionError::new(
+            ErrorCode::GameNotFound,
+            format!("No saved game '{game_code}'"),
+        )),
+        Err(e) => {
+            warn!("Failed to delete game {game_code} for user {user_id}: {e}");
+            Err(ActionError::new(
+                ErrorCode::SaveFailed,
+                "Failed to delete saved game",
+            ))
         }
-        HostAction::UpdateTypeSpecificSettings {
-            question_number,
-            question_config,
-        } => {
-            game.update_type_specific_settings(question_number, question_config)?;
-            Ok(false)
+    }
+}
+
+/// Process a host action that mutates game state.
+/// On success the state is broadcast to all clients and the game is scheduled for saving;
+/// on failure the error is sent to the host only, with its current state for rollback.
+fn process_host_action(
+    action: HostAction,
+    game: &mut Game,
+    app_state: &Arc<AppState>,
+    game_code: &str,
+) -> Result<(), ActionError> {
+    match action {
+        HostAction::StartTimer => start_timer(game, app_state, game_code),
+        HostAction::PauseTimer => pause_timer(game),
+        HostAction::ResetTimer => reset_timer(game),
+        HostAction::AdjustTimer { delta_seconds } => {
+            return adjust_timer(game, app_state, game_code, delta_seconds);
         }
+        action => return game.apply_host_action(action),
+    }
+    Ok(())
+}
+
+/// Check a fresh token from a host-side connection and extend its access to the token's
+/// expiry. The token must be for the same user, and the owner must still be a host.
+async fn reauthenticate(
+    app_state: &Arc<AppState>,
+    game_code: &str,
+    user_id: &str,
+    token: &str,
+    expiry: &mut AuthExpiry,
+) -> Result<Option<u64>, ActionError> {
+    let auth = app_state.validator.validate(token).await.map_err(|e| {
+        warn!("Reauthentication failed for {user_id}: {e}");
+        ActionError::new(ErrorCode::AuthenticationRequired, "Invalid token")
+    })?;
+    if auth.user_id != user_id {
+        warn!(
+            "Reauthentication for {user_id} sent a token for {}",
+            auth.user_id
+        );
+        return Err(ActionError::new(
+            ErrorCode::NotAuthorized,
+            "Token is for a different user",
+        ));
+    }
+    let is_owner = match app_state.game(game_code).await {
+        Some(game) => game.lock().await.host_user_id == user_id,
+        None => false,
+    };
+    if is_owner && !auth.is_host {
+        return Err(ActionError::new(
+            ErrorCode::NotAuthorized,
+            "User is no longer authorized as a host",
+        ));
     }
+    info!("User {user_id} reauthenticated for game {game_code}");
+    expiry.renew(auth.expires_at_ms);
+    Ok(auth.expires_at_ms)
 }
 
+/// Handle a message from a host-side connection: the game owner's, or a staff member's.
+/// Actions are checked against the sender's role when they're applied, under the game
+/// lock, so a role change or removal takes effect from the next message. Once the
+/// connection's token expires, only `Reauthenticate` and requests that change nothing
+/// are accepted.
 async fn process_host_message(
-    text: &str,
+    frame: &Message,
     app_state: &Arc<AppState>,
     game_code: &str,
     user_id: &str,
+    expiry: &mut AuthExpiry,
     host_tx: &Tx,
+    protocol_version: u32,
 ) {
     // Parse message before acquiring lock
-    let action = match serde_json::from_str::<ClientMessage>(text) {
-        Ok(ClientMessage::Host(action)) => action,
-        Ok(_) => {
-            warn!("Got unexpected message type when Host message expected");
+    let envelope = match protocol::decode_client_message(frame, protocol_version) {
+        Some(Ok(envelope)) => envelope,
+        None => return,
+        Some(Err(e)) => {
+            warn!("Failed to parse message: {e}");
             send_msg(
                 host_tx,
-                ServerMessage::error("Unexpected message type: expected Host message"),
-            );
-            return;
-        }
-        Err(e) => {
-            warn!("Failed to parse message: {text}");
-            warn!("Error: {e}");
-            send_msg(
-                host_tx,
-                ServerMessage::error("Server error: Failed to parse message"),
+                ServerMessage::error(
+                    ErrorCode::InvalidMessage,
+                    "Server error: Failed to parse message",
+                ),
             );
             return;
         }
     };
+    let request_id = envelope.request_id;
+    let ClientMessage::Host(action) = envelope.message else {
+        warn!("Got unexpected message type when Host message expected");
+        send_msg(
+            host_tx,
+            ServerMessage::error(
+                ErrorCode::InvalidMessage,
+                "Unexpected message type: expected Host message",
+            )
+            .for_request(request_id),
+        );
+        return;
+    };
+    // Raw frames may carry tokens, so only the parsed (redacted) message is logged
+    info!("Received message: {action:?}");
 
-    // Acquire lock, mutate state, and broadcast to all clients
-    let game_state_for_persist = {
-        let mut games_map = app_state.games.lock().await;
-        let Some(game) = games_map.get_mut(game_code) else {
-            error!("Game {game_code} not found while processing host message");
-            return;
-        };
-        let should_persist = match process_host_action(action, game, app_state, game_code) {
-            Ok(should_persist) => should_persist,
+    // Clock sync only needs a reply, not the game lock
+    if let HostAction::SyncClock { client_time_ms } = action {
+        send_msg(
+            host_tx,
+            ServerMessage::ClockSync {
+                client_time_ms,
+                server_time_ms: now_ms(),
+            },
+        );
+        send_ack(host_tx, request_id);
+        return;
+    }
+
+    // Resync only resends the sender's own state
+    if let HostAction::Resync = action {
+        if let Some(game) = app_state.game(game_code).await {
+            game.lock().await.resync_host(user_id);
+        }
+        send_ack(host_tx, request_id);
+        return;
+    }
+
+    if let HostAction::Reauthenticate { token } = action {
+        match reauthenticate(app_state, game_code, user_id, token.as_str(), expiry).await {
+            Ok(expires_at_ms) => {
+                send_msg(host_tx, ServerMessage::Reauthenticated { expires_at_ms });
+                send_ack(host_tx, request_id);
+            }
             Err(e) => {
-                send_msg(host_tx, ServerMessage::error(e.to_string()));
-                return;
+                send_msg(
+                    host_tx,
+                    ServerMessage::error(e.code, e.message).for_request(request_id),
+                );
             }
-        };
+        }
+        return;
+    }
 
-        // Broadcast updated state to all clients
+    // An expired connection keeps receiving updates but can't change anything. Its current
+    // state lets it undo optimistic updates.
+    if expiry.is_expired() {
+        let state = match app_state.game(game_code).await {
+            Some(game) => Some(Box::new(game.lock().await.to_game_state())),
+            None => None,
+        };
         send_msg(
             host_tx,
-            ServerMessage::GameState {
-                state: game.to_game_state(),
+            ServerMessage::Error {
+                code: ErrorCode::AuthenticationExpired,
+                message: "Token expired: send Reauthenticate with a fresh token".to_string(),
+                request_id,
+                state,
+                team_state: None,
             },
         );
-        for (team_name, team_tx) in &game.teams_tx {
-            if let Some(team_state) = game.to_team_game_state(team_name) {
-                send_msg(team_tx, ServerMessage::TeamGameState { state: team_state });
+        return;
+    }
+
+    // Saved-game requests are about the sender's own games, not this one
+    if let HostAction::ListGames | HostAction::DeleteGame { .. } = action {
+        let Some(game) = app_state.game(game_code).await else {
+            return;
+        };
+        let permitted = game.lock().await.check_permission(user_id, &action);
+        let reply = match permitted {
+            Ok(()) => saved_games_request(app_state, user_id, action).await,
+            Err(e) => Err(e),
+        };
+        match reply {
+            Ok(reply) => {
+                send_msg(host_tx, reply);
+                send_ack(host_tx, request_id);
+            }
+            Err(e) => {
+                send_msg(
+                    host_tx,
+                    ServerMessage::error(e.code, e.message).for_request(request_id),
+                );
             }
         }
-        game.broadcast_scoreboard_data();
+        return;
+    }
 
-        if should_persist {
-            Some(game.to_game_state())
-        } else {
-            None
-        }
+    // Acquire lock, mutate state, and broadcast to all clients
+    let Some(game) = app_state.game(game_code).await else {
+        error!("Game {game_code} not found while processing host message");
+        return;
     };
-
-    // Persist game state to S3 if needed
-    if let Some(state) = game_state_for_persist
-        && let Err(e) = app_state
-            .persistence
-            .save_game_state(user_id, game_code, &state)
-            .await
     {
-        warn!("Failed to save game state to S3: {e}");
-        send_msg(
-            host_tx,
-            ServerMessage::error(format!("Failed to save game state: {e}")),
-        );
+        let mut game = game.lock().await;
+        let result = game
+            .check_permission(user_id, &action)
+            .and_then(|()| process_host_action(action.clone(), &mut game, app_state, game_code));
+        if let Err(e) = result {
+            // Nothing changed; the host's current state lets it undo optimistic updates
+            send_msg(
+                host_tx,
+                ServerMessage::Error {
+                    code: e.code,
+                    message: e.message,
+                    request_id,
+                    state: Some(Box::new(game.to_game_state())),
+                    team_state: None,
+                },
+            );
+            return;
+        }
+
+        game.record_event(EventKind::Host { action });
+
+        // Broadcast updated state to all clients, so every host-side connection sees every
+        // edit in the order it was applied
+        game.broadcast_game_state();
+        send_ack(host_tx, request_id);
+    }
+    // Lock released here
+
+    app_state.autosave.schedule(game_code, &game);
+}
+
+/// Wait until the connection's token is due a warning or expiry, or forever if it isn't
+async fn auth_deadline(expiry: &AuthExpiry) {
+    match expiry.next_deadline() {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
     }
 }
 
+/// Serve a host-side connection, the owner's or a staff member's, until it disconnects
+#[allow(clippy::too_many_arguments)]
 async fn handle_host(
     ws_stream: WebSocketStream<TcpStream>,
     app_state: Arc<AppState>,
@@ -301,15 +812,24 @@ async fn handle_host(
     host_tx: Tx,
     game_code: String,
     user_id: String,
+    mut expiry: AuthExpiry,
+    protocol_version: u32,
 ) {
     let (mut ws_write, mut ws_read) = ws_stream.split();
     let mut heartbeat = HeartbeatState::new();
     let mut ping_interval = tokio::time::interval(PING_INTERVAL);
+    let mut limiter = app_state.limits.limiter_for(ClientKind::Host);
+    // Set when the client closes normally: it exited the game rather than dropping out
+    let mut left_for_good = false;
 
     loop {
         tokio::select! {
             // Outgoing messages from channel
-            Some(msg) = rx.recv() => {
+            msg = rx.recv() => {
+                // None once the outbox closes, e.g. because this client fell too far behind
+                let Some(msg) = msg else {
+                    break;
+                };
                 if ws_write.send(msg).await.is_err() {
                     break;
                 }
@@ -321,21 +841,39 @@ async fn handle_host(
                     Some(Ok(Message::Pong(_))) => {
                         heartbeat.record_pong();
                     }
-                    Some(Ok(Message::Text(text))) => {
-                        if text.is_empty() {
+                    Some(Ok(frame @ (Message::Text(_) | Message::Binary(_)))) => {
+                        if frame.is_empty() {
                             log::warn!("Received empty message");
                             continue;
                         }
-                        info!("Received message: {text}");
-                        process_host_message(&text, &app_state, &game_code, &user_id, &host_tx).await;
+                        match limiter.check() {
+                            Verdict::Allow => {
+                                process_host_message(&frame, &app_state, &game_code, &user_id, &mut expiry, &host_tx, protocol_version).await;
+                            }
+                            Verdict::Reject => limits::send_rate_limited(&host_tx, &frame),
+                            Verdict::Disconnect => {
+                                warn!("Disconnecting host of game {game_code}: too many messages");
+                                break;
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Close(frame))) => {
+                        left_for_good = frame.is_some_and(|frame| frame.code == CloseCode::Normal);
+                        break;
                     }
-                    Some(Ok(Message::Close(_))) | None => {
+                    None => {
+                        break;
+                    }
+                    Some(Err(Error::Capacity(e))) => {
+                        warn!("Disconnecting host of game {game_code}: {e}");
+                        let error = ServerMessage::error(ErrorCode::MessageTooLarge, e.to_string());
+                        let _ = ws_write.send(host_tx.encoding().encode(&error)).await;
                         break;
                     }
                     Some(Err(_)) => {
                         break;
                     }
-                    _ => {} // Ignore Ping (auto-handled by tungstenite), Binary
+                    _ => {} // Ignore Ping (auto-handled by tungstenite)
                 }
             }
 
@@ -349,36 +887,50 @@ async fn handle_host(
                     break;
                 }
             }
+
+            // Token about to expire, or expired
+            _ = auth_deadline(&expiry) => {
+                if let Some(msg) = expiry.poll() {
+                    info!("Host-side connection {user_id} of game {game_code}: {msg:?}");
+                    send_msg(&host_tx, msg);
+                }
+            }
         }
     }
 
-    info!("Host disconnected, clearing host_tx");
-    // Get game state and clear host_tx
-    let game_state = {
-        let mut games_map = app_state.games.lock().await;
-        if let Some(game) = games_map.get_mut(&game_code) {
-            game.clear_host_tx();
-            Some(game.to_game_state())
-        } else {
-            error!("Game {game_code} not found in app_state when host disconnected");
-            None
-        }
+    let Some(game) = app_state.game(&game_code).await else {
+        error!("Game {game_code} not found in app_state when host disconnected");
+        return;
     };
+    {
+        let mut game = game.lock().await;
+        if user_id != game.host_user_id {
+            info!("Staff member {user_id} disconnected from game {game_code}");
+            game.clear_staff_tx(&user_id, &host_tx);
+            game.send_host_state();
+            return;
+        }
+        info!("Host disconnected, clearing host_tx");
+        game.clear_host_tx();
+    }
 
-    // Fire-and-forget save to S3 on disconnect
-    if let Some(state) = game_state {
-        let persistence = app_state.persistence.clone();
-        let user_id = user_id.clone();
-        let game_code = game_code.clone();
-        tokio::spawn(async move {
-            if let Err(e) = persistence
-                .save_game_state(&user_id, &game_code, &state)
-                .await
-            {
-                warn!("Failed to save game state on disconnect: {e}");
-            } else {
-                info!("Saved game state on host disconnect: {game_code}");
-            }
-        });
+    if left_for_good {
+        // Nothing to bring back at startup until the host returns, which indexes it again
+        let entry = ActiveGame {
+            user_id: user_id.clone(),
+            game_code: game_code.clone(),
+        };
+        if let Err(e) = app_state.persistence.remove_active_games(&[entry]).await {
+            warn!("Failed to remove game {game_code} from the active games index: {e}");
+        }
     }
+
+    // Fire-and-forget save on disconnect, queued behind any save already in progress
+    tokio::spawn(async move {
+        if let Err(e) = app_state.autosave.save_now(&game).await {
+            warn!("Failed to save game state on disconnect: {e}");
+        } else {
+            info!("Saved game state on host disconnect: {game_code}");
+        }
+    });
 }
@@ -1,19 +1,29 @@
 use crate::{
+    game_timer::now_ms,
     heartbeat::{HeartbeatState, PING_INTERVAL},
+    limits::{self, Limits, Verdict},
     model::{
-        client_message::{ClientMessage, TeamAction},
-        game::Game,
-        server_message::{ServerMessage, send_msg},
+        client_message::{ClientKind, ClientMessage, TeamAction},
+        error::{ActionError, ErrorCode},
+        event::{EventKind, Submission},
+        game::{Game, SubmissionWindow},
+        protocol::{self, Handshake},
+        server_message::{ServerMessage, send_ack, send_msg},
         types::TeamColor,
     },
+    outbox,
     server::{AppState, Rx, Tx},
 };
 use futures_util::{SinkExt, StreamExt};
 use log::*;
 use std::sync::Arc;
-use tokio::{net::TcpStream, sync::mpsc};
-use tokio_tungstenite::{WebSocketStream, tungstenite::Message};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    WebSocketStream,
+    tungstenite::{Error, Message},
+};
 
+#[allow(clippy::too_many_arguments)]
 pub async fn join_game(
     app_state: Arc<AppState>,
     mut ws_stream: WebSocketStream<TcpStream>,
@@ -22,42 +32,68 @@ pub async fn join_game(
     color_hex: String,
     color_name: String,
     team_members: Vec<String>,
+    handshake: Handshake,
+    request_id: Option<String>,
 ) {
-    let (tx, rx) = mpsc::unbounded_channel::<Message>();
-    let mut games_map = app_state.games.lock().await;
-    if let Some(game) = games_map.get_mut(&game_code) {
+    let (tx, rx) = outbox::channel(handshake.encoding);
+    if let Some(shared_game) = app_state.game(&game_code).await {
+        let mut game = shared_game.lock().await;
         info!("Team {team_name} joined game {game_code}");
         let team_color = TeamColor {
             hex_code: color_hex,
             name: color_name,
         };
-        game.add_team(team_name.clone(), tx.clone(), team_color, team_members);
+        game.add_team(
+            team_name.clone(),
+            tx.clone(),
+            team_color.clone(),
+            team_members.clone(),
+        );
+        game.set_team_protocol(&team_name, handshake.protocol_version);
+        game.record_event(EventKind::Team {
+            team_name: team_name.clone(),
+            action: TeamAction::JoinGame {
+                team_name: team_name.clone(),
+                game_code: game_code.clone(),
+                color_hex: team_color.hex_code,
+                color_name: team_color.name,
+                team_members,
+            },
+            submission: None,
+        });
 
         // Send TeamGameState to the joining team
-        if let Some(team_state) = game.to_team_game_state(&team_name) {
-            let team_msg = ServerMessage::TeamGameState { state: team_state };
-            send_msg(&tx, team_msg);
-        }
+        game.send_team_state(&team_name);
+        send_ack(&tx, request_id);
 
         // Send updated GameState to host
-        if let Some(host_tx) = &game.host_tx {
-            let host_msg = ServerMessage::GameState {
-                state: game.to_game_state(),
-            };
-            send_msg(host_tx, host_msg);
-        }
+        game.send_host_state();
 
         // Notify watchers of team change
         game.broadcast_scoreboard_data();
 
-        drop(games_map);
-        handle_team(ws_stream, app_state, rx, tx, game_code, team_name).await;
+        drop(game);
+        app_state.autosave.schedule(&game_code, &shared_game);
+        handle_team(
+            ws_stream,
+            app_state,
+            rx,
+            tx,
+            game_code,
+            team_name,
+            handshake.protocol_version,
+        )
+        .await;
     } else {
-        drop(games_map);
         info!("Team {team_name} tried to join game {game_code}, but it doesn't exist");
-        let error_message = ServerMessage::error(format!("Game code {game_code} not found"));
-        let msg = serde_json::to_string(&error_message).unwrap();
-        let _ = ws_stream.send(Message::text(msg)).await;
+        let error_message = ServerMessage::error(
+            ErrorCode::GameNotFound,
+            format!("Game code {game_code} not found"),
+        )
+        .for_request(request_id);
+        let _ = ws_stream
+            .send(handshake.encoding.encode(&error_message))
+            .await;
     }
 }
 
@@ -68,131 +104,197 @@ pub async fn rejoin_game(
     ws_stream: WebSocketStream<TcpStream>,
     game_code: String,
     team_name: String,
+    handshake: Handshake,
 ) {
-    let (tx, rx) = mpsc::unbounded_channel::<Message>();
-    let mut games_map = app_state.games.lock().await;
-    if let Some(game) = games_map.get_mut(&game_code) {
+    let (tx, rx) = outbox::channel(handshake.encoding);
+    if let Some(game) = app_state.game(&game_code).await {
+        let mut game = game.lock().await;
         info!("Team {team_name} rejoining game {game_code}");
         game.rejoin_team(&team_name, tx.clone());
+        game.set_team_protocol(&team_name, handshake.protocol_version);
 
-        // TeamGameState already sent in ValidateJoin response, don't send again
+        // TeamGameState already sent in ValidateJoin response, don't send again.
+        // The team's next update is a full snapshot since its patch stream was reset.
 
         // Send updated GameState to host (so they see team is back)
-        if let Some(host_tx) = &game.host_tx {
-            let host_msg = ServerMessage::GameState {
-                state: game.to_game_state(),
-            };
-            send_msg(host_tx, host_msg);
-        }
+        game.send_host_state();
 
         // Notify watchers of team reconnection
         game.broadcast_scoreboard_data();
 
-        drop(games_map);
-        handle_team(ws_stream, app_state, rx, tx, game_code, team_name).await;
+        drop(game);
+        handle_team(
+            ws_stream,
+            app_state,
+            rx,
+            tx,
+            game_code,
+            team_name,
+            handshake.protocol_version,
+        )
+        .await;
     } else {
-        drop(games_map);
         // This shouldn't happen since we validated in ValidateJoin
         error!("Team {team_name} tried to rejoin game {game_code}, but it doesn't exist");
     }
 }
 
-/// Result of processing a team action: messages to send after releasing the lock
-struct TeamActionResult {
-    team_msg: ServerMessage,
-    host_msg: Option<(Tx, ServerMessage)>, // (cloned host_tx, message)
-}
-
 /// Process a team action that mutates game state.
 /// The game reference must be held under a lock; this function does not await.
-fn process_team_action(action: TeamAction, game: &mut Game, team_name: &str) -> TeamActionResult {
+/// State updates are sent directly; returns any other reply for the team (clock sync).
+fn process_team_action(
+    action: TeamAction,
+    game: &mut Game,
+    team_name: &str,
+    limits: &Limits,
+) -> Result<Option<ServerMessage>, ActionError> {
     match action {
-        TeamAction::ValidateJoin { .. } => TeamActionResult {
-            team_msg: ServerMessage::error("Already validated"),
-            host_msg: None,
-        },
+        TeamAction::ValidateJoin { .. } => Err(ActionError::new(
+            ErrorCode::UnexpectedAction,
+            "Already validated",
+        )),
 
-        TeamAction::JoinGame { .. } => TeamActionResult {
-            team_msg: ServerMessage::error("Game already joined"),
-            host_msg: None,
-        },
+        TeamAction::JoinGame { .. } => Err(ActionError::new(
+            ErrorCode::UnexpectedAction,
+            "Game already joined",
+        )),
+
+        TeamAction::SyncClock { client_time_ms } => Ok(Some(ServerMessage::ClockSync {
+            client_time_ms,
+            server_time_ms: now_ms(),
+        })),
+
+        TeamAction::Resync => {
+            game.resync_team(team_name);
+            Ok(None)
+        }
 
         TeamAction::SubmitAnswer { answer, .. } => {
-            // Add the answer
-            if !game.add_answer(team_name, answer) {
-                return TeamActionResult {
-                    team_msg: ServerMessage::error("Answer already submitted"),
-                    host_msg: None,
-                };
-            }
+            // Judge against the deadline, even if expiry hasn't fired yet
+            let late = match game.submission_window() {
+                SubmissionWindow::Open => false,
+                SubmissionWindow::Late => true,
+                SubmissionWindow::Closed => {
+                    return Err(ActionError::new(
+                        ErrorCode::SubmissionsClosed,
+                        "Submissions are closed",
+                    ));
+                }
+            };
 
-            // Build messages to send
-            let team_msg = game
-                .to_team_game_state(team_name)
-                .map(|state| ServerMessage::TeamGameState { state })
-                .unwrap_or_else(|| ServerMessage::error("Failed to get team state"));
+            let max_chars = limits.max_answer_chars(game.current_question().question_kind);
+            if answer.chars().count() > max_chars {
+                return Err(ActionError::new(
+                    ErrorCode::AnswerTooLong,
+                    format!("Answers can be at most {max_chars} characters"),
+                ));
+            }
 
-            let host_msg = game.host_tx.clone().map(|tx| {
-                (
-                    tx,
-                    ServerMessage::GameState {
-                        state: game.to_game_state(),
-                    },
-                )
+            // Add the answer
+            let submission = Submission {
+                late,
+                answer_latency_ms: game.answer_latency_ms(),
+                timer_window_ms: game.timer_window_ms(),
+            };
+            if !game.add_answer(team_name, answer.clone(), submission) {
+                return Err(ActionError::new(
+                    ErrorCode::AnswerAlreadySubmitted,
+                    "Answer already submitted",
+                ));
+            }
+            game.record_event(EventKind::Team {
+                team_name: team_name.to_string(),
+                action: TeamAction::SubmitAnswer {
+                    team_name: team_name.to_string(),
+                    answer,
+                },
+                submission: Some(submission),
             });
 
-            TeamActionResult { team_msg, host_msg }
+            // Send updated state to the team and host
+            game.send_team_state(team_name);
+            game.send_host_state();
+            Ok(None)
         }
     }
 }
 
 async fn process_team_message(
-    text: &str,
+    frame: &Message,
     app_state: &Arc<AppState>,
     game_code: &str,
     team_name: &str,
     team_tx: &Tx,
+    protocol_version: u32,
 ) {
     // Parse message before acquiring lock
-    let action = match serde_json::from_str::<ClientMessage>(text) {
-        Ok(ClientMessage::Team(action)) => action,
-        Ok(_) => {
-            send_msg(
-                team_tx,
-                ServerMessage::error("Unexpected message type: expected Team message"),
-            );
-            return;
-        }
-        Err(e) => {
-            error!("Failed to parse message: {text}");
-            error!("Error: {e}");
+    let envelope = match protocol::decode_client_message(frame, protocol_version) {
+        Some(Ok(envelope)) => envelope,
+        None => return,
+        Some(Err(e)) => {
+            error!("Failed to parse message: {e}");
             send_msg(
                 team_tx,
-                ServerMessage::error("Server error: Failed to parse message"),
+                ServerMessage::error(
+                    ErrorCode::InvalidMessage,
+                    "Server error: Failed to parse message",
+                ),
             );
             return;
         }
     };
+    let request_id = envelope.request_id;
+    let ClientMessage::Team(action) = envelope.message else {
+        send_msg(
+            team_tx,
+            ServerMessage::error(
+                ErrorCode::InvalidMessage,
+                "Unexpected message type: expected Team message",
+            )
+            .for_request(request_id),
+        );
+        return;
+    };
+    info!("Received message: {action:?}");
+
+    // Only answers change what gets saved
+    let changes_game = matches!(action, TeamAction::SubmitAnswer { .. });
 
-    // Acquire lock, mutate state, collect messages to send, then release lock
+    // Acquire lock, mutate state, then release lock
+    let Some(shared_game) = app_state.game(game_code).await else {
+        error!("Game {game_code} not found while processing team message from {team_name}");
+        return;
+    };
     let result = {
-        let mut games_map = app_state.games.lock().await;
-        let Some(game) = games_map.get_mut(game_code) else {
-            error!("Game {game_code} not found while processing team message from {team_name}");
-            return;
-        };
-        process_team_action(action, game, team_name)
+        let mut game = shared_game.lock().await;
+        process_team_action(action, &mut game, team_name, &app_state.limits).map_err(|e| {
+            warn!("Sending error response '{e}' back to team {team_name}");
+            // The team's current state lets it undo optimistic updates
+            ServerMessage::Error {
+                code: e.code,
+                message: e.message,
+                request_id: request_id.clone(),
+                state: None,
+                team_state: game.to_team_game_state(team_name).map(Box::new),
+            }
+        })
     };
     // Lock released here
 
-    if let ServerMessage::Error { message, state: _ } = &result.team_msg {
-        warn!("Sending error response '{message}' back to team {team_name}");
-    }
-
-    // Send messages outside the lock
-    send_msg(team_tx, result.team_msg);
-    if let Some((host_tx, msg)) = result.host_msg {
-        send_msg(&host_tx, msg);
+    // Send replies outside the lock
+    match result {
+        Ok(reply) => {
+            if let Some(msg) = reply {
+                send_msg(team_tx, msg);
+            }
+            send_ack(team_tx, request_id);
+            if changes_game {
+                app_state.autosave.schedule(game_code, &shared_game);
+            }
+        }
+        Err(error) => {
+            send_msg(team_tx, error);
+        }
     }
 }
 
@@ -203,15 +305,21 @@ async fn handle_team(
     team_tx: Tx,
     game_code: String,
     team_name: String,
+    protocol_version: u32,
 ) {
     let (mut ws_write, mut ws_read) = ws_stream.split();
     let mut heartbeat = HeartbeatState::new();
     let mut ping_interval = tokio::time::interval(PING_INTERVAL);
+    let mut limiter = app_state.limits.limiter_for(ClientKind::Team);
 
     loop {
         tokio::select! {
             // Outgoing messages from channel
-            Some(msg) = rx.recv() => {
+            msg = rx.recv() => {
+                // None once the outbox closes, e.g. because this client fell too far behind
+                let Some(msg) = msg else {
+                    break;
+                };
                 if ws_write.send(msg).await.is_err() {
                     break;
                 }
@@ -223,17 +331,31 @@ async fn handle_team(
                     Some(Ok(Message::Pong(_))) => {
                         heartbeat.record_pong();
                     }
-                    Some(Ok(Message::Text(text))) => {
-                        info!("Received message: {text}");
-                        process_team_message(&text, &app_state, &game_code, &team_name, &team_tx).await;
+                    Some(Ok(frame @ (Message::Text(_) | Message::Binary(_)))) => {
+                        match limiter.check() {
+                            Verdict::Allow => {
+                                process_team_message(&frame, &app_state, &game_code, &team_name, &team_tx, protocol_version).await;
+                            }
+                            Verdict::Reject => limits::send_rate_limited(&team_tx, &frame),
+                            Verdict::Disconnect => {
+                                warn!("Disconnecting team {team_name}: too many messages");
+                                break;
+                            }
+                        }
                     }
                     Some(Ok(Message::Close(_))) | None => {
                         break;
                     }
+                    Some(Err(Error::Capacity(e))) => {
+                        warn!("Disconnecting team {team_name}: {e}");
+                        let error = ServerMessage::error(ErrorCode::MessageTooLarge, e.to_string());
+                        let _ = ws_write.send(team_tx.encoding().encode(&error)).await;
+                        break;
+                    }
                     Some(Err(_)) => {
                         break;
                     }
-                    _ => {} // Ignore Ping (auto-handled by tungstenite), Binary
+                    _ => {} // Ignore Ping (auto-handled by tungstenite)
                 }
             }
 
@@ -252,26 +374,10 @@ async fn handle_team(
 
     // Team disconnected - update state and notify host
     info!("Team {team_name} disconnected from game {game_code}");
-    let host_tx = {
-        let mut games_map = app_state.games.lock().await;
-        if let Some(game) = games_map.get_mut(&game_code) {
-            game.set_team_connected(&team_name, false);
-            game.clear_team_tx(&team_name);
-            game.host_tx.clone().map(|tx| {
-                (
-                    tx,
-                    ServerMessage::GameState {
-                        state: game.to_game_state(),
-                    },
-                )
-            })
-        } else {
-            None
-        }
-    };
-    // Lock released here
-
-    if let Some((tx, msg)) = host_tx {
-        send_msg(&tx, msg);
+    if let Some(game) = app_state.game(&game_code).await {
+        let mut game = game.lock().await;
+        game.set_team_connected(&team_name, false);
+        game.clear_team_tx(&team_name);
+        game.send_host_state();
     }
 }
@@ -1,25 +1,37 @@
 use crate::{
     heartbeat::{HeartbeatState, PING_INTERVAL},
-    model::server_message::{ServerMessage, send_msg},
+    limits::{self, Verdict},
+    model::{
+        client_message::ClientKind,
+        error::ErrorCode,
+        protocol::Encoding,
+        server_message::{ServerMessage, send_ack, send_msg},
+    },
+    outbox,
     server::{AppState, Rx, Tx},
 };
 use futures_util::{SinkExt, StreamExt};
 use log::*;
 use std::sync::Arc;
-use tokio::{net::TcpStream, sync::mpsc};
-use tokio_tungstenite::{WebSocketStream, tungstenite::Message};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    WebSocketStream,
+    tungstenite::{Error, Message},
+};
 
 pub async fn watch_game(
     app_state: Arc<AppState>,
     mut ws_stream: WebSocketStream<TcpStream>,
     game_code: String,
+    encoding: Encoding,
+    request_id: Option<String>,
 ) {
-    let (tx, rx) = mpsc::unbounded_channel::<Message>();
+    let (tx, rx) = outbox::channel(encoding);
 
     // Validate game exists and add watcher
     let initial_data = {
-        let mut games_map = app_state.games.lock().await;
-        if let Some(game) = games_map.get_mut(&game_code) {
+        if let Some(game) = app_state.game(&game_code).await {
+            let mut game = game.lock().await;
             info!("Watcher connected to game {game_code}");
             game.add_watcher(tx.clone());
             Some(game.to_scoreboard_data())
@@ -37,14 +49,18 @@ pub async fn watch_game(
                     data: scoreboard_data,
                 },
             );
+            send_ack(&tx, request_id);
             // Enter watcher loop
             handle_watcher(ws_stream, app_state, rx, tx, game_code).await;
         }
         None => {
             info!("Watcher tried to connect to non-existent game {game_code}");
-            let error_message = ServerMessage::error(format!("Game code {game_code} not found"));
-            let msg = serde_json::to_string(&error_message).unwrap();
-            let _ = ws_stream.send(Message::text(msg)).await;
+            let error_message = ServerMessage::error(
+                ErrorCode::GameNotFound,
+                format!("Game code {game_code} not found"),
+            )
+            .for_request(request_id);
+            let _ = ws_stream.send(encoding.encode(&error_message)).await;
         }
     }
 }
@@ -59,11 +75,16 @@ async fn handle_watcher(
     let (mut ws_write, mut ws_read) = ws_stream.split();
     let mut heartbeat = HeartbeatState::new();
     let mut ping_interval = tokio::time::interval(PING_INTERVAL);
+    let mut limiter = app_state.limits.limiter_for(ClientKind::Watcher);
 
     loop {
         tokio::select! {
             // Outgoing messages from channel
-            Some(msg) = rx.recv() => {
+            msg = rx.recv() => {
+                // None once the outbox closes, e.g. because this client fell too far behind
+                let Some(msg) = msg else {
+                    break;
+                };
                 if ws_write.send(msg).await.is_err() {
                     break;
                 }
@@ -75,17 +96,31 @@ async fn handle_watcher(
                     Some(Ok(Message::Pong(_))) => {
                         heartbeat.record_pong();
                     }
-                    Some(Ok(Message::Text(text))) => {
+                    Some(Ok(msg @ (Message::Text(_) | Message::Binary(_)))) => {
                         // Watchers are read-only, ignore incoming messages
-                        info!("Ignoring message from watcher: {text}");
+                        info!("Ignoring message from watcher");
+                        match limiter.check() {
+                            Verdict::Allow => {}
+                            Verdict::Reject => limits::send_rate_limited(&watcher_tx, &msg),
+                            Verdict::Disconnect => {
+                                warn!("Disconnecting watcher of game {game_code}: too many messages");
+                                break;
+                            }
+                        }
                     }
                     Some(Ok(Message::Close(_))) | None => {
                         break;
                     }
+                    Some(Err(Error::Capacity(e))) => {
+                        warn!("Disconnecting watcher of game {game_code}: {e}");
+                        let error = ServerMessage::error(ErrorCode::MessageTooLarge, e.to_string());
+                        let _ = ws_write.send(watcher_tx.encoding().encode(&error)).await;
+                        break;
+                    }
                     Some(Err(_)) => {
                         break;
                     }
-                    _ => {} // Ignore Ping (auto-handled by tungstenite), Binary
+                    _ => {} // Ignore Ping (auto-handled by tungstenite)
                 }
             }
 
@@ -104,8 +139,7 @@ async fn handle_watcher(
 
     // Watcher disconnected - remove from game
     info!("Watcher disconnected from game {game_code}");
-    let mut games_map = app_state.games.lock().await;
-    if let Some(game) = games_map.get_mut(&game_code) {
-        game.remove_watcher(&watcher_tx);
+    if let Some(game) = app_state.game(&game_code).await {
+        game.lock().await.remove_watcher(&watcher_tx);
     }
 }
@@ -0,0 +1,157 @@
+//! Cached JSON Web Key Sets.
+//!
+//! Keys are fetched on first use and served from memory after that. Once they're older
+//! than the TTL the cached set keeps being used while a background task refreshes it. A
+//! token signed with a key the cache doesn't know triggers one refetch, in case the
+//! issuer rotated its keys, but no more often than the refetch cooldown, so tokens with
+//! made-up key IDs can't make every connection wait on a request.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, anyhow};
+use jsonwebtoken::DecodingKey;
+use jsonwebtoken::jwk::JwkSet;
+use log::{info, warn};
+use tokio::sync::Mutex;
+
+/// How long fetched keys are used before refreshing them
+pub const JWKS_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Shortest gap between refetches for unknown key IDs
+pub const JWKS_REFETCH_COOLDOWN: Duration = Duration::from_secs(30);
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct JwksCache {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    url: String,
+    client: reqwest::Client,
+    ttl: Duration,
+    refetch_cooldown: Duration,
+    keys: RwLock<Option<FetchedKeys>>,
+    /// Held while fetching, so callers that miss at the same time share one request
+    fetch_lock: Mutex<()>,
+    /// Set while a background refresh is running
+    refreshing: AtomicBool,
+}
+
+#[derive(Clone)]
+struct FetchedKeys {
+    jwks: Arc<JwkSet>,
+    fetched_at: Instant,
+}
+
+impl JwksCache {
+    pub fn new(url: String, ttl: Duration, refetch_cooldown: Duration) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .build()
+            .expect("HTTP client should build");
+        JwksCache {
+            inner: Arc::new(Inner {
+                url,
+                client,
+                ttl,
+                refetch_cooldown,
+                keys: RwLock::new(None),
+                fetch_lock: Mutex::new(()),
+                refreshing: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// The key that verifies tokens with key ID `kid`
+    pub async fn decoding_key(&self, kid: &str) -> Result<DecodingKey> {
+        let cached = self.inner.cached();
+        if let Some(cached) = &cached {
+            if cached.fetched_at.elapsed() >= self.inner.ttl {
+                self.refresh_in_background(cached.fetched_at);
+            }
+            if let Some(key) = find_key(&cached.jwks, kid) {
+                return key;
+            }
+            if cached.fetched_at.elapsed() < self.inner.refetch_cooldown {
+                return Err(anyhow!("No matching key found for kid: {kid}"));
+            }
+            info!("Unknown kid {kid}; refetching JWKS in case keys rotated");
+        }
+
+        let jwks = self
+            .inner
+            .refetch(cached.map(|cached| cached.fetched_at))
+            .await?;
+        find_key(&jwks, kid).unwrap_or_else(|| Err(anyhow!("No matching key found for kid: {kid}")))
+    }
+
+    fn refresh_in_background(&self, fetched_at: Instant) {
+        if self.inner.refreshing.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            if let Err(e) = inner.refetch(Some(fetched_at)).await {
+                warn!("Failed to refresh JWKS, still using the old keys: {e}");
+            }
+            inner.refreshing.store(false, Ordering::Release);
+        });
+    }
+}
+
+impl Inner {
+    fn cached(&self) -> Option<FetchedKeys> {
+        self.keys.read().unwrap().clone()
+    }
+
+    /// Fetch the keys, unless another caller already replaced the ones fetched at `stale`
+    async fn refetch(&self, stale: Option<Instant>) -> Result<Arc<JwkSet>> {
+        let _fetching = self.fetch_lock.lock().await;
+        if let Some(cached) = self.cached()
+            && Some(cached.fetched_at) != stale
+        {
+            return Ok(cached.jwks);
+        }
+
+        let jwks = Arc::new(self.fetch().await?);
+        *self.keys.write().unwrap() = Some(FetchedKeys {
+            jwks: jwks.clone(),
+            fetched_at: Instant::now(),
+        });
+        info!("Fetched {} keys from {}", jwks.keys.len(), self.url);
+        Ok(jwks)
+    }
+
+    async fn fetch(&self) -> Result<JwkSet> {
+        let response = self
+            .client
+            .get(&self.url)
+            .send()
+            .await
+            .map_err(|e| anyhow!("Failed to fetch JWKS: {e}"))?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "JWKS request failed with status: {}",
+                response.status()
+            ));
+        }
+
+        response
+            .json::<JwkSet>()
+            .await
+            .map_err(|e| anyhow!("Failed to parse JWKS: {e}"))
+    }
+}
+
+/// None if the set has no key with this ID
+fn find_key(jwks: &JwkSet, kid: &str) -> Option<Result<DecodingKey>> {
+    let jwk = jwks
+        .keys
+        .iter()
+        .find(|k| k.common.key_id.as_deref() == Some(kid))?;
+    Some(DecodingKey::from_jwk(jwk).map_err(|e| anyhow!("Failed to create decoding key: {e}")))
+}
@@ -1,9 +1,16 @@
 pub mod auth;
+pub mod auth_expiry;
+pub mod autosave;
 pub mod game_timer;
 pub mod handler;
 pub mod heartbeat;
 pub mod infra;
+pub mod jwks;
+pub mod limits;
+pub mod local_auth;
 pub mod model;
+pub mod outbox;
 pub mod persistence;
+pub mod schema;
 pub mod server;
 pub mod timer;
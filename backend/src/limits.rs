@@ -0,0 +1,273 @@
+//! Per-connection abuse limits.
+//!
+//! Each connection gets a token bucket sized for its client kind, so a client spamming
+//! actions can't keep grabbing its game's lock. Messages over the bucket are rejected with
+//! `RateLimited`; a client that keeps sending after `max_violations` rejections in a row
+//! is disconnected. Frames and messages over the size caps are refused by the socket
+//! itself, and answers are capped per question kind.
+
+use std::env;
+use std::str::FromStr;
+use std::time::Instant;
+
+use log::warn;
+use tokio_tungstenite::tungstenite::{Message, protocol::WebSocketConfig};
+
+use crate::model::client_message::ClientKind;
+use crate::model::error::ErrorCode;
+use crate::model::protocol::{self, PROTOCOL_VERSION};
+use crate::model::server_message::{ServerMessage, send_msg};
+use crate::model::types::QuestionKind;
+use crate::server::Tx;
+
+/// Sustained rate with an allowance for bursts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Messages a client can send back to back
+    pub burst: u32,
+    /// Messages per second once the burst is used up
+    pub per_second: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Limits {
+    pub host_rate: RateLimit,
+    pub team_rate: RateLimit,
+    pub watcher_rate: RateLimit,
+    /// Rate-limited messages in a row before the connection is closed
+    pub max_violations: u32,
+    pub max_frame_bytes: usize,
+    pub max_message_bytes: usize,
+    pub max_standard_answer_chars: usize,
+    pub max_multiple_choice_answer_chars: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            host_rate: RateLimit {
+                burst: 30,
+                per_second: 10.0,
+            },
+            team_rate: RateLimit {
+                burst: 10,
+                per_second: 2.0,
+            },
+            watcher_rate: RateLimit {
+                burst: 5,
+                per_second: 1.0,
+            },
+            max_violations: 20,
+            max_frame_bytes: 64 * 1024,
+            max_message_bytes: 64 * 1024,
+            max_standard_answer_chars: 500,
+            max_multiple_choice_answer_chars: 50,
+        }
+    }
+}
+
+impl Limits {
+    /// Defaults, overridden by any of the `*_RATE_BURST`, `*_RATE_PER_SECOND`,
+    /// `MAX_RATE_VIOLATIONS`, `MAX_FRAME_BYTES`, `MAX_MESSAGE_BYTES` and
+    /// `MAX_*_ANSWER_CHARS` env vars that are set
+    pub fn from_env() -> Self {
+        let defaults = Limits::default();
+        let rate = |prefix: &str, default: RateLimit| RateLimit {
+            burst: env_or(&format!("{prefix}_RATE_BURST"), default.burst),
+            per_second: env_or(&format!("{prefix}_RATE_PER_SECOND"), default.per_second),
+        };
+        Limits {
+            host_rate: rate("HOST", defaults.host_rate),
+            team_rate: rate("TEAM", defaults.team_rate),
+            watcher_rate: rate("WATCHER", defaults.watcher_rate),
+            max_violations: env_or("MAX_RATE_VIOLATIONS", defaults.max_violations),
+            max_frame_bytes: env_or("MAX_FRAME_BYTES", defaults.max_frame_bytes),
+            max_message_bytes: env_or("MAX_MESSAGE_BYTES", defaults.max_message_bytes),
+            max_standard_answer_chars: env_or(
+                "MAX_STANDARD_ANSWER_CHARS",
+                defaults.max_standard_answer_chars,
+            ),
+            max_multiple_choice_answer_chars: env_or(
+                "MAX_MULTIPLE_CHOICE_ANSWER_CHARS",
+                defaults.max_multiple_choice_answer_chars,
+            ),
+        }
+    }
+
+    pub fn rate_for(&self, client_kind: ClientKind) -> RateLimit {
+        match client_kind {
+            ClientKind::Host => self.host_rate,
+            ClientKind::Team => self.team_rate,
+            ClientKind::Watcher => self.watcher_rate,
+        }
+    }
+
+    /// Longest answer accepted for a question of this kind, in characters
+    pub fn max_answer_chars(&self, question_kind: QuestionKind) -> usize {
+        match question_kind {
+            QuestionKind::Standard | QuestionKind::MultiAnswer => self.max_standard_answer_chars,
+            QuestionKind::MultipleChoice => self.max_multiple_choice_answer_chars,
+        }
+    }
+
+    /// Socket settings enforcing the frame and message size caps
+    pub fn websocket_config(&self) -> WebSocketConfig {
+        WebSocketConfig::default()
+            .max_frame_size(Some(self.max_frame_bytes))
+            .max_message_size(Some(self.max_message_bytes))
+    }
+
+    pub fn limiter_for(&self, client_kind: ClientKind) -> RateLimiter {
+        RateLimiter::new(self.rate_for(client_kind), self.max_violations)
+    }
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match env::var(name) {
+        Ok(value) => value.parse().unwrap_or_else(|_| {
+            warn!("Ignoring invalid {name}={value}");
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+/// What to do with an incoming message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Allow,
+    /// Over the rate; reply with `RateLimited` and drop the message
+    Reject,
+    /// Still sending after too many rejections; close the connection
+    Disconnect,
+}
+
+/// Token bucket for one connection
+pub struct RateLimiter {
+    rate: RateLimit,
+    tokens: f64,
+    last_refill: Instant,
+    violations: u32,
+    max_violations: u32,
+}
+
+impl RateLimiter {
+    pub fn new(rate: RateLimit, max_violations: u32) -> Self {
+        RateLimiter {
+            rate,
+            tokens: rate.burst as f64,
+            last_refill: Instant::now(),
+            violations: 0,
+            max_violations,
+        }
+    }
+
+    /// Take a token for an incoming message
+    pub fn check(&mut self) -> Verdict {
+        self.check_at(Instant::now())
+    }
+
+    fn check_at(&mut self, now: Instant) -> Verdict {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate.per_second).min(self.rate.burst as f64);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            self.violations = 0;
+            Verdict::Allow
+        } else {
+            self.violations += 1;
+            if self.violations > self.max_violations {
+                Verdict::Disconnect
+            } else {
+                Verdict::Reject
+            }
+        }
+    }
+}
+
+/// Tell the client a message was dropped, echoing its request ID so it can roll back
+pub fn send_rate_limited(tx: &Tx, frame: &Message) {
+    send_msg(tx, rate_limited_error(frame));
+}
+
+/// The error for a dropped message. Clients on version 1 predate request IDs, so the
+/// frame is read as the current protocol.
+pub fn rate_limited_error(frame: &Message) -> ServerMessage {
+    let request_id = protocol::decode_client_message(frame, PROTOCOL_VERSION)
+        .and_then(Result::ok)
+        .and_then(|envelope| envelope.request_id);
+    ServerMessage::error(
+        ErrorCode::RateLimited,
+        "Too many messages; slow down and try again",
+    )
+    .for_request(request_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(
+            RateLimit {
+                burst: 2,
+                per_second: 1.0,
+            },
+            2,
+        )
+    }
+
+    #[test]
+    fn test_burst_then_reject() {
+        let mut limiter = limiter();
+        let now = Instant::now();
+        assert_eq!(limiter.check_at(now), Verdict::Allow);
+        assert_eq!(limiter.check_at(now), Verdict::Allow);
+        assert_eq!(limiter.check_at(now), Verdict::Reject);
+    }
+
+    #[test]
+    fn test_tokens_refill_over_time() {
+        let mut limiter = limiter();
+        let now = Instant::now();
+        limiter.check_at(now);
+        limiter.check_at(now);
+        assert_eq!(limiter.check_at(now), Verdict::Reject);
+
+        let later = now + Duration::from_millis(1500);
+        assert_eq!(limiter.check_at(later), Verdict::Allow);
+        assert_eq!(limiter.check_at(later), Verdict::Reject);
+    }
+
+    #[test]
+    fn test_persistent_spam_disconnects() {
+        let mut limiter = limiter();
+        let now = Instant::now();
+        limiter.check_at(now);
+        limiter.check_at(now);
+        assert_eq!(limiter.check_at(now), Verdict::Reject);
+        assert_eq!(limiter.check_at(now), Verdict::Reject);
+        assert_eq!(limiter.check_at(now), Verdict::Disconnect);
+    }
+
+    #[test]
+    fn test_allowed_message_resets_violations() {
+        let mut limiter = limiter();
+        let now = Instant::now();
+        limiter.check_at(now);
+        limiter.check_at(now);
+        limiter.check_at(now);
+        limiter.check_at(now);
+
+        let later = now + Duration::from_secs(1);
+        assert_eq!(limiter.check_at(later), Verdict::Allow);
+        assert_eq!(limiter.check_at(later), Verdict::Reject);
+        assert_eq!(limiter.check_at(later), Verdict::Reject);
+        assert_eq!(limiter.check_at(later), Verdict::Disconnect);
+    }
+}
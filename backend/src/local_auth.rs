@@ -0,0 +1,192 @@
+//! Self-hosted auth: host tokens signed with a locally configured key instead of Cognito.
+//!
+//! Tokens are checked with either an HS256 shared secret or a static RSA public key. A
+//! token's user is a host when its host claim (a group list by default) names the host
+//! group. `backend mint-token` signs tokens with the same settings, so a self-hosted
+//! server needs no AWS at all.
+//!
+//! Configured by env vars:
+//! - `AUTH_HS256_SECRET`: shared secret, used to both mint and check tokens
+//! - `AUTH_RSA_PUBLIC_KEY_FILE`: PEM public key that checks tokens (if no secret is set)
+//! - `AUTH_RSA_PRIVATE_KEY_FILE`: PEM private key that mints tokens (if no secret is set)
+//! - `AUTH_ISSUER`: expected `iss` claim, default `trivia-wizard`
+//! - `AUTH_HOST_CLAIM`: claim that marks hosts, default `groups`
+//! - `AUTH_HOST_GROUP`: value the host claim must hold, default `Trivia-Hosts`
+
+use std::env;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, anyhow};
+use futures_util::future::{self, BoxFuture};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use serde_json::{Map, Value, json};
+
+use crate::auth::{AuthResult, JwtValidator};
+
+pub const DEFAULT_ISSUER: &str = "trivia-wizard";
+pub const DEFAULT_HOST_CLAIM: &str = "groups";
+pub const DEFAULT_HOST_GROUP: &str = "Trivia-Hosts";
+
+/// Which tokens count, and which of their users are hosts
+#[derive(Debug, Clone)]
+pub struct TokenSettings {
+    pub issuer: String,
+    pub host_claim: String,
+    pub host_group: String,
+}
+
+impl Default for TokenSettings {
+    fn default() -> Self {
+        Self {
+            issuer: DEFAULT_ISSUER.to_string(),
+            host_claim: DEFAULT_HOST_CLAIM.to_string(),
+            host_group: DEFAULT_HOST_GROUP.to_string(),
+        }
+    }
+}
+
+impl TokenSettings {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            issuer: env::var("AUTH_ISSUER").unwrap_or(defaults.issuer),
+            host_claim: env::var("AUTH_HOST_CLAIM").unwrap_or(defaults.host_claim),
+            host_group: env::var("AUTH_HOST_GROUP").unwrap_or(defaults.host_group),
+        }
+    }
+
+    /// The host claim may be a list of groups, a single group, or `true`
+    fn is_host(&self, claims: &Map<String, Value>) -> bool {
+        match claims.get(&self.host_claim) {
+            Some(Value::Array(groups)) => groups
+                .iter()
+                .any(|group| group.as_str() == Some(&self.host_group)),
+            Some(Value::String(group)) => *group == self.host_group,
+            Some(Value::Bool(is_host)) => *is_host,
+            _ => false,
+        }
+    }
+}
+
+/// Validator for tokens signed with a locally configured key
+pub struct LocalValidator {
+    decoding_key: DecodingKey,
+    algorithm: Algorithm,
+    settings: TokenSettings,
+}
+
+impl LocalValidator {
+    pub fn hs256(secret: &[u8], settings: TokenSettings) -> Self {
+        Self {
+            decoding_key: DecodingKey::from_secret(secret),
+            algorithm: Algorithm::HS256,
+            settings,
+        }
+    }
+
+    pub fn rs256(public_key_pem: &[u8], settings: TokenSettings) -> Result<Self> {
+        Ok(Self {
+            decoding_key: DecodingKey::from_rsa_pem(public_key_pem)?,
+            algorithm: Algorithm::RS256,
+            settings,
+        })
+    }
+
+    pub fn from_env() -> Result<Self> {
+        let settings = TokenSettings::from_env();
+        if let Ok(secret) = env::var("AUTH_HS256_SECRET") {
+            return Ok(Self::hs256(secret.as_bytes(), settings));
+        }
+        let path = env::var("AUTH_RSA_PUBLIC_KEY_FILE")
+            .map_err(|_| anyhow!("Set AUTH_HS256_SECRET or AUTH_RSA_PUBLIC_KEY_FILE"))?;
+        let pem = std::fs::read(&path).with_context(|| format!("Failed to read {path}"))?;
+        Self::rs256(&pem, settings)
+    }
+
+    fn validate_token(&self, token: &str) -> Result<AuthResult> {
+        let mut validation = Validation::new(self.algorithm);
+        validation.set_issuer(&[&self.settings.issuer]);
+        validation.set_required_spec_claims(&["exp", "sub", "iss"]);
+
+        let claims = decode::<Map<String, Value>>(token, &self.decoding_key, &validation)
+            .map_err(|e| anyhow!("Invalid token: {}", e))?
+            .claims;
+
+        let user_id = claims
+            .get("sub")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("Token sub must be a string"))?
+            .to_string();
+
+        Ok(AuthResult {
+            is_host: self.settings.is_host(&claims),
+            expires_at_ms: claims
+                .get("exp")
+                .and_then(Value::as_u64)
+                .map(|exp| exp * 1000),
+            user_id,
+        })
+    }
+}
+
+impl JwtValidator for LocalValidator {
+    fn validate<'a>(&'a self, token: &'a str) -> BoxFuture<'a, Result<AuthResult>> {
+        Box::pin(future::ready(self.validate_token(token)))
+    }
+}
+
+/// Signs host tokens that a `LocalValidator` with the same settings accepts
+pub struct TokenMinter {
+    encoding_key: EncodingKey,
+    algorithm: Algorithm,
+    settings: TokenSettings,
+}
+
+impl TokenMinter {
+    pub fn hs256(secret: &[u8], settings: TokenSettings) -> Self {
+        Self {
+            encoding_key: EncodingKey::from_secret(secret),
+            algorithm: Algorithm::HS256,
+            settings,
+        }
+    }
+
+    pub fn rs256(private_key_pem: &[u8], settings: TokenSettings) -> Result<Self> {
+        Ok(Self {
+            encoding_key: EncodingKey::from_rsa_pem(private_key_pem)?,
+            algorithm: Algorithm::RS256,
+            settings,
+        })
+    }
+
+    pub fn from_env() -> Result<Self> {
+        let settings = TokenSettings::from_env();
+        if let Ok(secret) = env::var("AUTH_HS256_SECRET") {
+            return Ok(Self::hs256(secret.as_bytes(), settings));
+        }
+        let path = env::var("AUTH_RSA_PRIVATE_KEY_FILE")
+            .map_err(|_| anyhow!("Set AUTH_HS256_SECRET or AUTH_RSA_PRIVATE_KEY_FILE"))?;
+        let pem = std::fs::read(&path).with_context(|| format!("Failed to read {path}"))?;
+        Self::rs256(&pem, settings)
+    }
+
+    /// A token for `user_id` that expires after `valid_for`
+    pub fn mint_host_token(&self, user_id: &str, valid_for: Duration) -> Result<String> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let mut claims = Map::new();
+        claims.insert("sub".to_string(), json!(user_id));
+        claims.insert("iss".to_string(), json!(self.settings.issuer));
+        claims.insert("iat".to_string(), json!(now));
+        let exp = now
+            .checked_add(valid_for.as_secs())
+            .ok_or_else(|| anyhow!("Token lifetime is too long"))?;
+        claims.insert("exp".to_string(), json!(exp));
+        claims.insert(
+            self.settings.host_claim.clone(),
+            json!([self.settings.host_group]),
+        );
+
+        encode(&Header::new(self.algorithm), &claims, &self.encoding_key)
+            .map_err(|e| anyhow!("Failed to sign token: {e}"))
+    }
+}
@@ -2,13 +2,19 @@ use std::time::Duration;
 
 use axum::{Router, routing::get};
 use log::*;
-use tokio::{net::TcpListener, sync::mpsc};
+use tokio::{
+    net::TcpListener,
+    signal::unix::{SignalKind, signal},
+    sync::{mpsc, watch},
+};
 use tokio_tungstenite::tungstenite::Result;
 use tower_http::cors::{Any, CorsLayer};
 
 use backend::{
     auth,
     infra::{self, ServiceDiscovery},
+    limits::Limits,
+    local_auth::TokenMinter,
     persistence::PersistenceClient,
     server::start_ws_server,
     timer::ShutdownTimer,
@@ -17,13 +23,36 @@ use std::sync::Arc;
 
 const SHUTDOWN_MINS: u64 = 30;
 
+const MINT_TOKEN_USAGE: &str = "Usage: backend mint-token <user-id> [--days <days>]";
+const DEFAULT_TOKEN_DAYS: u64 = 30;
+
 async fn health_check() -> &'static str {
     "OK"
 }
 
+/// `backend mint-token`: print a host token signed with the self-hosted auth key
+fn mint_token(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let (user_id, days) = match args {
+        [user_id] => (user_id, DEFAULT_TOKEN_DAYS),
+        [user_id, flag, days] if flag == "--days" => (user_id, days.parse()?),
+        _ => return Err(MINT_TOKEN_USAGE.into()),
+    };
+    let seconds = days.checked_mul(24 * 60 * 60).ok_or(MINT_TOKEN_USAGE)?;
+    let minter = TokenMinter::from_env()?;
+    let token = minter.mint_host_token(user_id, Duration::from_secs(seconds))?;
+    println!("{token}");
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenvy::dotenv().ok();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|command| command == "mint-token") {
+        return mint_token(&args[1..]);
+    }
+
     env_logger::init();
     info!("Starting Trivia Wizard 2 backend");
 
@@ -49,7 +78,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let timer = ShutdownTimer::new(shutdown_tx.clone(), Duration::from_secs(SHUTDOWN_MINS * 60));
     let validator = auth::create_validator_from_env();
     let persistence = Arc::new(PersistenceClient::new().await);
-    let ws_server = start_ws_server(ws_listener, timer, validator, persistence);
+    let limits = Limits::from_env();
+    let (stop_tx, stop_rx) = watch::channel(false);
+    let mut ws_server = tokio::spawn(start_ws_server(
+        ws_listener,
+        timer,
+        validator,
+        persistence,
+        limits,
+        auth::allow_query_token_from_env(),
+        stop_rx,
+    ));
 
     let health_app = Router::new()
         .route("/health", get(health_check))
@@ -57,17 +96,33 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let health_listener = TcpListener::bind("0.0.0.0:8080").await.unwrap();
 
+    let mut sigterm = signal(SignalKind::terminate())?;
+
     tokio::select! {
-        _ = ws_server => {
+        _ = &mut ws_server => {
             info!("WS server task finished");
+            return Ok(());
         },
         _ = axum::serve(health_listener, health_app) => {
             info!("Health check server task finished");
         },
         _ = shutdown_rx.recv() => {
-            info!("Shutting down...");
+            info!("Shutdown timer fired");
+        },
+        _ = sigterm.recv() => {
+            info!("Received SIGTERM");
+        },
+        _ = tokio::signal::ctrl_c() => {
+            info!("Received Ctrl-C");
         }
     }
 
+    // Save every game and close client sockets before exiting
+    info!("Shutting down...");
+    let _ = stop_tx.send(true);
+    if let Err(e) = ws_server.await {
+        error!("WS server task failed during shutdown: {e}");
+    }
+
     Ok(())
 }
@@ -1,20 +1,107 @@
+use std::fmt;
+
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::model::types::{McConfig, QuestionConfig, QuestionKind, ScoreData};
+use crate::model::protocol::Encoding;
+use crate::model::types::{
+    GameSettings, McConfig, QuestionConfig, QuestionKind, ScoreData, SpeedBonusMode, StaffRole,
+};
+
+// An access token sent in a message. Its `Debug` output is redacted, so messages carrying
+// one can be logged.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+#[schemars(inline)]
+pub struct Token(String);
+
+impl Token {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Token {
+    fn from(token: String) -> Self {
+        Token(token)
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Token(<redacted>)")
+    }
+}
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub enum HostAction {
     #[serde(rename_all = "camelCase")]
     CreateGame {
         #[serde(skip_serializing_if = "Option::is_none")]
         game_code: Option<String>,
+        /// Start the new game with the settings and questions of this saved game, without
+        /// its teams or answers
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        copy_settings_from: Option<String>,
+    },
+
+    /// List the host's saved games; answered with `SavedGames` to the sender only.
+    /// Allowed before `CreateGame`.
+    ListGames,
+
+    /// Delete one of the host's saved games; answered with `GameDeleted`.
+    /// Allowed before `CreateGame`. Games with a host connected can't be deleted.
+    #[serde(rename_all = "camelCase")]
+    DeleteGame {
+        game_code: String,
+    },
+
+    /// Join another host's game as staff. Sent instead of `CreateGame` by a user the game's
+    /// owner invited; needs a valid token but not the host group.
+    #[serde(rename_all = "camelCase")]
+    JoinAsStaff {
+        game_code: String,
+    },
+
+    /// Let another user help run the game, or change their role. Owner only.
+    #[serde(rename_all = "camelCase")]
+    InviteStaff {
+        user_id: String,
+        role: StaffRole,
+    },
+
+    /// Take back an invitation, disconnecting the user if they're connected. Owner only.
+    #[serde(rename_all = "camelCase")]
+    RemoveStaff {
+        user_id: String,
     },
 
     StartTimer,
     PauseTimer,
     ResetTimer,
 
+    #[serde(rename_all = "camelCase")]
+    AdjustTimer {
+        delta_seconds: i32,
+    },
+
+    /// Clock-offset handshake; answered with a `ClockSync` to the sender only
+    #[serde(rename_all = "camelCase")]
+    SyncClock {
+        client_time_ms: u64,
+    },
+
+    /// Request a full `GameState` snapshot after a gap in patch sequence numbers
+    Resync,
+
+    /// Replace the connection's token with a fresh one for the same user, before it
+    /// expires or to regain control after it did; answered with `Reauthenticated`
+    #[serde(rename_all = "camelCase")]
+    Reauthenticate {
+        token: Token,
+    },
+
     NextQuestion,
     PrevQuestion,
 
@@ -25,6 +112,14 @@ pub enum HostAction {
         score: ScoreData,
     },
 
+    /// Accept or reject an answer that arrived during the late-submission grace window
+    #[serde(rename_all = "camelCase")]
+    ResolveLateAnswer {
+        question_number: usize,
+        team_name: String,
+        accept: bool,
+    },
+
     #[serde(rename_all = "camelCase")]
     OverrideTeamScore {
         team_name: String,
@@ -41,6 +136,12 @@ pub enum HostAction {
         speed_bonus_enabled: bool,
         speed_bonus_num_teams: u32,
         speed_bonus_first_place_points: u32,
+        #[serde(default = "GameSettings::default_late_grace_seconds")]
+        late_grace_seconds: u32,
+        #[serde(default)]
+        speed_bonus_mode: SpeedBonusMode,
+        #[serde(default = "GameSettings::default_timer_warning_seconds")]
+        timer_warning_seconds: Vec<u32>,
     },
 
     #[serde(rename_all = "camelCase")]
@@ -60,7 +161,23 @@ pub enum HostAction {
     },
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl HostAction {
+    /// Whether staff with `role` may send this action. Managing staff is left to the owner.
+    pub fn allowed_for(&self, role: StaffRole) -> bool {
+        match self {
+            HostAction::InviteStaff { .. } | HostAction::RemoveStaff { .. } => false,
+            _ if role == StaffRole::CoHost => true,
+            HostAction::ScoreAnswer { .. }
+            | HostAction::OverrideTeamScore { .. }
+            | HostAction::SyncClock { .. }
+            | HostAction::Resync
+            | HostAction::Reauthenticate { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum TeamAction {
     #[serde(rename_all = "camelCase")]
@@ -80,19 +197,81 @@ pub enum TeamAction {
 
     #[serde(rename_all = "camelCase")]
     SubmitAnswer { team_name: String, answer: String },
+
+    /// Clock-offset handshake; answered with a `ClockSync` to the sender only
+    #[serde(rename_all = "camelCase")]
+    SyncClock { client_time_ms: u64 },
+
+    /// Request a full `TeamGameState` snapshot after a gap in patch sequence numbers
+    Resync,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum WatcherAction {
     #[serde(rename_all = "camelCase")]
     WatchGame { game_code: String },
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Which role a connection is opened for, declared in `Hello`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ClientKind {
+    Host,
+    Team,
+    Watcher,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum ClientMessage {
+    /// Protocol handshake, sent once before the first action; answered with `Welcome`
+    /// or `UnsupportedProtocol`
+    #[serde(rename_all = "camelCase")]
+    Hello {
+        protocol_version: u32,
+        client_kind: ClientKind,
+        /// Encoding for server messages after `Welcome`; JSON if omitted
+        #[serde(default)]
+        encoding: Encoding,
+        /// Access token, for clients that can't set it in the `Sec-WebSocket-Protocol`
+        /// header. Takes the place of one sent there.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<Token>,
+    },
     Host(HostAction),
     Team(TeamAction),
     Watcher(WatcherAction),
 }
+
+/// A client message with the optional ID the client uses to match it to its reply.
+/// The ID is echoed in the `Ack` or `Error` that answers the message.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientEnvelope {
+    #[serde(flatten)]
+    pub message: ClientMessage,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl From<ClientMessage> for ClientEnvelope {
+    fn from(message: ClientMessage) -> Self {
+        ClientEnvelope {
+            message,
+            request_id: None,
+        }
+    }
+}
+
+impl ClientMessage {
+    /// The kind of client that sends this message (None for `Hello`)
+    pub fn kind(&self) -> Option<ClientKind> {
+        match self {
+            ClientMessage::Hello { .. } => None,
+            ClientMessage::Host(_) => Some(ClientKind::Host),
+            ClientMessage::Team(_) => Some(ClientKind::Team),
+            ClientMessage::Watcher(_) => Some(ClientKind::Watcher),
+        }
+    }
+}
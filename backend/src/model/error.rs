@@ -0,0 +1,67 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Machine-readable reason carried by every `ServerMessage::Error`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCode {
+    /// The message couldn't be parsed, or isn't one this connection accepts
+    InvalidMessage,
+    /// The action isn't valid at this point, e.g. a second `CreateGame`
+    UnexpectedAction,
+    /// The connection is sending too fast; the message was dropped
+    RateLimited,
+    /// A frame or message over the size limit; the connection is closed
+    MessageTooLarge,
+    AuthenticationRequired,
+    /// The connection's token expired; it's read-only until `Reauthenticate` succeeds
+    AuthenticationExpired,
+    NotAuthorized,
+    GameNotFound,
+    GameAlreadyHasHost,
+    /// The user already has a connection to this game
+    AlreadyConnected,
+    GameCodeTaken,
+    TeamNameTaken,
+    TeamNotFound,
+    StaffNotFound,
+    QuestionNotFound,
+    AnswerNotFound,
+    AnswerAlreadySubmitted,
+    AnswerTooLong,
+    SubmissionsClosed,
+    AlreadyAtFirstQuestion,
+    /// Question settings can't change once answers are in
+    QuestionHasAnswers,
+    ConfigDoesNotMatchQuestionType,
+    /// A game settings value is out of range
+    InvalidSettings,
+    LoadFailed,
+    SaveFailed,
+    Internal,
+}
+
+/// A rejected client action, reported back to the sender as `ServerMessage::Error`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ActionError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ActionError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ActionError {}
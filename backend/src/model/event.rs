@@ -0,0 +1,65 @@
+//! Game event log.
+//!
+//! Every accepted action that changes a game is recorded as a `GameEvent`. Events are saved
+//! append-only between snapshots, and a game is restored by replaying the events after its
+//! last snapshot (see `Game::replay`).
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::client_message::{HostAction, TeamAction};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameEvent {
+    /// Position in the game's log, counting from 1
+    pub seq: u64,
+    /// Server time the action was accepted
+    pub at_ms: u64,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "source")]
+pub enum EventKind {
+    Host {
+        action: HostAction,
+    },
+    #[serde(rename_all = "camelCase")]
+    Team {
+        team_name: String,
+        action: TeamAction,
+        /// How a `SubmitAnswer` was judged, so replay doesn't depend on the timer
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        submission: Option<Submission>,
+    },
+}
+
+/// How a submitted answer was judged, which depends on the timer when it arrived
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Submission {
+    pub late: bool,
+    pub answer_latency_ms: Option<u64>,
+    /// The question's timer window when the answer arrived, for time-curve bonuses
+    #[serde(default)]
+    pub timer_window_ms: Option<u64>,
+}
+
+/// Serialize a batch of events as JSON lines
+pub fn encode_batch(events: &[GameEvent]) -> serde_json::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    for event in events {
+        serde_json::to_writer(&mut body, event)?;
+        body.push(b'\n');
+    }
+    Ok(body)
+}
+
+/// Parse a batch written by `encode_batch`
+pub fn decode_batch(body: &[u8]) -> serde_json::Result<Vec<GameEvent>> {
+    body.split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(serde_json::from_slice)
+        .collect()
+}
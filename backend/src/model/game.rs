@@ -1,10 +1,20 @@
-use crate::model::server_message::{GameState, ServerMessage, TeamGameState, send_msg};
+use crate::game_timer::{now_ms, seconds_until};
+use crate::model::client_message::{HostAction, TeamAction};
+use crate::model::error::{ActionError, ErrorCode};
+use crate::model::event::{EventKind, GameEvent, Submission};
+use crate::model::patch::PatchStream;
+use crate::model::protocol;
+use crate::model::server_message::{
+    GamePhase, GameState, SavedGameSummary, ServerMessage, TeamGameState, TimerEventKind,
+    send_close, send_msg,
+};
 use crate::model::types::{
     AnswerContent, GameSettings, McConfig, Question, QuestionConfig, QuestionKind, ScoreData,
-    ScoreboardData, TeamColor, TeamData, TeamQuestion,
+    ScoreboardData, SpeedBonusMode, StaffMember, StaffRole, TeamColor, TeamData, TeamQuestion,
 };
+use crate::persistence::SavedGame;
 use crate::server::Tx;
-use anyhow::{Result, anyhow};
+use log::warn;
 use std::collections::HashMap;
 use tokio::task::AbortHandle;
 
@@ -18,6 +28,15 @@ fn normalize_answer_text(content: &Option<AnswerContent>) -> Option<String> {
     }
 }
 
+/// Clients older than patch support get a full snapshot on every update
+fn patch_stream_for(protocol_version: u32) -> PatchStream {
+    if protocol::supports_patches(protocol_version) {
+        PatchStream::default()
+    } else {
+        PatchStream::snapshots_only()
+    }
+}
+
 /// Hardcoded game settings for this iteration
 const DEFAULT_TIMER_DURATION: u32 = 30;
 const DEFAULT_QUESTION_POINTS: u32 = 50;
@@ -26,6 +45,15 @@ const DEFAULT_SPEED_BONUS_ENABLED: bool = false;
 const DEFAULT_SPEED_BONUS_NUM_TEAMS: u32 = 2;
 const DEFAULT_SPEED_BONUS_FIRST_PLACE_POINTS: u32 = 10;
 
+/// Whether a submission arriving now should be accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubmissionWindow {
+    Open,
+    /// Within the grace period after expiry; stored as a late answer for the host to review
+    Late,
+    Closed,
+}
+
 pub struct Game {
     // Connection channels
     pub game_code: String,
@@ -33,17 +61,37 @@ pub struct Game {
     pub host_tx: Option<Tx>,
     pub teams_tx: HashMap<String, Tx>,
     pub watchers_tx: Vec<Tx>,
+    // Connected staff, by user ID
+    pub staff_tx: HashMap<String, Tx>,
+
+    // What each client last received, for sending state as patches
+    pub host_stream: PatchStream,
+    pub team_streams: HashMap<String, PatchStream>,
+    pub staff_streams: HashMap<String, PatchStream>,
 
     // Game state
     pub current_question_number: usize,
     pub timer_running: bool,
     pub timer_seconds_remaining: Option<u32>,
+    pub timer_deadline_ms: Option<u64>,
+    pub timer_expired_at_ms: Option<u64>,
+    // Running time accumulated on the current question, for answer latency
+    pub timer_elapsed_ms: u64,
+    pub timer_run_started_at_ms: Option<u64>,
     pub teams: Vec<TeamData>,
     pub questions: Vec<Question>,
     pub game_settings: GameSettings,
+    // Users invited to help run the game, connected or not
+    pub staff: Vec<StaffMember>,
+    // None for games restored from saves that didn't record it
+    pub created_at_ms: Option<u64>,
 
     // Timer task handle for cancellation
     pub timer_abort_handle: Option<AbortHandle>,
+
+    // Event log: the last sequence number used, and events not yet saved
+    pub event_seq: u64,
+    pub pending_events: Vec<GameEvent>,
 }
 
 impl Game {
@@ -57,6 +105,9 @@ impl Game {
             speed_bonus_enabled: DEFAULT_SPEED_BONUS_ENABLED,
             speed_bonus_num_teams: DEFAULT_SPEED_BONUS_NUM_TEAMS,
             speed_bonus_first_place_points: DEFAULT_SPEED_BONUS_FIRST_PLACE_POINTS,
+            late_grace_seconds: GameSettings::DEFAULT_LATE_GRACE_SECONDS,
+            speed_bonus_mode: SpeedBonusMode::default(),
+            timer_warning_seconds: GameSettings::default_timer_warning_seconds(),
         };
 
         // Initialize with one empty standard question
@@ -68,6 +119,7 @@ impl Game {
             question_config: QuestionConfig::Standard,
             answers: vec![],
             speed_bonus_enabled: DEFAULT_SPEED_BONUS_ENABLED,
+            timer_window_ms: None,
         };
 
         Self {
@@ -76,23 +128,35 @@ impl Game {
             host_tx: Some(host_tx),
             teams_tx: HashMap::new(),
             watchers_tx: Vec::new(),
+            staff_tx: HashMap::new(),
+            host_stream: PatchStream::default(),
+            team_streams: HashMap::new(),
+            staff_streams: HashMap::new(),
             current_question_number: 1,
             timer_running: false,
             timer_seconds_remaining: Some(DEFAULT_TIMER_DURATION),
+            timer_deadline_ms: None,
+            timer_expired_at_ms: None,
+            timer_elapsed_ms: 0,
+            timer_run_started_at_ms: None,
             teams: vec![],
             questions: vec![initial_question],
             game_settings,
+            staff: Vec::new(),
+            created_at_ms: Some(now_ms()),
             timer_abort_handle: None,
+            event_seq: 0,
+            pending_events: Vec::new(),
         }
     }
 
     /// Create a Game from a saved GameState (for S3 restoration).
     /// Sets up connection state (host_tx, empty teams_tx, no timer handle).
-    /// All teams are marked as disconnected since they need to reconnect.
+    /// All teams and staff are marked as disconnected since they need to reconnect.
     pub fn from_saved_state(
         host_user_id: String,
         game_code: String,
-        host_tx: Tx,
+        host_tx: Option<Tx>,
         state: GameState,
     ) -> Self {
         // Mark all teams as disconnected - they need to reconnect
@@ -104,20 +168,105 @@ impl Game {
                 team
             })
             .collect();
+        let staff = state
+            .staff
+            .into_iter()
+            .map(|mut member| {
+                member.connected = false;
+                member
+            })
+            .collect();
 
         Self {
             game_code,
             host_user_id,
-            host_tx: Some(host_tx),
+            host_tx,
             teams_tx: HashMap::new(),
             watchers_tx: Vec::new(),
+            staff_tx: HashMap::new(),
+            host_stream: PatchStream::default(),
+            team_streams: HashMap::new(),
+            staff_streams: HashMap::new(),
             current_question_number: state.current_question_number,
             timer_running: false, // Always start with timer stopped on restore
             timer_seconds_remaining: state.timer_seconds_remaining,
+            timer_deadline_ms: None,
+            timer_expired_at_ms: None,
+            timer_elapsed_ms: 0,
+            timer_run_started_at_ms: None,
             teams,
             questions: state.questions,
             game_settings: state.game_settings,
+            staff,
+            created_at_ms: None,
             timer_abort_handle: None,
+            event_seq: 0,
+            pending_events: Vec::new(),
+        }
+    }
+
+    /// Restore a saved game: its last snapshot plus the events logged after it.
+    /// Teams stay disconnected until they rejoin. Without a host channel the game is only
+    /// for reading, e.g. to summarize or copy it.
+    pub fn restore(
+        host_user_id: String,
+        game_code: String,
+        host_tx: Option<Tx>,
+        saved: SavedGame,
+    ) -> Self {
+        let mut game = Self::from_saved_state(host_user_id, game_code, host_tx, saved.state);
+        game.created_at_ms = saved.meta.created_at_ms;
+        game.event_seq = saved.meta.event_seq;
+        for event in &saved.events {
+            if let Err(e) = game.replay(event) {
+                warn!(
+                    "Skipping event {} of game {} that no longer applies: {}",
+                    event.seq, game.game_code, e.message
+                );
+            }
+            game.event_seq = event.seq;
+        }
+        for team in &mut game.teams {
+            team.connected = false;
+        }
+        for member in &mut game.staff {
+            member.connected = false;
+        }
+        game
+    }
+
+    /// Take another game's settings and questions, without its teams or answers, and start
+    /// over from question 1
+    pub fn copy_settings_from(&mut self, source: &GameState) {
+        self.game_settings = source.game_settings.clone();
+        self.questions = source
+            .questions
+            .iter()
+            .map(|question| Question {
+                answers: vec![],
+                timer_window_ms: None,
+                ..question.clone()
+            })
+            .collect();
+        self.current_question_number = 1;
+        self.timer_seconds_remaining = Some(self.current_question().timer_duration);
+    }
+
+    /// Describe this game for a host's list of saved games
+    pub fn summary(&self, last_played_ms: Option<u64>) -> SavedGameSummary {
+        let phase = if self.questions.iter().any(Question::has_answers) {
+            GamePhase::InProgress
+        } else {
+            GamePhase::NotStarted
+        };
+        SavedGameSummary {
+            game_code: self.game_code.clone(),
+            created_at_ms: self.created_at_ms,
+            last_played_ms,
+            team_count: self.teams.len(),
+            question_count: self.questions.len(),
+            current_question_number: self.current_question_number,
+            phase,
         }
     }
 
@@ -139,6 +288,19 @@ impl Game {
 
     pub fn set_host_tx(&mut self, host_tx: Tx) {
         self.host_tx = Some(host_tx);
+        self.host_stream.reset();
+    }
+
+    /// Match the host's state updates to what its protocol version understands.
+    /// Call after `set_host_tx`, before sending state.
+    pub fn set_host_protocol(&mut self, protocol_version: u32) {
+        self.host_stream = patch_stream_for(protocol_version);
+    }
+
+    /// Like `set_host_protocol`, for a team that just joined or rejoined
+    pub fn set_team_protocol(&mut self, team_name: &str, protocol_version: u32) {
+        self.team_streams
+            .insert(team_name.to_lowercase(), patch_stream_for(protocol_version));
     }
 
     pub fn clear_host_tx(&mut self) {
@@ -147,6 +309,7 @@ impl Game {
 
     pub fn clear_team_tx(&mut self, team_name: &str) {
         self.teams_tx.remove(&team_name.to_lowercase());
+        self.team_streams.remove(&team_name.to_lowercase());
     }
 
     pub fn add_watcher(&mut self, watcher_tx: Tx) {
@@ -157,6 +320,129 @@ impl Game {
         self.watchers_tx.retain(|tx| !tx.same_channel(watcher_tx));
     }
 
+    // === Staff ===
+
+    /// The role a user was invited with, None if they weren't
+    pub fn staff_role(&self, user_id: &str) -> Option<StaffRole> {
+        self.staff
+            .iter()
+            .find(|member| member.user_id == user_id)
+            .map(|member| member.role)
+    }
+
+    /// Whether the owner or any staff member is connected
+    pub fn has_host_connection(&self) -> bool {
+        self.host_tx.is_some() || !self.staff_tx.is_empty()
+    }
+
+    /// Check that `user_id` may send `action` on this game: the owner may send anything,
+    /// staff what their role allows
+    pub fn check_permission(&self, user_id: &str, action: &HostAction) -> Result<(), ActionError> {
+        if user_id == self.host_user_id {
+            return Ok(());
+        }
+        match self.staff_role(user_id) {
+            Some(role) if action.allowed_for(role) => Ok(()),
+            Some(StaffRole::CoHost) => Err(ActionError::new(
+                ErrorCode::NotAuthorized,
+                "Only the game's owner can manage staff",
+            )),
+            Some(StaffRole::Scorekeeper) => Err(ActionError::new(
+                ErrorCode::NotAuthorized,
+                "Scorekeepers can only score answers and override team scores",
+            )),
+            None => Err(ActionError::new(
+                ErrorCode::NotAuthorized,
+                format!("You are no longer staff on game '{}'", self.game_code),
+            )),
+        }
+    }
+
+    /// Invite a user as staff, or change the role of one already invited
+    pub fn invite_staff(&mut self, user_id: String, role: StaffRole) -> Result<(), ActionError> {
+        if user_id == self.host_user_id {
+            return Err(ActionError::new(
+                ErrorCode::UnexpectedAction,
+                "The game's owner can't be invited as staff",
+            ));
+        }
+        match self
+            .staff
+            .iter_mut()
+            .find(|member| member.user_id == user_id)
+        {
+            Some(member) => member.role = role,
+            None => self.staff.push(StaffMember {
+                connected: self.staff_tx.contains_key(&user_id),
+                user_id,
+                role,
+            }),
+        }
+        Ok(())
+    }
+
+    /// Take back a staff invitation, closing the user's connection if they have one
+    pub fn remove_staff(&mut self, user_id: &str) -> Result<(), ActionError> {
+        let before = self.staff.len();
+        self.staff.retain(|member| member.user_id != user_id);
+        if self.staff.len() == before {
+            return Err(ActionError::new(
+                ErrorCode::StaffNotFound,
+                format!("'{user_id}' is not staff on this game"),
+            ));
+        }
+        if let Some(tx) = self.staff_tx.remove(user_id) {
+            send_close(&tx, "Removed from game");
+        }
+        self.staff_streams.remove(user_id);
+        Ok(())
+    }
+
+    /// Connect an invited user. Each staff member may have one connection at a time.
+    pub fn add_staff_connection(
+        &mut self,
+        user_id: &str,
+        staff_tx: Tx,
+        protocol_version: u32,
+    ) -> Result<StaffRole, ActionError> {
+        let Some(role) = self.staff_role(user_id) else {
+            return Err(ActionError::new(
+                ErrorCode::NotAuthorized,
+                format!("You haven't been invited to game '{}'", self.game_code),
+            ));
+        };
+        if self.staff_tx.get(user_id).is_some_and(|tx| !tx.is_closed()) {
+            return Err(ActionError::new(
+                ErrorCode::AlreadyConnected,
+                format!("You're already connected to game '{}'", self.game_code),
+            ));
+        }
+        self.staff_tx.insert(user_id.to_string(), staff_tx);
+        self.staff_streams
+            .insert(user_id.to_string(), patch_stream_for(protocol_version));
+        self.set_staff_connected(user_id, true);
+        Ok(role)
+    }
+
+    /// Forget a staff connection, unless it has already been replaced by a newer one
+    pub fn clear_staff_tx(&mut self, user_id: &str, staff_tx: &Tx) {
+        if self
+            .staff_tx
+            .get(user_id)
+            .is_some_and(|tx| tx.same_channel(staff_tx))
+        {
+            self.staff_tx.remove(user_id);
+            self.staff_streams.remove(user_id);
+            self.set_staff_connected(user_id, false);
+        }
+    }
+
+    fn set_staff_connected(&mut self, user_id: &str, connected: bool) {
+        if let Some(member) = self.staff.iter_mut().find(|m| m.user_id == user_id) {
+            member.connected = connected;
+        }
+    }
+
     pub fn add_team(
         &mut self,
         team_name: String,
@@ -166,7 +452,19 @@ impl Game {
     ) {
         // Add to connection tracking
         self.teams_tx.insert(team_name.to_lowercase(), team_tx);
+        self.team_streams
+            .insert(team_name.to_lowercase(), PatchStream::default());
+        self.register_team(team_name, team_color, team_members);
+    }
 
+    /// Add a team to the game state, or update a returning team's color and members while
+    /// keeping its score
+    pub fn register_team(
+        &mut self,
+        team_name: String,
+        team_color: TeamColor,
+        team_members: Vec<String>,
+    ) {
         // Check if team already exists (reconnection scenario)
         if let Some(team) = self.find_team_mut(&team_name) {
             // Team is reconnecting - preserve their score and update connection status
@@ -192,6 +490,8 @@ impl Game {
             return false;
         }
         self.teams_tx.insert(team_name.to_lowercase(), team_tx);
+        self.team_streams
+            .insert(team_name.to_lowercase(), PatchStream::default());
         // Safe to unwrap since we just checked existence
         self.find_team_mut(team_name).unwrap().connected = true;
         true
@@ -205,16 +505,97 @@ impl Game {
         &mut self.questions[self.current_question_number - 1]
     }
 
+    /// Seconds left on the timer. While running this is derived from the deadline.
+    pub fn seconds_remaining(&self) -> Option<u32> {
+        match self.timer_deadline_ms {
+            Some(deadline) if self.timer_running => Some(seconds_until(deadline)),
+            _ => self.timer_seconds_remaining,
+        }
+    }
+
+    /// Submissions are open while the timer runs and its deadline hasn't passed.
+    /// For `late_grace_seconds` after the timer expires they are accepted as late answers.
+    pub fn submission_window(&self) -> SubmissionWindow {
+        let now = now_ms();
+        let expired_at = if self.timer_running {
+            match self.timer_deadline_ms {
+                Some(deadline) if now < deadline => return SubmissionWindow::Open,
+                deadline => deadline,
+            }
+        } else {
+            self.timer_expired_at_ms
+        };
+
+        let grace_ms = self.game_settings.late_grace_seconds as u64 * 1000;
+        match expired_at {
+            Some(expired_at) if now < expired_at + grace_ms => SubmissionWindow::Late,
+            _ => SubmissionWindow::Closed,
+        }
+    }
+
+    /// Fold the current timer run into `timer_elapsed_ms`, ending it at `end_ms`
+    pub fn end_timer_run(&mut self, end_ms: u64) {
+        if let Some(started_at) = self.timer_run_started_at_ms.take() {
+            self.timer_elapsed_ms += end_ms.saturating_sub(started_at);
+        }
+    }
+
+    /// Running time since the timer started on this question.
+    /// Late answers keep counting from the moment the timer expired.
+    pub fn answer_latency_ms(&self) -> Option<u64> {
+        let now = now_ms();
+        let since = self.timer_run_started_at_ms.or(self.timer_expired_at_ms)?;
+        Some(self.timer_elapsed_ms + now.saturating_sub(since))
+    }
+
+    /// Running time the current question's timer has had plus what's left on it, if it has
+    /// been started
+    pub fn timer_window_ms(&self) -> Option<u64> {
+        let now = now_ms();
+        let running_ms = self
+            .timer_run_started_at_ms
+            .map_or(0, |started_at| now.saturating_sub(started_at));
+        let elapsed_ms = self.timer_elapsed_ms + running_ms;
+        if elapsed_ms == 0 && self.timer_expired_at_ms.is_none() {
+            return None;
+        }
+        let remaining_ms = match self.timer_deadline_ms {
+            Some(deadline) if self.timer_running => deadline.saturating_sub(now),
+            _ => self.timer_seconds_remaining.unwrap_or(0) as u64 * 1000,
+        };
+        Some(elapsed_ms + remaining_ms)
+    }
+
+    /// Record the current question's timer window after it was adjusted or expired, and
+    /// rescore time-curve speed bonuses against it
+    pub fn update_timer_window(&mut self) {
+        self.set_timer_window(self.timer_window_ms());
+    }
+
+    fn set_timer_window(&mut self, window_ms: Option<u64>) {
+        let question_idx = self.current_question_number - 1;
+        if window_ms.is_none() || self.questions[question_idx].timer_window_ms == window_ms {
+            return;
+        }
+        self.questions[question_idx].timer_window_ms = window_ms;
+        for team in self.recalculate_speed_bonuses(question_idx) {
+            self.recalculate_team_score(&team);
+        }
+    }
+
     /// Convert to the wire format for host clients
     pub fn to_game_state(&self) -> GameState {
         GameState {
             game_code: self.game_code.clone(),
             current_question_number: self.current_question_number,
             timer_running: self.timer_running,
-            timer_seconds_remaining: self.timer_seconds_remaining,
+            timer_seconds_remaining: self.seconds_remaining(),
+            timer_deadline_ms: self.timer_deadline_ms,
+            server_time_ms: now_ms(),
             teams: self.teams.clone(),
             questions: self.questions.clone(),
             game_settings: self.game_settings.clone(),
+            staff: self.staff.clone(),
         }
     }
 
@@ -231,7 +612,9 @@ impl Game {
             game_code: self.game_code.clone(),
             current_question_number: self.current_question_number,
             timer_running: self.timer_running,
-            timer_seconds_remaining: self.timer_seconds_remaining,
+            timer_seconds_remaining: self.seconds_remaining(),
+            timer_deadline_ms: self.timer_deadline_ms,
+            server_time_ms: now_ms(),
             team: team.clone(),
             questions,
         })
@@ -264,6 +647,7 @@ impl Game {
             question_config,
             answers: vec![],
             speed_bonus_enabled: self.game_settings.speed_bonus_enabled,
+            timer_window_ms: None,
         }
     }
 
@@ -273,6 +657,10 @@ impl Game {
             handle.abort();
         }
         self.timer_running = false;
+        self.timer_deadline_ms = None;
+        self.timer_expired_at_ms = None;
+        self.timer_elapsed_ms = 0;
+        self.timer_run_started_at_ms = None;
     }
 
     /// Navigate to the next question. Creates a new question if needed.
@@ -294,11 +682,13 @@ impl Game {
     }
 
     /// Navigate to the previous question. Returns error if already at question 1.
-    pub fn prev_question(&mut self) -> anyhow::Result<()> {
-        anyhow::ensure!(
-            self.current_question_number > 1,
-            "Already at first question"
-        );
+    pub fn prev_question(&mut self) -> Result<(), ActionError> {
+        if self.current_question_number <= 1 {
+            return Err(ActionError::new(
+                ErrorCode::AlreadyAtFirstQuestion,
+                "Already at first question",
+            ));
+        }
 
         // Stop timer if running
         self.stop_timer();
@@ -312,37 +702,204 @@ impl Game {
         Ok(())
     }
 
-    /// Broadcast full GameState to host and TeamGameState to all teams
-    pub fn broadcast_game_state(&self) {
-        // Send full GameState to host
-        if let Some(host_tx) = &self.host_tx {
-            send_msg(
-                host_tx,
-                ServerMessage::GameState {
-                    state: self.to_game_state(),
-                },
-            );
+    /// Drop channels whose clients disconnected or fell too far behind.
+    /// Teams losing their channel are marked disconnected.
+    pub fn prune_closed_channels(&mut self) {
+        let closed_teams: Vec<String> = self
+            .teams_tx
+            .iter()
+            .filter(|(_, tx)| tx.is_closed())
+            .map(|(team_name, _)| team_name.clone())
+            .collect();
+        for team_name in closed_teams {
+            self.clear_team_tx(&team_name);
+            self.set_team_connected(&team_name, false);
+        }
+        let closed_staff: Vec<String> = self
+            .staff_tx
+            .iter()
+            .filter(|(_, tx)| tx.is_closed())
+            .map(|(user_id, _)| user_id.clone())
+            .collect();
+        for user_id in closed_staff {
+            self.staff_tx.remove(&user_id);
+            self.staff_streams.remove(&user_id);
+            self.set_staff_connected(&user_id, false);
         }
+        self.watchers_tx.retain(|tx| !tx.is_closed());
+    }
 
-        // Send filtered TeamGameState to each team
-        for (team_name, team_tx) in &self.teams_tx {
-            if let Some(team_state) = self.to_team_game_state(team_name) {
-                send_msg(team_tx, ServerMessage::TeamGameState { state: team_state });
-            }
+    /// Send the host and every connected staff member their state: a patch against what
+    /// each last received, or a full snapshot after (re)connecting, a resync, or when
+    /// patches are backing up in its outbox. Nothing is sent if the state didn't change.
+    pub fn send_host_state(&mut self) {
+        let staff: Vec<String> = self.staff_tx.keys().cloned().collect();
+        let host_user_id = self.host_user_id.clone();
+        for user_id in std::iter::once(host_user_id).chain(staff) {
+            self.send_host_state_to(&user_id);
         }
+    }
 
-        // Send scoreboard data to all watchers
+    /// Send one host-side connection, the owner's or a staff member's, its state
+    fn send_host_state_to(&mut self, user_id: &str) {
+        let tx = if user_id == self.host_user_id {
+            self.host_tx.clone()
+        } else {
+            self.staff_tx.get(user_id).cloned()
+        };
+        let Some(tx) = tx else {
+            return;
+        };
+        let state = self.to_game_state();
+        let value = serde_json::to_value(&state).expect("GameState serializes to JSON");
+        let stream = if user_id == self.host_user_id {
+            &mut self.host_stream
+        } else {
+            self.staff_streams.entry(user_id.to_string()).or_default()
+        };
+        if tx.has_patch_backlog() {
+            // Collapse the patches the client hasn't read yet into one snapshot
+            stream.reset();
+        }
+        let msg = match stream.next(value) {
+            Some((seq, Some(ops))) => ServerMessage::GameStatePatch { seq, ops },
+            Some((seq, None)) => ServerMessage::GameState { seq, state },
+            None => return,
+        };
+        send_msg(&tx, msg);
+    }
+
+    /// Send a team its state, as a patch or snapshot like `send_host_state`
+    pub fn send_team_state(&mut self, team_name: &str) {
+        let key = team_name.to_lowercase();
+        let Some(team_tx) = self.teams_tx.get(&key).cloned() else {
+            return;
+        };
+        let Some(state) = self.to_team_game_state(team_name) else {
+            return;
+        };
+        let value = serde_json::to_value(&state).expect("TeamGameState serializes to JSON");
+        let stream = self.team_streams.entry(key).or_default();
+        if team_tx.has_patch_backlog() {
+            stream.reset();
+        }
+        let msg = match stream.next(value) {
+            Some((seq, Some(ops))) => ServerMessage::TeamGameStatePatch { seq, ops },
+            Some((seq, None)) => ServerMessage::TeamGameState { seq, state },
+            None => return,
+        };
+        send_msg(&team_tx, msg);
+    }
+
+    /// Send the owner or a staff member a full snapshot, e.g. after it detected a gap in
+    /// patch sequence numbers
+    pub fn resync_host(&mut self, user_id: &str) {
+        if user_id == self.host_user_id {
+            self.host_stream.reset();
+        } else if let Some(stream) = self.staff_streams.get_mut(user_id) {
+            stream.reset();
+        }
+        self.send_host_state_to(user_id);
+    }
+
+    /// Send a team a full snapshot, e.g. after it detected a gap in patch sequence numbers
+    pub fn resync_team(&mut self, team_name: &str) {
+        if let Some(stream) = self.team_streams.get_mut(&team_name.to_lowercase()) {
+            stream.reset();
+        }
+        self.send_team_state(team_name);
+    }
+
+    /// Broadcast GameState to host and staff, and TeamGameState to all teams
+    pub fn broadcast_game_state(&mut self) {
+        self.send_host_state();
+
+        let team_names: Vec<String> = self.teams_tx.keys().cloned().collect();
+        for team_name in team_names {
+            self.send_team_state(&team_name);
+        }
+
+        // Send scoreboard data to all watchers (prunes closed channels)
         self.broadcast_scoreboard_data();
     }
 
+    /// Broadcast a timer event to host, staff, all teams and all watchers
+    pub fn broadcast_timer_event(&mut self, event: TimerEventKind) {
+        let msg = ServerMessage::TimerEvent {
+            event,
+            seconds_remaining: self.seconds_remaining().unwrap_or(0),
+            deadline_ms: self.timer_deadline_ms,
+            server_time_ms: now_ms(),
+        };
+        for tx in self.all_connections() {
+            send_msg(tx, msg.clone());
+        }
+        self.prune_closed_channels();
+    }
+
+    /// Send version 1 clients the seconds left on the timer
+    pub fn send_timer_tick(&self) {
+        let msg = ServerMessage::TimerTick {
+            seconds_remaining: self.seconds_remaining().unwrap_or(0),
+        };
+        for tx in self.legacy_connections() {
+            send_msg(tx, msg.clone());
+        }
+    }
+
+    pub fn has_legacy_connections(&self) -> bool {
+        self.legacy_connections().next().is_some()
+    }
+
+    /// Host and teams on protocol version 1, which predates deadlines and patches
+    fn legacy_connections(&self) -> impl Iterator<Item = &Tx> {
+        let host = self
+            .host_tx
+            .iter()
+            .filter(|_| self.host_stream.is_snapshots_only());
+        let teams = self
+            .teams_tx
+            .iter()
+            .filter(|(key, _)| {
+                self.team_streams
+                    .get(*key)
+                    .is_some_and(PatchStream::is_snapshots_only)
+            })
+            .map(|(_, tx)| tx);
+        host.chain(teams)
+    }
+
+    /// Send a message to the host, staff, all teams and all watchers
+    pub fn broadcast_to_all(&self, msg: ServerMessage) {
+        for tx in self.all_connections() {
+            send_msg(tx, msg.clone());
+        }
+    }
+
+    /// Close every client's socket once its queued messages are sent
+    pub fn close_all_connections(&self, reason: &str) {
+        for tx in self.all_connections() {
+            send_close(tx, reason);
+        }
+    }
+
+    fn all_connections(&self) -> impl Iterator<Item = &Tx> {
+        self.host_tx
+            .iter()
+            .chain(self.staff_tx.values())
+            .chain(self.teams_tx.values())
+            .chain(self.watchers_tx.iter())
+    }
+
     /// Broadcast scoreboard data to all watchers
-    pub fn broadcast_scoreboard_data(&self) {
+    pub fn broadcast_scoreboard_data(&mut self) {
         let scoreboard_msg = ServerMessage::ScoreboardData {
             data: self.to_scoreboard_data(),
         };
         for watcher_tx in &self.watchers_tx {
             send_msg(watcher_tx, scoreboard_msg.clone());
         }
+        self.prune_closed_channels();
     }
 
     // === Answer submission ===
@@ -350,7 +907,18 @@ impl Game {
     /// Add an answer to the current question. Returns false if team already submitted.
     /// If the answer matches an existing scored-correct answer (case-insensitive, trimmed),
     /// the new answer is automatically scored correct as well.
-    pub fn add_answer(&mut self, team_name: &str, answer_text: String) -> bool {
+    /// Late answers are stored unscored until the host accepts them.
+    pub fn add_answer(
+        &mut self,
+        team_name: &str,
+        answer_text: String,
+        submission: Submission,
+    ) -> bool {
+        let Submission {
+            late,
+            answer_latency_ms,
+            timer_window_ms,
+        } = submission;
         let question = self.current_question_mut();
 
         // Check if team already submitted
@@ -373,48 +941,111 @@ impl Game {
             QuestionKind::MultiAnswer => return false, // Not supported yet
         };
 
-        // Check if this answer matches any already-scored-correct answer
-        let question_base_points = question.question_points as i32;
-        let normalized_new = answer_text.trim().to_lowercase();
-
-        let auto_score = question.answers.iter().find_map(|existing| {
-            if existing.score.question_points == question_base_points {
-                if let Some(existing_text) = normalize_answer_text(&existing.content) {
-                    if existing_text == normalized_new {
-                        return Some((question_base_points, existing.score.bonus_points));
-                    }
-                }
-            }
-            None
-        });
-
-        let mut new_score = ScoreData::new();
-        if let Some((question_points, bonus_points)) = auto_score {
-            new_score.question_points = question_points;
-            new_score.bonus_points = bonus_points;
-        }
-
         question.answers.push(TeamQuestion {
             team_name: team_name.to_string(),
-            score: new_score,
+            score: ScoreData::new(),
             content: Some(content),
             question_kind: question.question_kind,
             question_config: question.question_config.clone(),
+            late,
+            answer_latency_ms,
         });
 
-        // If auto-scored, recalculate speed bonuses and team scores
-        if auto_score.is_some() {
+        if !late {
+            self.set_timer_window(timer_window_ms);
             let question_idx = self.current_question_number - 1;
-            let speed_bonus_teams = self.recalculate_speed_bonuses(question_idx);
-            self.recalculate_team_score(team_name);
-            for team in speed_bonus_teams {
-                if team != team_name {
-                    self.recalculate_team_score(&team);
+            self.apply_auto_score(question_idx, team_name);
+        }
+
+        true
+    }
+
+    /// Score an answer to match an already-scored-correct answer with the same text, if any.
+    /// Recalculates speed bonuses and team scores when a match is found.
+    fn apply_auto_score(&mut self, question_idx: usize, team_name: &str) {
+        let question = &mut self.questions[question_idx];
+        let question_base_points = question.question_points as i32;
+
+        let Some(answer_idx) = question
+            .answers
+            .iter()
+            .position(|a| a.team_name.eq_ignore_ascii_case(team_name))
+        else {
+            return;
+        };
+        let Some(normalized_new) = normalize_answer_text(&question.answers[answer_idx].content)
+        else {
+            return;
+        };
+
+        // Check if this answer matches any already-scored-correct answer
+        let auto_score = question
+            .answers
+            .iter()
+            .enumerate()
+            .find_map(|(i, existing)| {
+                if i != answer_idx
+                    && !existing.late
+                    && existing.score.question_points == question_base_points
+                    && normalize_answer_text(&existing.content).as_ref() == Some(&normalized_new)
+                {
+                    return Some((question_base_points, existing.score.bonus_points));
                 }
+                None
+            });
+
+        let Some((question_points, bonus_points)) = auto_score else {
+            return;
+        };
+        let answer = &mut question.answers[answer_idx];
+        answer.score.question_points = question_points;
+        answer.score.bonus_points = bonus_points;
+
+        // Auto-scored, so recalculate speed bonuses and team scores
+        let speed_bonus_teams = self.recalculate_speed_bonuses(question_idx);
+        self.recalculate_team_score(team_name);
+        for team in speed_bonus_teams {
+            if team != team_name {
+                self.recalculate_team_score(&team);
             }
         }
+    }
 
-        true
+    /// Accept or reject a late answer. Accepted answers are treated like on-time answers
+    /// (including auto-scoring); rejected answers are removed.
+    pub fn resolve_late_answer(
+        &mut self,
+        question_number: usize,
+        team_name: &str,
+        accept: bool,
+    ) -> Result<(), ActionError> {
+        let question_idx = question_number
+            .checked_sub(1)
+            .filter(|idx| *idx < self.questions.len())
+            .ok_or_else(|| {
+                ActionError::new(ErrorCode::QuestionNotFound, "Question does not exist")
+            })?;
+
+        let question = &mut self.questions[question_idx];
+        let answer_idx = question
+            .answers
+            .iter()
+            .position(|a| a.late && a.team_name.eq_ignore_ascii_case(team_name))
+            .ok_or_else(|| {
+                ActionError::new(
+                    ErrorCode::AnswerNotFound,
+                    format!("No late answer from team '{team_name}'"),
+                )
+            })?;
+
+        if accept {
+            question.answers[answer_idx].late = false;
+            self.apply_auto_score(question_idx, team_name);
+        } else {
+            question.answers.remove(answer_idx);
+        }
+
+        Ok(())
     }
 
     // === Scoring operations ===
@@ -436,11 +1067,11 @@ impl Game {
 
         let question = &mut self.questions[question_idx];
 
-        // Find the target answer's index
+        // Find the target answer's index (late answers must be accepted before scoring)
         let Some(answer_idx) = question
             .answers
             .iter()
-            .position(|a| a.team_name.eq_ignore_ascii_case(team_name))
+            .position(|a| !a.late && a.team_name.eq_ignore_ascii_case(team_name))
         else {
             return false;
         };
@@ -476,17 +1107,16 @@ impl Game {
         // Sync question_points and bonus_points to all matching answers
         let question = &mut self.questions[question_idx];
         for (i, other_answer) in question.answers.iter_mut().enumerate() {
-            if i != answer_idx {
-                if let Some(other_text) = normalize_answer_text(&other_answer.content) {
-                    if other_text == normalized_text
-                        && (other_answer.score.question_points != score.question_points
-                            || other_answer.score.bonus_points != score.bonus_points)
-                    {
-                        other_answer.score.question_points = score.question_points;
-                        other_answer.score.bonus_points = score.bonus_points;
-                        teams_to_update.push(other_answer.team_name.clone());
-                    }
-                }
+            if i != answer_idx
+                && !other_answer.late
+                && let Some(other_text) = normalize_answer_text(&other_answer.content)
+                && other_text == normalized_text
+                && (other_answer.score.question_points != score.question_points
+                    || other_answer.score.bonus_points != score.bonus_points)
+            {
+                other_answer.score.question_points = score.question_points;
+                other_answer.score.bonus_points = score.bonus_points;
+                teams_to_update.push(other_answer.team_name.clone());
             }
         }
 
@@ -531,7 +1161,7 @@ impl Game {
             if let Some(answer) = question
                 .answers
                 .iter()
-                .find(|a| a.team_name.eq_ignore_ascii_case(team_name))
+                .find(|a| !a.late && a.team_name.eq_ignore_ascii_case(team_name))
             {
                 total_question_points += answer.score.question_points;
                 total_bonus_points += answer.score.bonus_points;
@@ -556,13 +1186,32 @@ impl Game {
         (first_place_points as i32 * remaining as i32) / num_teams as i32
     }
 
+    /// Calculate speed bonus points on a time curve: full points for an instant answer,
+    /// decreasing linearly to 0 at the end of the question's timer window
+    fn calculate_time_curve_bonus(
+        latency_ms: u64,
+        duration_ms: u64,
+        first_place_points: u32,
+    ) -> i32 {
+        if duration_ms == 0 {
+            return 0;
+        }
+        let remaining_ms = duration_ms.saturating_sub(latency_ms);
+        (first_place_points as u64 * remaining_ms / duration_ms) as i32
+    }
+
     /// Recalculate speed bonuses for all answers in a question.
     /// Returns the list of team names whose scores changed.
     fn recalculate_speed_bonuses(&mut self, question_idx: usize) -> Vec<String> {
         let num_teams = self.game_settings.speed_bonus_num_teams;
         let first_place_points = self.game_settings.speed_bonus_first_place_points;
+        let mode = self.game_settings.speed_bonus_mode;
 
         let question = &mut self.questions[question_idx];
+        // The timer's actual run, adjustments included, or its configured length if unknown
+        let window_ms = question
+            .timer_window_ms
+            .unwrap_or(question.timer_duration as u64 * 1000);
         let mut teams_changed = Vec::new();
 
         // If speed bonus is disabled for this question, clear all speed bonuses
@@ -579,8 +1228,18 @@ impl Game {
         // Count correct answers in submission order (answers are stored in submission order)
         let mut place = 0usize;
         for answer in &mut question.answers {
-            let new_speed_bonus = if answer.score.question_points > 0 {
-                let bonus = Self::calculate_speed_bonus(place, num_teams, first_place_points);
+            let new_speed_bonus = if !answer.late && answer.score.question_points > 0 {
+                let bonus = match mode {
+                    SpeedBonusMode::Placement => {
+                        Self::calculate_speed_bonus(place, num_teams, first_place_points)
+                    }
+                    SpeedBonusMode::TimeCurve => answer
+                        .answer_latency_ms
+                        .map(|latency| {
+                            Self::calculate_time_curve_bonus(latency, window_ms, first_place_points)
+                        })
+                        .unwrap_or(0),
+                };
                 place += 1;
                 bonus
             } else {
@@ -600,7 +1259,24 @@ impl Game {
 
     /// Update game-level settings.
     /// Also updates any existing questions that have NOT yet received answers.
-    pub fn update_game_settings(&mut self, settings: GameSettings) {
+    /// Returns Err if a timer warning is zero or longer than the default timer.
+    pub fn update_game_settings(&mut self, mut settings: GameSettings) -> Result<(), ActionError> {
+        settings.timer_warning_seconds.sort_unstable();
+        settings.timer_warning_seconds.dedup();
+        if let Some(seconds) = settings
+            .timer_warning_seconds
+            .iter()
+            .find(|seconds| **seconds == 0 || **seconds > settings.default_timer_duration)
+        {
+            return Err(ActionError::new(
+                ErrorCode::InvalidSettings,
+                format!(
+                    "Timer warning of {seconds}s must be between 1s and the {}s timer",
+                    settings.default_timer_duration
+                ),
+            ));
+        }
+
         self.game_settings = settings.clone();
 
         // Build the question config for the new default question type
@@ -629,6 +1305,7 @@ impl Game {
         if !current_q.has_answers() && !self.timer_running {
             self.timer_seconds_remaining = Some(settings.default_timer_duration);
         }
+        Ok(())
     }
 
     /// Update settings for a specific question.
@@ -641,18 +1318,8 @@ impl Game {
         bonus_increment: u32,
         question_type: QuestionKind,
         speed_bonus_enabled: bool,
-    ) -> Result<()> {
-        let question_idx = question_number - 1;
-        if question_idx >= self.questions.len() {
-            return Err(anyhow!("Question does not exist"));
-        }
-
-        let question = &mut self.questions[question_idx];
-        if question.has_answers() {
-            return Err(anyhow!(
-                "Cannot update settings for a question that has answers"
-            ));
-        }
+    ) -> Result<(), ActionError> {
+        let question = self.question_for_settings_update(question_number)?;
 
         question.timer_duration = timer_duration;
         question.question_points = question_points;
@@ -681,22 +1348,39 @@ impl Game {
         Ok(())
     }
 
-    pub fn update_type_specific_settings(
+    /// The question to update, provided it exists and has no answers yet
+    fn question_for_settings_update(
         &mut self,
         question_number: usize,
-        question_config: QuestionConfig,
-    ) -> Result<()> {
-        let question_idx = question_number - 1;
-        let question = &mut self.questions[question_idx];
+    ) -> Result<&mut Question, ActionError> {
+        let question = question_number
+            .checked_sub(1)
+            .and_then(|idx| self.questions.get_mut(idx))
+            .ok_or_else(|| {
+                ActionError::new(ErrorCode::QuestionNotFound, "Question does not exist")
+            })?;
 
         if question.has_answers() {
-            return Err(anyhow!(
-                "Cannot update settings for a question that has answers"
+            return Err(ActionError::new(
+                ErrorCode::QuestionHasAnswers,
+                "Cannot update settings for a question that has answers",
             ));
         }
+        Ok(question)
+    }
+
+    pub fn update_type_specific_settings(
+        &mut self,
+        question_number: usize,
+        question_config: QuestionConfig,
+    ) -> Result<(), ActionError> {
+        let question = self.question_for_settings_update(question_number)?;
 
         if question_config.kind() != question.question_kind {
-            return Err(anyhow!("Config type does not match question type"));
+            return Err(ActionError::new(
+                ErrorCode::ConfigDoesNotMatchQuestionType,
+                "Config type does not match question type",
+            ));
         }
 
         question.question_config = question_config;
@@ -714,11 +1398,196 @@ impl Game {
             false
         }
     }
+
+    // === Actions and the event log ===
+
+    /// Apply a host action that doesn't involve the timer task.
+    /// Timer actions are handled by `game_timer`, which needs the app state to run expiry.
+    pub fn apply_host_action(&mut self, action: HostAction) -> Result<(), ActionError> {
+        match action {
+            HostAction::CreateGame { .. } => Err(ActionError::new(
+                ErrorCode::UnexpectedAction,
+                "Game already created",
+            )),
+            HostAction::JoinAsStaff { .. } => Err(ActionError::new(
+                ErrorCode::UnexpectedAction,
+                "Already connected to a game",
+            )),
+            HostAction::StartTimer
+            | HostAction::PauseTimer
+            | HostAction::ResetTimer
+            | HostAction::AdjustTimer { .. } => Err(ActionError::new(
+                ErrorCode::Internal,
+                "Timer actions are handled by the game timer",
+            )),
+            HostAction::SyncClock { .. }
+            | HostAction::Resync
+            | HostAction::Reauthenticate { .. }
+            | HostAction::ListGames
+            | HostAction::DeleteGame { .. } => Err(ActionError::new(
+                ErrorCode::Internal,
+                "Action does not modify game state",
+            )),
+            HostAction::InviteStaff { user_id, role } => self.invite_staff(user_id, role),
+            HostAction::RemoveStaff { user_id } => self.remove_staff(&user_id),
+            HostAction::NextQuestion => {
+                self.next_question();
+                Ok(())
+            }
+            HostAction::PrevQuestion => self.prev_question(),
+            HostAction::ScoreAnswer {
+                question_number,
+                team_name,
+                score,
+            } => {
+                if !self.score_answer(question_number, &team_name, score) {
+                    return Err(ActionError::new(
+                        ErrorCode::AnswerNotFound,
+                        format!("Failed to score answer for team '{team_name}'"),
+                    ));
+                }
+                Ok(())
+            }
+            HostAction::ResolveLateAnswer {
+                question_number,
+                team_name,
+                accept,
+            } => self.resolve_late_answer(question_number, &team_name, accept),
+            HostAction::OverrideTeamScore {
+                team_name,
+                override_points,
+            } => {
+                if !self.override_team_score(&team_name, override_points) {
+                    return Err(ActionError::new(
+                        ErrorCode::TeamNotFound,
+                        format!("Team '{team_name}' not found"),
+                    ));
+                }
+                Ok(())
+            }
+            HostAction::UpdateGameSettings {
+                default_timer_duration,
+                default_question_points,
+                default_bonus_increment,
+                default_question_type,
+                default_mc_config,
+                speed_bonus_enabled,
+                speed_bonus_num_teams,
+                speed_bonus_first_place_points,
+                late_grace_seconds,
+                speed_bonus_mode,
+                timer_warning_seconds,
+            } => self.update_game_settings(GameSettings {
+                default_timer_duration,
+                default_question_points,
+                default_bonus_increment,
+                default_question_type,
+                default_mc_config,
+                speed_bonus_enabled,
+                speed_bonus_num_teams,
+                speed_bonus_first_place_points,
+                late_grace_seconds,
+                speed_bonus_mode,
+                timer_warning_seconds,
+            }),
+            HostAction::UpdateQuestionSettings {
+                question_number,
+                timer_duration,
+                question_points,
+                bonus_increment,
+                question_type,
+                speed_bonus_enabled,
+            } => self.update_question_settings(
+                question_number,
+                timer_duration,
+                question_points,
+                bonus_increment,
+                question_type,
+                speed_bonus_enabled,
+            ),
+            HostAction::UpdateTypeSpecificSettings {
+                question_number,
+                question_config,
+            } => self.update_type_specific_settings(question_number, question_config),
+        }
+    }
+
+    /// Log an accepted action, to be saved with the next autosave
+    pub fn record_event(&mut self, kind: EventKind) {
+        self.event_seq += 1;
+        self.pending_events.push(GameEvent {
+            seq: self.event_seq,
+            at_ms: now_ms(),
+            kind,
+        });
+    }
+
+    /// Events logged since the last call, for saving
+    pub fn take_pending_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.pending_events)
+    }
+
+    /// Put back events whose save failed, ahead of any logged since
+    pub fn requeue_events(&mut self, mut events: Vec<GameEvent>) {
+        events.append(&mut self.pending_events);
+        self.pending_events = events;
+    }
+
+    /// Re-apply a logged action to the state it was originally applied to.
+    /// Timer actions are skipped: a restored game's timer always starts stopped, and answers
+    /// carry how they were judged.
+    pub fn replay(&mut self, event: &GameEvent) -> Result<(), ActionError> {
+        match &event.kind {
+            EventKind::Host {
+                action:
+                    HostAction::StartTimer
+                    | HostAction::PauseTimer
+                    | HostAction::ResetTimer
+                    | HostAction::AdjustTimer { .. },
+            } => Ok(()),
+            EventKind::Host { action } => self.apply_host_action(action.clone()),
+            EventKind::Team {
+                action:
+                    TeamAction::JoinGame {
+                        team_name,
+                        color_hex,
+                        color_name,
+                        team_members,
+                        ..
+                    },
+                ..
+            } => {
+                let team_color = TeamColor {
+                    hex_code: color_hex.clone(),
+                    name: color_name.clone(),
+                };
+                self.register_team(team_name.clone(), team_color, team_members.clone());
+                Ok(())
+            }
+            EventKind::Team {
+                team_name,
+                action: TeamAction::SubmitAnswer { answer, .. },
+                submission,
+            } => {
+                let submission = submission.unwrap_or_default();
+                if !self.add_answer(team_name, answer.clone(), submission) {
+                    return Err(ActionError::new(
+                        ErrorCode::AnswerAlreadySubmitted,
+                        "Answer already submitted",
+                    ));
+                }
+                Ok(())
+            }
+            EventKind::Team { .. } => Ok(()),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::model::protocol::Encoding;
+    use crate::outbox;
 
     #[test]
     fn test_calculate_speed_bonus_3_teams_10_points() {
@@ -768,6 +1637,21 @@ mod tests {
         assert_eq!(Game::calculate_speed_bonus(1, 1, 10), 0);
     }
 
+    #[test]
+    fn test_calculate_time_curve_bonus_30_seconds_10_points() {
+        // Linear from 10 points at 0s down to 0 at 30s
+        assert_eq!(Game::calculate_time_curve_bonus(0, 30_000, 10), 10);
+        assert_eq!(Game::calculate_time_curve_bonus(15_000, 30_000, 10), 5);
+        assert_eq!(Game::calculate_time_curve_bonus(27_000, 30_000, 10), 1);
+        assert_eq!(Game::calculate_time_curve_bonus(30_000, 30_000, 10), 0);
+        assert_eq!(Game::calculate_time_curve_bonus(45_000, 30_000, 10), 0);
+    }
+
+    #[test]
+    fn test_calculate_time_curve_bonus_zero_duration() {
+        assert_eq!(Game::calculate_time_curve_bonus(0, 0, 10), 0);
+    }
+
     #[test]
     fn test_calculate_speed_bonus_zero_points() {
         // Even with teams eligible, 0 first place points = 0 for everyone
@@ -775,4 +1659,44 @@ mod tests {
         assert_eq!(Game::calculate_speed_bonus(1, 3, 0), 0);
         assert_eq!(Game::calculate_speed_bonus(2, 3, 0), 0);
     }
+
+    #[test]
+    fn test_broadcast_prunes_closed_team_channels() {
+        let (host_tx, _host_rx) = outbox::channel(Encoding::Json);
+        let mut game = Game::new("TEST".to_string(), host_tx, "user".to_string());
+        let (team_tx, team_rx) = outbox::channel(Encoding::Json);
+        let team_color = TeamColor {
+            hex_code: "#000000".to_string(),
+            name: "Black".to_string(),
+        };
+        game.add_team("Team A".to_string(), team_tx, team_color, vec![]);
+
+        // Team's connection handler exited without cleaning up
+        drop(team_rx);
+        game.broadcast_game_state();
+
+        assert!(game.teams_tx.is_empty());
+        assert!(!game.find_team("Team A").unwrap().connected);
+    }
+
+    #[tokio::test]
+    async fn test_patch_backlog_collapses_into_snapshot() {
+        let (host_tx, mut host_rx) = outbox::channel(Encoding::Json);
+        let mut game = Game::new("TEST".to_string(), host_tx, "user".to_string());
+
+        // A snapshot, then patches the host never reads
+        for points in 0..=outbox::PATCH_BACKLOG as u32 {
+            game.game_settings.default_question_points = points;
+            game.send_host_state();
+        }
+        // With the backlog full, the next update replaces it with a snapshot
+        game.game_settings.default_question_points = 100;
+        game.send_host_state();
+
+        let message = host_rx.recv().await.unwrap();
+        let message: serde_json::Value = serde_json::from_str(message.to_text().unwrap()).unwrap();
+        assert_eq!(message["type"], "gameState");
+        assert_eq!(message["seq"], outbox::PATCH_BACKLOG as u64 + 2);
+        assert!(!game.host_tx.as_ref().unwrap().has_patch_backlog());
+    }
 }
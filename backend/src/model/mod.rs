@@ -1,4 +1,8 @@
 pub mod client_message;
+pub mod error;
+pub mod event;
 pub mod game;
+pub mod patch;
+pub mod protocol;
 pub mod server_message;
 pub mod types;
@@ -0,0 +1,330 @@
+use anyhow::{Result, anyhow, bail};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// One JSON Patch (RFC 6902) operation. Only the subset needed to describe state
+/// changes is supported: add, remove and replace.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", tag = "op")]
+pub enum PatchOp {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+}
+
+impl PatchOp {
+    pub fn path(&self) -> &str {
+        match self {
+            PatchOp::Add { path, .. }
+            | PatchOp::Remove { path }
+            | PatchOp::Replace { path, .. } => path,
+        }
+    }
+}
+
+/// Operations that turn `old` into `new`. Arrays are diffed index by index, with
+/// items appended or removed at the end.
+pub fn diff(old: &Value, new: &Value) -> Vec<PatchOp> {
+    let mut ops = Vec::new();
+    diff_into(old, new, &mut String::new(), &mut ops);
+    ops
+}
+
+fn diff_into(old: &Value, new: &Value, path: &mut String, ops: &mut Vec<PatchOp>) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (key, old_value) in old_map {
+                let len = path.len();
+                push_token(path, key);
+                match new_map.get(key) {
+                    Some(new_value) => diff_into(old_value, new_value, path, ops),
+                    None => ops.push(PatchOp::Remove { path: path.clone() }),
+                }
+                path.truncate(len);
+            }
+            for (key, new_value) in new_map {
+                if !old_map.contains_key(key) {
+                    let len = path.len();
+                    push_token(path, key);
+                    ops.push(PatchOp::Add {
+                        path: path.clone(),
+                        value: new_value.clone(),
+                    });
+                    path.truncate(len);
+                }
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items)) => {
+            for (index, (old_item, new_item)) in old_items.iter().zip(new_items).enumerate() {
+                let len = path.len();
+                push_token(path, &index.to_string());
+                diff_into(old_item, new_item, path, ops);
+                path.truncate(len);
+            }
+            for (index, new_item) in new_items.iter().enumerate().skip(old_items.len()) {
+                ops.push(PatchOp::Add {
+                    path: format!("{path}/{index}"),
+                    value: new_item.clone(),
+                });
+            }
+            // Remove from the end so earlier indices stay valid
+            for index in (new_items.len()..old_items.len()).rev() {
+                ops.push(PatchOp::Remove {
+                    path: format!("{path}/{index}"),
+                });
+            }
+        }
+        _ if old != new => ops.push(PatchOp::Replace {
+            path: path.clone(),
+            value: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// Append a JSON Pointer reference token, escaping `~` and `/`
+fn push_token(path: &mut String, token: &str) {
+    path.push('/');
+    path.push_str(&token.replace('~', "~0").replace('/', "~1"));
+}
+
+fn parse_pointer(path: &str) -> Result<Vec<String>> {
+    if path.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(rest) = path.strip_prefix('/') else {
+        bail!("Invalid patch path '{path}'");
+    };
+    Ok(rest
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+/// Apply operations in order. On error `target` may be partially patched;
+/// clients should request a resync.
+pub fn apply(target: &mut Value, ops: &[PatchOp]) -> Result<()> {
+    for op in ops {
+        match op {
+            PatchOp::Add { path, value } => set(target, path, value.clone(), true)?,
+            PatchOp::Replace { path, value } => set(target, path, value.clone(), false)?,
+            PatchOp::Remove { path } => remove(target, path)?,
+        }
+    }
+    Ok(())
+}
+
+/// Resolve all but the last token of `path`, returning the parent and the last token
+fn parent<'a>(target: &'a mut Value, path: &str) -> Result<(&'a mut Value, String)> {
+    let mut tokens = parse_pointer(path)?;
+    let last = tokens
+        .pop()
+        .ok_or_else(|| anyhow!("Patch path must not be empty"))?;
+    let mut current = target;
+    for token in &tokens {
+        current = match current {
+            Value::Object(map) => map.get_mut(token),
+            Value::Array(items) => token.parse::<usize>().ok().and_then(|i| items.get_mut(i)),
+            _ => None,
+        }
+        .ok_or_else(|| anyhow!("Patch path '{path}' not found"))?;
+    }
+    Ok((current, last))
+}
+
+fn set(target: &mut Value, path: &str, value: Value, insert: bool) -> Result<()> {
+    if path.is_empty() {
+        *target = value;
+        return Ok(());
+    }
+    let (container, last) = parent(target, path)?;
+    match container {
+        Value::Object(map) => {
+            if !insert && !map.contains_key(&last) {
+                bail!("Patch path '{path}' not found");
+            }
+            map.insert(last, value);
+        }
+        Value::Array(items) => {
+            let index = if insert && last == "-" {
+                items.len()
+            } else {
+                last.parse::<usize>()
+                    .map_err(|_| anyhow!("Invalid array index in patch path '{path}'"))?
+            };
+            if insert && index <= items.len() {
+                items.insert(index, value);
+            } else if !insert && index < items.len() {
+                items[index] = value;
+            } else {
+                bail!("Array index out of bounds in patch path '{path}'");
+            }
+        }
+        _ => bail!("Patch path '{path}' not found"),
+    }
+    Ok(())
+}
+
+fn remove(target: &mut Value, path: &str) -> Result<()> {
+    let (container, last) = parent(target, path)?;
+    let removed = match container {
+        Value::Object(map) => map.remove(&last).is_some(),
+        Value::Array(items) => match last.parse::<usize>() {
+            Ok(index) if index < items.len() => {
+                items.remove(index);
+                true
+            }
+            _ => false,
+        },
+        _ => false,
+    };
+    if !removed {
+        bail!("Patch path '{path}' not found");
+    }
+    Ok(())
+}
+
+/// Fields that change on every update, so an update changing only these is skipped
+const VOLATILE_PATHS: &[&str] = &["/serverTimeMs"];
+
+/// What one client's stream last received, so the next update can be sent as a patch.
+/// Sequence numbers increase by one per update; a gap tells the client to resync.
+#[derive(Debug, Default)]
+pub struct PatchStream {
+    seq: u64,
+    last_sent: Option<Value>,
+    /// Always send full snapshots, for clients that can't apply patches
+    snapshots_only: bool,
+}
+
+impl PatchStream {
+    pub fn snapshots_only() -> Self {
+        PatchStream {
+            snapshots_only: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_snapshots_only(&self) -> bool {
+        self.snapshots_only
+    }
+
+    /// Record `state` as sent and return its sequence number, plus the ops since the
+    /// previous update (None if the client has no baseline and needs the full state).
+    /// Returns None, using no sequence number, if nothing but `VOLATILE_PATHS` changed.
+    pub fn next(&mut self, state: Value) -> Option<(u64, Option<Vec<PatchOp>>)> {
+        if self.snapshots_only {
+            self.seq += 1;
+            return Some((self.seq, None));
+        }
+        let ops = self.last_sent.as_ref().map(|last| diff(last, &state));
+        if ops
+            .as_ref()
+            .is_some_and(|ops| ops.iter().all(|op| VOLATILE_PATHS.contains(&op.path())))
+        {
+            return None;
+        }
+        self.seq += 1;
+        self.last_sent = Some(state);
+        Some((self.seq, ops))
+    }
+
+    /// Forget the client's baseline so the next update is a full snapshot
+    pub fn reset(&mut self) {
+        self.last_sent = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_then_apply_round_trips() {
+        let old = json!({
+            "timerRunning": false,
+            "teams": [{"teamName": "a", "score": 1}, {"teamName": "b", "score": 2}],
+            "removed": true,
+        });
+        let new = json!({
+            "timerRunning": true,
+            "teams": [{"teamName": "a", "score": 5}],
+            "added": {"x/y": 1},
+        });
+
+        let ops = diff(&old, &new);
+        let mut patched = old.clone();
+        apply(&mut patched, &ops).unwrap();
+
+        assert_eq!(patched, new);
+    }
+
+    #[test]
+    fn test_diff_of_equal_values_is_empty() {
+        let value = json!({"questions": [{"answers": []}]});
+        assert!(diff(&value, &value).is_empty());
+    }
+
+    #[test]
+    fn test_diff_appends_array_items() {
+        let ops = diff(&json!({"a": [1]}), &json!({"a": [1, 2, 3]}));
+        assert_eq!(
+            ops,
+            vec![
+                PatchOp::Add {
+                    path: "/a/1".to_string(),
+                    value: json!(2)
+                },
+                PatchOp::Add {
+                    path: "/a/2".to_string(),
+                    value: json!(3)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snapshots_only_stream_never_patches() {
+        let mut stream = PatchStream::snapshots_only();
+        assert_eq!(stream.next(json!({"a": 1})), Some((1, None)));
+        assert_eq!(stream.next(json!({"a": 2})), Some((2, None)));
+    }
+
+    #[test]
+    fn test_stream_skips_unchanged_state() {
+        let mut stream = PatchStream::default();
+        assert_eq!(
+            stream.next(json!({"a": 1, "serverTimeMs": 1})),
+            Some((1, None))
+        );
+        assert_eq!(stream.next(json!({"a": 1, "serverTimeMs": 2})), None);
+        assert_eq!(
+            stream.next(json!({"a": 2, "serverTimeMs": 3})),
+            Some((
+                2,
+                Some(vec![
+                    PatchOp::Replace {
+                        path: "/a".to_string(),
+                        value: json!(2)
+                    },
+                    PatchOp::Replace {
+                        path: "/serverTimeMs".to_string(),
+                        value: json!(3)
+                    },
+                ])
+            ))
+        );
+    }
+
+    #[test]
+    fn test_apply_rejects_missing_path() {
+        let mut value = json!({"a": 1});
+        let ops = vec![PatchOp::Replace {
+            path: "/b".to_string(),
+            value: json!(2),
+        }];
+        assert!(apply(&mut value, &ops).is_err());
+    }
+}
@@ -0,0 +1,465 @@
+//! Protocol versioning.
+//!
+//! A client opens its connection with `Hello`, naming the protocol version it speaks and
+//! what kind of client it is. The server answers `Welcome`, or `UnsupportedProtocol` with
+//! the versions it does speak so the client can ask the user to refresh. Clients that
+//! predate the handshake send their first action straight away and are treated as
+//! `LEGACY_PROTOCOL_VERSION`, whose messages are converted by the shims in `v1`.
+//!
+//! `Hello` may also ask for MessagePack instead of JSON. `Hello` and its reply are always
+//! JSON text; after that the server sends the requested encoding, as binary frames for
+//! MessagePack. Clients may send either: text frames are read as JSON, binary frames as
+//! MessagePack.
+
+use log::error;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::model::client_message::{ClientEnvelope, ClientMessage};
+use crate::model::server_message::ServerMessage;
+
+/// Current protocol: adds the handshake, state patches, clock sync, timer adjustments,
+/// late answers and timer warnings
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Version assumed for clients that connect without a `Hello`
+pub const LEGACY_PROTOCOL_VERSION: u32 = 1;
+
+pub const REFRESH_MESSAGE: &str =
+    "This version of the app is no longer supported by the server. Please refresh the page.";
+
+/// Versions this server accepts, oldest first
+pub fn supported_versions() -> Vec<u32> {
+    (LEGACY_PROTOCOL_VERSION..=PROTOCOL_VERSION).collect()
+}
+
+pub fn is_supported(protocol_version: u32) -> bool {
+    (LEGACY_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&protocol_version)
+}
+
+/// Whether clients on this version understand `GameStatePatch`/`TeamGameStatePatch`
+pub fn supports_patches(protocol_version: u32) -> bool {
+    protocol_version >= 2
+}
+
+/// How server messages are encoded on a connection, requested in `Hello`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Encoding {
+    /// camelCase JSON in text frames
+    #[default]
+    Json,
+    /// The same structure as the JSON, as MessagePack maps in binary frames
+    MessagePack,
+}
+
+impl Encoding {
+    pub fn encode(self, msg: &ServerMessage) -> Message {
+        match self {
+            Encoding::Json => {
+                let text = serde_json::to_string(msg).unwrap_or_else(|e| {
+                    format!("Catastrophic! Serde error when trying to serialize serverside: {e}")
+                });
+                Message::text(text)
+            }
+            Encoding::MessagePack => match rmp_serde::to_vec_named(msg) {
+                Ok(bytes) => Message::binary(bytes),
+                Err(e) => {
+                    error!("Failed to encode {msg:?} as MessagePack: {e}");
+                    Encoding::Json.encode(msg)
+                }
+            },
+        }
+    }
+}
+
+/// What a connection settled on in the handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handshake {
+    pub protocol_version: u32,
+    pub encoding: Encoding,
+}
+
+impl Handshake {
+    /// Clients that open without `Hello`
+    pub const LEGACY: Handshake = Handshake {
+        protocol_version: LEGACY_PROTOCOL_VERSION,
+        encoding: Encoding::Json,
+    };
+}
+
+/// A client frame that couldn't be parsed
+#[derive(Debug)]
+pub enum DecodeError {
+    Json(serde_json::Error),
+    MessagePack(rmp_serde::decode::Error),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Json(e) => write!(f, "Invalid JSON: {e}"),
+            DecodeError::MessagePack(e) => write!(f, "Invalid MessagePack: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Parse a client message sent under `protocol_version`. Version 1 predates request IDs.
+pub fn parse_client_message(
+    text: &str,
+    protocol_version: u32,
+) -> serde_json::Result<ClientEnvelope> {
+    if protocol_version == LEGACY_PROTOCOL_VERSION {
+        serde_json::from_str::<v1::ClientMessage>(text)
+            .map(|message| ClientMessage::from(message).into())
+    } else {
+        serde_json::from_str(text)
+    }
+}
+
+/// Parse a data frame: JSON text or MessagePack binary. Binary frames postdate version 1,
+/// so they're always read as the current protocol. None for control frames.
+pub fn decode_client_message(
+    frame: &Message,
+    protocol_version: u32,
+) -> Option<Result<ClientEnvelope, DecodeError>> {
+    match frame {
+        Message::Text(text) => {
+            Some(parse_client_message(text, protocol_version).map_err(DecodeError::Json))
+        }
+        Message::Binary(bytes) => {
+            Some(rmp_serde::from_slice(bytes).map_err(DecodeError::MessagePack))
+        }
+        _ => None,
+    }
+}
+
+/// Version 1 actions, converted to the current ones on receipt. Settings that didn't
+/// exist yet take their defaults.
+pub mod v1 {
+    use serde::Deserialize;
+
+    use crate::model::client_message::{self as current, WatcherAction};
+    use crate::model::types::{
+        GameSettings, McConfig, QuestionConfig, QuestionKind, ScoreData, SpeedBonusMode,
+    };
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase", tag = "type")]
+    pub enum HostAction {
+        #[serde(rename_all = "camelCase")]
+        CreateGame {
+            game_code: Option<String>,
+        },
+
+        StartTimer,
+        PauseTimer,
+        ResetTimer,
+        NextQuestion,
+        PrevQuestion,
+
+        #[serde(rename_all = "camelCase")]
+        ScoreAnswer {
+            question_number: usize,
+            team_name: String,
+            score: ScoreData,
+        },
+
+        #[serde(rename_all = "camelCase")]
+        OverrideTeamScore {
+            team_name: String,
+            override_points: i32,
+        },
+
+        #[serde(rename_all = "camelCase")]
+        UpdateGameSettings {
+            default_timer_duration: u32,
+            default_question_points: u32,
+            default_bonus_increment: u32,
+            default_question_type: QuestionKind,
+            default_mc_config: McConfig,
+            speed_bonus_enabled: bool,
+            speed_bonus_num_teams: u32,
+            speed_bonus_first_place_points: u32,
+        },
+
+        #[serde(rename_all = "camelCase")]
+        UpdateQuestionSettings {
+            question_number: usize,
+            timer_duration: u32,
+            question_points: u32,
+            bonus_increment: u32,
+            question_type: QuestionKind,
+            speed_bonus_enabled: bool,
+        },
+
+        #[serde(rename_all = "camelCase")]
+        UpdateTypeSpecificSettings {
+            question_number: usize,
+            question_config: QuestionConfig,
+        },
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub enum TeamAction {
+        #[serde(rename_all = "camelCase")]
+        ValidateJoin {
+            team_name: String,
+            game_code: String,
+        },
+
+        #[serde(rename_all = "camelCase")]
+        JoinGame {
+            team_name: String,
+            game_code: String,
+            color_hex: String,
+            color_name: String,
+            team_members: Vec<String>,
+        },
+
+        #[serde(rename_all = "camelCase")]
+        SubmitAnswer { team_name: String, answer: String },
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub enum ClientMessage {
+        Host(HostAction),
+        Team(TeamAction),
+        Watcher(WatcherAction),
+    }
+
+    impl From<HostAction> for current::HostAction {
+        fn from(action: HostAction) -> Self {
+            match action {
+                HostAction::CreateGame { game_code } => current::HostAction::CreateGame {
+                    game_code,
+                    copy_settings_from: None,
+                },
+                HostAction::StartTimer => current::HostAction::StartTimer,
+                HostAction::PauseTimer => current::HostAction::PauseTimer,
+                HostAction::ResetTimer => current::HostAction::ResetTimer,
+                HostAction::NextQuestion => current::HostAction::NextQuestion,
+                HostAction::PrevQuestion => current::HostAction::PrevQuestion,
+                HostAction::ScoreAnswer {
+                    question_number,
+                    team_name,
+                    score,
+                } => current::HostAction::ScoreAnswer {
+                    question_number,
+                    team_name,
+                    score,
+                },
+                HostAction::OverrideTeamScore {
+                    team_name,
+                    override_points,
+                } => current::HostAction::OverrideTeamScore {
+                    team_name,
+                    override_points,
+                },
+                HostAction::UpdateGameSettings {
+                    default_timer_duration,
+                    default_question_points,
+                    default_bonus_increment,
+                    default_question_type,
+                    default_mc_config,
+                    speed_bonus_enabled,
+                    speed_bonus_num_teams,
+                    speed_bonus_first_place_points,
+                } => current::HostAction::UpdateGameSettings {
+                    default_timer_duration,
+                    default_question_points,
+                    default_bonus_increment,
+                    default_question_type,
+                    default_mc_config,
+                    speed_bonus_enabled,
+                    speed_bonus_num_teams,
+                    speed_bonus_first_place_points,
+                    late_grace_seconds: GameSettings::DEFAULT_LATE_GRACE_SECONDS,
+                    speed_bonus_mode: SpeedBonusMode::default(),
+                    timer_warning_seconds: GameSettings::default_timer_warning_seconds(),
+                },
+                HostAction::UpdateQuestionSettings {
+                    question_number,
+                    timer_duration,
+                    question_points,
+                    bonus_increment,
+                    question_type,
+                    speed_bonus_enabled,
+                } => current::HostAction::UpdateQuestionSettings {
+                    question_number,
+                    timer_duration,
+                    question_points,
+                    bonus_increment,
+                    question_type,
+                    speed_bonus_enabled,
+                },
+                HostAction::UpdateTypeSpecificSettings {
+                    question_number,
+                    question_config,
+                } => current::HostAction::UpdateTypeSpecificSettings {
+                    question_number,
+                    question_config,
+                },
+            }
+        }
+    }
+
+    impl From<TeamAction> for current::TeamAction {
+        fn from(action: TeamAction) -> Self {
+            match action {
+                TeamAction::ValidateJoin {
+                    team_name,
+                    game_code,
+                } => current::TeamAction::ValidateJoin {
+                    team_name,
+                    game_code,
+                },
+                TeamAction::JoinGame {
+                    team_name,
+                    game_code,
+                    color_hex,
+                    color_name,
+                    team_members,
+                } => current::TeamAction::JoinGame {
+                    team_name,
+                    game_code,
+                    color_hex,
+                    color_name,
+                    team_members,
+                },
+                TeamAction::SubmitAnswer { team_name, answer } => {
+                    current::TeamAction::SubmitAnswer { team_name, answer }
+                }
+            }
+        }
+    }
+
+    impl From<ClientMessage> for current::ClientMessage {
+        fn from(message: ClientMessage) -> Self {
+            match message {
+                ClientMessage::Host(action) => current::ClientMessage::Host(action.into()),
+                ClientMessage::Team(action) => current::ClientMessage::Team(action.into()),
+                ClientMessage::Watcher(action) => current::ClientMessage::Watcher(action),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::client_message::{HostAction, TeamAction};
+    use crate::model::error::ErrorCode;
+    use crate::model::types::{GameSettings, SpeedBonusMode};
+
+    #[test]
+    fn test_v1_update_game_settings_gets_defaults() {
+        let text = r#"{"host": {"type": "updateGameSettings", "defaultTimerDuration": 30,
+            "defaultQuestionPoints": 50, "defaultBonusIncrement": 5,
+            "defaultQuestionType": "standard", "defaultMcConfig": {"optionType": "letters", "numOptions": 4},
+            "speedBonusEnabled": false, "speedBonusNumTeams": 2, "speedBonusFirstPlacePoints": 10}}"#;
+
+        let message = parse_client_message(text, LEGACY_PROTOCOL_VERSION)
+            .unwrap()
+            .message;
+        let ClientMessage::Host(HostAction::UpdateGameSettings {
+            late_grace_seconds,
+            timer_warning_seconds,
+            ..
+        }) = message
+        else {
+            panic!("Expected UpdateGameSettings, got {message:?}");
+        };
+        assert_eq!(late_grace_seconds, GameSettings::DEFAULT_LATE_GRACE_SECONDS);
+        assert_eq!(
+            timer_warning_seconds,
+            GameSettings::default_timer_warning_seconds()
+        );
+    }
+
+    #[test]
+    fn test_update_game_settings_without_newer_settings_gets_defaults() {
+        let text = r#"{"host": {"type": "updateGameSettings", "defaultTimerDuration": 30,
+            "defaultQuestionPoints": 50, "defaultBonusIncrement": 5,
+            "defaultQuestionType": "standard", "defaultMcConfig": {"optionType": "letters", "numOptions": 4},
+            "speedBonusEnabled": false, "speedBonusNumTeams": 2, "speedBonusFirstPlacePoints": 10}}"#;
+
+        let message = parse_client_message(text, PROTOCOL_VERSION)
+            .unwrap()
+            .message;
+        let ClientMessage::Host(HostAction::UpdateGameSettings {
+            late_grace_seconds,
+            speed_bonus_mode,
+            timer_warning_seconds,
+            ..
+        }) = message
+        else {
+            panic!("Expected UpdateGameSettings, got {message:?}");
+        };
+        assert_eq!(late_grace_seconds, GameSettings::DEFAULT_LATE_GRACE_SECONDS);
+        assert_eq!(speed_bonus_mode, SpeedBonusMode::default());
+        assert_eq!(
+            timer_warning_seconds,
+            GameSettings::default_timer_warning_seconds()
+        );
+    }
+
+    #[test]
+    fn test_request_id_is_read_alongside_the_action() {
+        let text = r#"{"team": "resync", "requestId": "r1"}"#;
+        let envelope = parse_client_message(text, PROTOCOL_VERSION).unwrap();
+        assert_eq!(envelope.request_id.as_deref(), Some("r1"));
+        assert!(matches!(
+            envelope.message,
+            ClientMessage::Team(TeamAction::Resync)
+        ));
+    }
+
+    #[test]
+    fn test_v1_rejects_actions_added_later() {
+        let text = r#"{"team": "resync"}"#;
+        assert!(parse_client_message(text, LEGACY_PROTOCOL_VERSION).is_err());
+        assert!(matches!(
+            parse_client_message(text, PROTOCOL_VERSION)
+                .unwrap()
+                .message,
+            ClientMessage::Team(TeamAction::Resync)
+        ));
+    }
+
+    #[test]
+    fn test_message_pack_frames_mirror_json() {
+        let json: serde_json::Value = serde_json::from_str(
+            r#"{"team": {"submitAnswer": {"teamName": "A", "answer": "42"}}, "requestId": "r1"}"#,
+        )
+        .unwrap();
+        let frame = Message::binary(rmp_serde::to_vec_named(&json).unwrap());
+
+        let envelope = decode_client_message(&frame, PROTOCOL_VERSION)
+            .unwrap()
+            .unwrap();
+        assert_eq!(envelope.request_id.as_deref(), Some("r1"));
+        assert!(matches!(
+            envelope.message,
+            ClientMessage::Team(TeamAction::SubmitAnswer { .. })
+        ));
+
+        let error = ServerMessage::error(ErrorCode::SubmissionsClosed, "Submissions are closed");
+        let Message::Binary(bytes) = Encoding::MessagePack.encode(&error) else {
+            panic!("MessagePack should be sent as a binary frame");
+        };
+        let Message::Text(text) = Encoding::Json.encode(&error) else {
+            panic!("JSON should be sent as a text frame");
+        };
+        assert_eq!(
+            rmp_serde::from_slice::<serde_json::Value>(&bytes).unwrap(),
+            serde_json::from_str::<serde_json::Value>(&text).unwrap()
+        );
+    }
+}
@@ -1,91 +1,298 @@
-use log::{error, info};
+use log::{debug, info, warn};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tokio_tungstenite::tungstenite::Message;
 
-use crate::model::types::{GameSettings, Question, ScoreboardData, TeamData, TeamQuestion};
+use crate::model::error::ErrorCode;
+use crate::model::patch::PatchOp;
+use crate::model::protocol::Encoding;
+use crate::model::types::{
+    GameSettings, Question, ScoreboardData, StaffMember, TeamData, TeamQuestion,
+};
+use crate::outbox::{MessageKind, SendError};
 use crate::server::Tx;
+use tokio_tungstenite::tungstenite::{
+    Message,
+    protocol::{CloseFrame, frame::coding::CloseCode},
+};
 
 // === GameState (Server → Host) ===
 
-/// The complete game state sent to the host on every update.
-/// Submissions are open iff `timer_running` is true.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The complete game state sent to the host, and any staff, on every update.
+/// Submissions are open while `timer_running` is true and `timer_deadline_ms` hasn't passed.
+/// Clients count down locally from `timer_deadline_ms`, using `server_time_ms`
+/// (or a `ClockSync` round trip) to correct for clock offset.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct GameState {
     pub game_code: String,
     pub current_question_number: usize,
     pub timer_running: bool,
     pub timer_seconds_remaining: Option<u32>,
+    #[serde(default)]
+    pub timer_deadline_ms: Option<u64>,
+    #[serde(default)]
+    pub server_time_ms: u64,
     pub teams: Vec<TeamData>,
     pub questions: Vec<Question>,
     pub game_settings: GameSettings,
+    /// Users the host invited to help run the game
+    #[serde(default)]
+    pub staff: Vec<StaffMember>,
 }
 
 // === TeamGameState (Server → Team) ===
 
 /// Filtered game state for team clients.
 /// Does not include other teams' answers or scoring details.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct TeamGameState {
     pub game_code: String,
     pub current_question_number: usize,
     pub timer_running: bool,
     pub timer_seconds_remaining: Option<u32>,
+    #[serde(default)]
+    pub timer_deadline_ms: Option<u64>,
+    #[serde(default)]
+    pub server_time_ms: u64,
     pub team: TeamData,
     pub questions: Vec<TeamQuestion>,
 }
 
+// === Saved Games ===
+
+/// How far a saved game got
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum GamePhase {
+    /// No answers submitted yet
+    NotStarted,
+    InProgress,
+}
+
+/// One of a host's saved games, as listed by `ListGames`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedGameSummary {
+    pub game_code: String,
+    /// None for games saved before creation times were recorded
+    pub created_at_ms: Option<u64>,
+    /// When the game last changed, as far as its saves show
+    pub last_played_ms: Option<u64>,
+    pub team_count: usize,
+    pub question_count: usize,
+    pub current_question_number: usize,
+    pub phase: GamePhase,
+}
+
+// === Timer Events ===
+
+/// Typed timer lifecycle events, so every client reacts to the countdown consistently
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum TimerEventKind {
+    Started,
+    Resumed,
+    Paused,
+    /// Remaining time crossed one of the game's `timer_warning_seconds` thresholds
+    #[serde(rename_all = "camelCase")]
+    Warning {
+        threshold_seconds: u32,
+    },
+    Expired,
+    #[serde(rename_all = "camelCase")]
+    Adjusted {
+        delta_seconds: i32,
+    },
+}
+
 // === Server Messages ===
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub enum ServerMessage {
-    /// Full game state update (sent to host)
+    /// Full game state snapshot (sent to host on connect and resync)
     #[serde(rename_all = "camelCase")]
-    GameState { state: GameState },
+    GameState {
+        #[serde(default)]
+        seq: u64,
+        state: GameState,
+    },
 
-    /// Filtered game state update (sent to team)
+    /// Filtered game state snapshot (sent to team on connect and resync)
     #[serde(rename_all = "camelCase")]
-    TeamGameState { state: TeamGameState },
+    TeamGameState {
+        #[serde(default)]
+        seq: u64,
+        state: TeamGameState,
+    },
+
+    /// Changes to the host's last `GameState`. `seq` is one more than the previous update;
+    /// on a gap the host should send `Resync`.
+    #[serde(rename_all = "camelCase")]
+    GameStatePatch { seq: u64, ops: Vec<PatchOp> },
+
+    /// Changes to the team's last `TeamGameState`, sequenced like `GameStatePatch`
+    #[serde(rename_all = "camelCase")]
+    TeamGameStatePatch { seq: u64, ops: Vec<PatchOp> },
+
+    /// Reply to `Hello`: the connection will use `protocol_version`, and `encoding` for
+    /// every later server message
+    #[serde(rename_all = "camelCase")]
+    Welcome {
+        protocol_version: u32,
+        supported_versions: Vec<u32>,
+        encoding: Encoding,
+    },
+
+    /// Reply to `Hello` with a version this server doesn't speak; the connection is closed
+    /// and the client should refresh to pick up a compatible build
+    #[serde(rename_all = "camelCase")]
+    UnsupportedProtocol {
+        message: String,
+        supported_versions: Vec<u32>,
+    },
 
     /// Simple acknowledgement that join validation passed (new team, game exists)
     JoinValidated,
 
-    /// Lightweight timer tick (sent to all clients each second while timer runs)
+    /// Timer lifecycle event (sent to host, teams and watchers)
+    #[serde(rename_all = "camelCase")]
+    TimerEvent {
+        event: TimerEventKind,
+        seconds_remaining: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        deadline_ms: Option<u64>,
+        server_time_ms: u64,
+    },
+
+    /// Whole seconds left on the running timer, sent once a second to version 1 clients,
+    /// which count down from these instead of from deadlines
     #[serde(rename_all = "camelCase")]
     TimerTick { seconds_remaining: u32 },
 
-    /// Error with optional state for rollback
+    /// Reply to a client's clock sync request, for estimating the client/server clock offset
+    #[serde(rename_all = "camelCase")]
+    ClockSync {
+        client_time_ms: u64,
+        server_time_ms: u64,
+    },
+
+    /// A client action succeeded. Only sent for actions that carried a request ID.
+    #[serde(rename_all = "camelCase")]
+    Ack { request_id: String },
+
+    /// A client action failed. `state`/`team_state` carry the sender's current state
+    /// when it may have applied the action optimistically and needs to revert.
     #[serde(rename_all = "camelCase")]
     Error {
+        code: ErrorCode,
         message: String,
         #[serde(skip_serializing_if = "Option::is_none")]
-        state: Option<GameState>,
+        request_id: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        state: Option<Box<GameState>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        team_state: Option<Box<TeamGameState>>,
     },
 
     /// Scoreboard data for watchers
     #[serde(rename_all = "camelCase")]
     ScoreboardData { data: ScoreboardData },
+
+    /// The connection's token expires soon. Send `Reauthenticate` with a fresh one before
+    /// `expires_at_ms` to keep control of the game.
+    #[serde(rename_all = "camelCase")]
+    AuthExpiring { expires_at_ms: u64 },
+
+    /// The connection's token expired. It still receives updates, but its actions are
+    /// refused until `Reauthenticate` succeeds.
+    AuthExpired,
+
+    /// Reply to `Reauthenticate`: full access until the new token expires
+    #[serde(rename_all = "camelCase")]
+    Reauthenticated { expires_at_ms: Option<u64> },
+
+    /// Reply to `ListGames`: the host's saved games, most recently played first
+    #[serde(rename_all = "camelCase")]
+    SavedGames { games: Vec<SavedGameSummary> },
+
+    /// Reply to `DeleteGame`: the game's saves are gone
+    #[serde(rename_all = "camelCase")]
+    GameDeleted { game_code: String },
+
+    /// The server is shutting down; games have been saved and the connection is about to
+    /// close. Clients should reconnect after a short wait.
+    #[serde(rename_all = "camelCase")]
+    ServerRestarting { message: String },
 }
 
 impl ServerMessage {
     /// Create an error message without rollback state
-    pub fn error(message: impl Into<String>) -> Self {
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
         ServerMessage::Error {
+            code,
             message: message.into(),
+            request_id: None,
             state: None,
+            team_state: None,
+        }
+    }
+
+    /// Echo the request ID of the action an error answers
+    pub fn for_request(mut self, id: Option<String>) -> Self {
+        if let ServerMessage::Error { request_id, .. } = &mut self {
+            *request_id = id;
         }
+        self
+    }
+}
+
+/// Acknowledge a successful action, if the client gave it a request ID
+pub fn send_ack(tx: &Tx, request_id: Option<String>) {
+    if let Some(request_id) = request_id {
+        send_msg(tx, ServerMessage::Ack { request_id });
     }
 }
 
-pub fn send_msg(tx: &Tx, msg: ServerMessage) {
+/// Queue a close frame after anything already queued, so the client's connection handler
+/// closes the socket once the client has received everything else
+pub fn send_close(tx: &Tx, reason: &str) {
+    let frame = CloseFrame {
+        code: CloseCode::Away,
+        reason: reason.to_string().into(),
+    };
+    if tx
+        .send(Message::Close(Some(frame)), MessageKind::Other)
+        .is_err()
+    {
+        debug!("Channel already closed");
+    }
+}
+
+/// Queue a message for a client in its connection's encoding. Snapshots replace any stale
+/// state still queued.
+/// Returns false if the client's channel is closed; closed channels are pruned by `Game`.
+pub fn send_msg(tx: &Tx, msg: ServerMessage) -> bool {
     info!("Sending server message: {msg:?}");
-    let msg = serde_json::to_string(&msg).unwrap_or_else(|e| {
-        format!("Catastrophic! Serde error when trying to serialize serverside: {e}").to_string()
-    });
-    tx.send(Message::text(&msg)).unwrap_or_else(|e| {
-        error!("Sending server message through channel failed: {e}");
-        error!("Tried to send message: {msg}");
-    })
+    let kind = match msg {
+        ServerMessage::GameState { .. } | ServerMessage::TeamGameState { .. } => {
+            MessageKind::Snapshot
+        }
+        ServerMessage::GameStatePatch { .. } | ServerMessage::TeamGameStatePatch { .. } => {
+            MessageKind::Patch
+        }
+        _ => MessageKind::Other,
+    };
+    match tx.send(tx.encoding().encode(&msg), kind) {
+        Ok(()) => true,
+        Err(SendError::Overflow) => {
+            warn!("Client fell too far behind, closing its channel");
+            false
+        }
+        Err(SendError::Closed) => {
+            debug!("Dropping message for closed channel");
+            false
+        }
+    }
 }
@@ -1,8 +1,9 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 // === Question Kind ===
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum QuestionKind {
     Standard,
@@ -12,7 +13,7 @@ pub enum QuestionKind {
 
 // === Multiple Choice Configuration ===
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum McOptionType {
     Letters,
@@ -22,7 +23,7 @@ pub enum McOptionType {
     Other,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct McConfig {
     pub option_type: McOptionType,
@@ -42,7 +43,7 @@ impl Default for McConfig {
 
 // === Question Config (discriminated union by question kind) ===
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub enum QuestionConfig {
     Standard,
@@ -65,7 +66,7 @@ impl QuestionConfig {
 
 // === Score Types ===
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ScoreData {
     pub question_points: i32,
@@ -90,7 +91,7 @@ impl ScoreData {
 // - On the team side (TeamGameState.questions): includes all historic questions,
 //   so content may be None if the team didn't submit.
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct TeamQuestion {
     pub team_name: String,
@@ -98,10 +99,17 @@ pub struct TeamQuestion {
     pub content: Option<AnswerContent>,
     pub question_kind: QuestionKind,
     pub question_config: QuestionConfig,
+    /// Submitted during the grace window after the timer expired.
+    /// Late answers earn no points until the host accepts them. Host only.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub late: bool,
+    /// Milliseconds of timer running time before the answer arrived. Host only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer_latency_ms: Option<u64>,
 }
 
 /// The content of a team's answer, varying by question type.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub enum AnswerContent {
     #[serde(rename_all = "camelCase")]
@@ -114,7 +122,7 @@ pub enum AnswerContent {
 
 // === Question ===
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Question {
     pub timer_duration: u32,
@@ -124,6 +132,10 @@ pub struct Question {
     pub question_config: QuestionConfig,
     pub answers: Vec<TeamQuestion>,
     pub speed_bonus_enabled: bool,
+    /// Running time the question's timer has had, adjustments included, as of the last
+    /// answer or adjustment. Time-curve speed bonuses are scored against it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timer_window_ms: Option<u64>,
 }
 
 impl Question {
@@ -132,25 +144,42 @@ impl Question {
         !self.answers.is_empty()
     }
 
-    /// Filter question to only include a specific team's data
+    /// Filter question to only include a specific team's data, without host-only details
     pub fn filter_for_team(&self, team_name: &str) -> TeamQuestion {
         self.answers
             .iter()
             .find(|a| a.team_name.eq_ignore_ascii_case(team_name))
-            .cloned()
+            .map(|answer| TeamQuestion {
+                late: false,
+                answer_latency_ms: None,
+                ..answer.clone()
+            })
             .unwrap_or_else(|| TeamQuestion {
                 team_name: team_name.to_string(),
                 score: ScoreData::new(),
                 content: None,
                 question_kind: self.question_kind,
                 question_config: self.question_config.clone(),
+                late: false,
+                answer_latency_ms: None,
             })
     }
 }
 
 // === Game Settings ===
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// How speed bonus points are awarded to correct answers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum SpeedBonusMode {
+    /// Points by order of correct answers (1st, 2nd, ...)
+    #[default]
+    Placement,
+    /// Points decreasing linearly with answer latency over the question's timer
+    TimeCurve,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct GameSettings {
     pub default_timer_duration: u32,
@@ -161,18 +190,37 @@ pub struct GameSettings {
     pub speed_bonus_enabled: bool,
     pub speed_bonus_num_teams: u32,
     pub speed_bonus_first_place_points: u32,
+    #[serde(default = "GameSettings::default_late_grace_seconds")]
+    pub late_grace_seconds: u32,
+    #[serde(default)]
+    pub speed_bonus_mode: SpeedBonusMode,
+    /// Remaining-seconds thresholds at which a timer warning event is sent
+    #[serde(default = "GameSettings::default_timer_warning_seconds")]
+    pub timer_warning_seconds: Vec<u32>,
+}
+
+impl GameSettings {
+    pub const DEFAULT_LATE_GRACE_SECONDS: u32 = 3;
+
+    pub fn default_late_grace_seconds() -> u32 {
+        Self::DEFAULT_LATE_GRACE_SECONDS
+    }
+
+    pub fn default_timer_warning_seconds() -> Vec<u32> {
+        vec![10]
+    }
 }
 
 // === Team Types ===
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct TeamColor {
     pub hex_code: String,
     pub name: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct TeamData {
     pub team_name: String,
@@ -182,9 +230,29 @@ pub struct TeamData {
     pub connected: bool,
 }
 
+// === Staff ===
+
+/// What a user invited to help run someone else's game may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum StaffRole {
+    /// Full control of the game, except inviting and removing staff
+    CoHost,
+    /// May only score answers and override team scores
+    Scorekeeper,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StaffMember {
+    pub user_id: String,
+    pub role: StaffRole,
+    pub connected: bool,
+}
+
 // === Scoreboard Data (for watchers) ===
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ScoreboardData {
     pub teams: Vec<TeamData>,
@@ -0,0 +1,252 @@
+//! Bounded per-connection outbound queues.
+//!
+//! Each connection's socket writer drains its own outbox. A state snapshot supersedes any
+//! snapshot or patch still waiting, so a slow client never applies stale updates; once
+//! `PATCH_BACKLOG` patches are waiting, the next update is sent as a snapshot to collapse
+//! them. A client that still falls `OUTBOX_CAPACITY` messages behind has its outbox closed
+//! and is disconnected; it gets fresh state when it reconnects. The outbox also records the
+//! encoding its connection negotiated, so messages are encoded per client.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::Notify;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::model::protocol::Encoding;
+
+pub const OUTBOX_CAPACITY: usize = 64;
+
+/// Queued patches past which a client's next update should be a snapshot instead
+pub const PATCH_BACKLOG: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendError {
+    /// The client disconnected or was already cut off
+    Closed,
+    /// The client fell too far behind; the outbox has just been closed
+    Overflow,
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Closed => write!(f, "channel closed"),
+            SendError::Overflow => write!(f, "channel full"),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+/// How a queued message interacts with later state updates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    /// Full state; drops any queued snapshots and patches
+    Snapshot,
+    /// Incremental state update; dropped if a snapshot is queued after it
+    Patch,
+    Other,
+}
+
+struct Queued {
+    message: Message,
+    kind: MessageKind,
+}
+
+#[derive(Default)]
+struct Queue {
+    messages: VecDeque<Queued>,
+    closed: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    notify: Notify,
+    capacity: usize,
+}
+
+impl Shared {
+    fn queue(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Create an outbox holding at most `OUTBOX_CAPACITY` pending messages.
+pub fn channel(encoding: Encoding) -> (Sender, Receiver) {
+    with_capacity(OUTBOX_CAPACITY, encoding)
+}
+
+pub fn with_capacity(capacity: usize, encoding: Encoding) -> (Sender, Receiver) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(Queue::default()),
+        notify: Notify::new(),
+        capacity,
+    });
+    (
+        Sender {
+            shared: shared.clone(),
+            encoding,
+        },
+        Receiver { shared },
+    )
+}
+
+#[derive(Clone)]
+pub struct Sender {
+    shared: Arc<Shared>,
+    encoding: Encoding,
+}
+
+impl Sender {
+    /// Queue a message for the client. Snapshots replace any older state that hasn't been
+    /// written yet.
+    pub fn send(&self, message: Message, kind: MessageKind) -> Result<(), SendError> {
+        let mut queue = self.shared.queue();
+        if queue.closed {
+            return Err(SendError::Closed);
+        }
+
+        if kind == MessageKind::Snapshot {
+            queue
+                .messages
+                .retain(|queued| queued.kind == MessageKind::Other);
+        }
+
+        let result = if queue.messages.len() >= self.shared.capacity {
+            // Too far behind - drop everything and let the writer disconnect the client
+            queue.closed = true;
+            queue.messages.clear();
+            Err(SendError::Overflow)
+        } else {
+            queue.messages.push_back(Queued { message, kind });
+            Ok(())
+        };
+        drop(queue);
+
+        self.shared.notify.notify_one();
+        result
+    }
+
+    /// True if enough patches are waiting that a snapshot would be cheaper to catch up with
+    pub fn has_patch_backlog(&self) -> bool {
+        let queue = self.shared.queue();
+        let patches = queue
+            .messages
+            .iter()
+            .filter(|queued| queued.kind == MessageKind::Patch)
+            .count();
+        patches >= PATCH_BACKLOG
+    }
+
+    /// How messages for this client should be encoded
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// True once the client disconnected or was cut off for falling behind
+    pub fn is_closed(&self) -> bool {
+        self.shared.queue().closed
+    }
+
+    pub fn same_channel(&self, other: &Sender) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+}
+
+pub struct Receiver {
+    shared: Arc<Shared>,
+}
+
+impl Receiver {
+    /// Wait for the next queued message. Returns None once the outbox is closed.
+    /// Cancel-safe: nothing is dequeued until a message is returned.
+    pub async fn recv(&mut self) -> Option<Message> {
+        loop {
+            {
+                let mut queue = self.shared.queue();
+                if let Some(queued) = queue.messages.pop_front() {
+                    return Some(queued.message);
+                }
+                if queue.closed {
+                    return None;
+                }
+            }
+            self.shared.notify.notified().await;
+        }
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        // Connection handler exited; senders see the channel as closed from now on
+        let mut queue = self.shared.queue();
+        queue.closed = true;
+        queue.messages.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_snapshot_replaces_queued_state() {
+        let (tx, mut rx) = channel(Encoding::Json);
+        tx.send(Message::text("state 1"), MessageKind::Snapshot)
+            .unwrap();
+        tx.send(Message::text("event"), MessageKind::Other).unwrap();
+        tx.send(Message::text("patch"), MessageKind::Patch).unwrap();
+        tx.send(Message::text("state 2"), MessageKind::Snapshot)
+            .unwrap();
+
+        assert_eq!(rx.recv().await, Some(Message::text("event")));
+        assert_eq!(rx.recv().await, Some(Message::text("state 2")));
+    }
+
+    #[test]
+    fn test_patch_backlog() {
+        let (tx, _rx) = channel(Encoding::Json);
+        for _ in 0..PATCH_BACKLOG - 1 {
+            tx.send(Message::text("patch"), MessageKind::Patch).unwrap();
+        }
+        assert!(!tx.has_patch_backlog());
+
+        tx.send(Message::text("patch"), MessageKind::Patch).unwrap();
+        assert!(tx.has_patch_backlog());
+
+        tx.send(Message::text("state"), MessageKind::Snapshot)
+            .unwrap();
+        assert!(!tx.has_patch_backlog());
+    }
+
+    #[tokio::test]
+    async fn test_overflow_closes_outbox() {
+        let (tx, mut rx) = with_capacity(2, Encoding::Json);
+        tx.send(Message::text("1"), MessageKind::Patch).unwrap();
+        tx.send(Message::text("2"), MessageKind::Patch).unwrap();
+
+        assert_eq!(
+            tx.send(Message::text("3"), MessageKind::Patch),
+            Err(SendError::Overflow)
+        );
+        assert!(tx.is_closed());
+        assert_eq!(
+            tx.send(Message::text("4"), MessageKind::Other),
+            Err(SendError::Closed)
+        );
+        assert_eq!(rx.recv().await, None);
+    }
+
+    #[test]
+    fn test_dropping_receiver_closes_outbox() {
+        let (tx, rx) = channel(Encoding::Json);
+        drop(rx);
+
+        assert!(tx.is_closed());
+        assert_eq!(
+            tx.send(Message::text("hello"), MessageKind::Other),
+            Err(SendError::Closed)
+        );
+    }
+}
@@ -0,0 +1,237 @@
+//! Saved game format.
+//!
+//! A snapshot is a `{"version": N, "eventSeq": S, "createdAtMs": C, "savedAtMs": T,
+//! "state": {...}}` envelope, where `S` is the last logged event the state includes. The
+//! envelope fields besides `version` may be missing from older saves. Loading runs the state through
+//! `MIGRATIONS` one version at a time up to `SAVE_FORMAT_VERSION` before deserializing it,
+//! so saves from older servers keep loading after `GameState` changes shape.
+//!
+//! To change the format: bump `SAVE_FORMAT_VERSION`, append a migration from the previous
+//! version, and add a `tests/fixtures/saves/v{N}.json` saved by the new version.
+
+use crate::model::server_message::GameState;
+use crate::model::types::GameSettings;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::fmt;
+
+pub const SAVE_FORMAT_VERSION: u32 = 2;
+
+/// Upgrades a state in place from one version to the next
+type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// `MIGRATIONS[i]` upgrades a state saved at version `i + 1` to version `i + 2`
+const MIGRATIONS: [Migration; SAVE_FORMAT_VERSION as usize - 1] = [migrate_v1_to_v2];
+
+/// Bookkeeping saved alongside the state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotMeta {
+    /// The last logged event the state includes
+    #[serde(default)]
+    pub event_seq: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub saved_at_ms: Option<u64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Snapshot<'a> {
+    version: u32,
+    #[serde(flatten)]
+    meta: &'a SnapshotMeta,
+    state: &'a GameState,
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+    /// Saved by a newer server than this one
+    TooNew(u32),
+    /// Not a save, or one that couldn't be migrated
+    Invalid(String),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::TooNew(_) => {
+                write!(f, "This saved game was made by a newer server version")
+            }
+            FormatError::Invalid(_) => write!(
+                f,
+                "This saved game is no longer compatible with the current server version"
+            ),
+        }
+    }
+}
+
+impl FormatError {
+    /// What went wrong, for logs
+    pub fn detail(&self) -> String {
+        match self {
+            FormatError::TooNew(version) => {
+                format!("saved at v{version}; this server reads up to v{SAVE_FORMAT_VERSION}")
+            }
+            FormatError::Invalid(reason) => reason.clone(),
+        }
+    }
+}
+
+/// Serialize a snapshot in the current format
+pub fn encode(state: &GameState, meta: &SnapshotMeta) -> serde_json::Result<Vec<u8>> {
+    serde_json::to_vec(&Snapshot {
+        version: SAVE_FORMAT_VERSION,
+        meta,
+        state,
+    })
+}
+
+/// Deserialize a snapshot from any known version, migrating it to the current `GameState`.
+/// Also returns the envelope's bookkeeping; an event sequence of 0 for saves from before
+/// the log.
+pub fn decode(bytes: &[u8]) -> Result<(GameState, SnapshotMeta), FormatError> {
+    let saved: Value =
+        serde_json::from_slice(bytes).map_err(|e| FormatError::Invalid(e.to_string()))?;
+    // A bare v1 state has none of the envelope's fields, so this reads as the defaults
+    let meta = SnapshotMeta::deserialize(&saved).unwrap_or_default();
+    let (version, mut state) = split_envelope(saved)?;
+
+    if version > SAVE_FORMAT_VERSION {
+        return Err(FormatError::TooNew(version));
+    }
+    for (from, migrate) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+        migrate(&mut state)
+            .map_err(|e| FormatError::Invalid(format!("migrating from v{}: {e}", from + 1)))?;
+    }
+
+    let state = serde_json::from_value(state).map_err(|e| FormatError::Invalid(e.to_string()))?;
+    Ok((state, meta))
+}
+
+/// Version 1 saves were a bare `GameState`, with no envelope
+fn split_envelope(saved: Value) -> Result<(u32, Value), FormatError> {
+    let Value::Object(mut envelope) = saved else {
+        return Err(FormatError::Invalid("save is not an object".into()));
+    };
+    let Some(version) = envelope.get("version") else {
+        return Ok((1, Value::Object(envelope)));
+    };
+    let version = version
+        .as_u64()
+        .and_then(|v| u32::try_from(v).ok())
+        .filter(|&v| v >= 1)
+        .ok_or_else(|| FormatError::Invalid(format!("bad version {version}")))?;
+    let state = envelope
+        .remove("state")
+        .ok_or_else(|| FormatError::Invalid("envelope has no state".into()))?;
+    Ok((version, state))
+}
+
+/// v2 added deadline timers, late answers with latency, the speed bonus mode and timer
+/// warnings; v1 games get the defaults
+fn migrate_v1_to_v2(state: &mut Value) -> Result<(), String> {
+    let state = state.as_object_mut().ok_or("state is not an object")?;
+    state.entry("timerDeadlineMs").or_insert(Value::Null);
+    state.entry("serverTimeMs").or_insert(json!(0));
+
+    let questions = state
+        .get_mut("questions")
+        .and_then(Value::as_array_mut)
+        .ok_or("state has no questions")?;
+    for question in questions {
+        let answers = question
+            .get_mut("answers")
+            .and_then(Value::as_array_mut)
+            .ok_or("question has no answers")?;
+        for answer in answers.iter_mut().filter_map(Value::as_object_mut) {
+            answer.entry("late").or_insert(json!(false));
+            answer.entry("answerLatencyMs").or_insert(Value::Null);
+        }
+    }
+
+    let settings = state
+        .get_mut("gameSettings")
+        .and_then(Value::as_object_mut)
+        .ok_or("state has no game settings")?;
+    settings
+        .entry("lateGraceSeconds")
+        .or_insert(json!(GameSettings::DEFAULT_LATE_GRACE_SECONDS));
+    settings
+        .entry("speedBonusMode")
+        .or_insert(json!("placement"));
+    settings
+        .entry("timerWarningSeconds")
+        .or_insert(json!(GameSettings::default_timer_warning_seconds()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixture(version: u32) -> Vec<u8> {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join(format!("tests/fixtures/saves/v{version}.json"));
+        std::fs::read(&path).unwrap_or_else(|e| {
+            panic!("Every save version needs a fixture; couldn't read {path:?}: {e}")
+        })
+    }
+
+    #[test]
+    fn test_every_version_loads() {
+        for version in 1..=SAVE_FORMAT_VERSION {
+            let (state, _) = decode(&fixture(version))
+                .unwrap_or_else(|e| panic!("v{version} fixture failed to load: {e:?}"));
+
+            assert_eq!(state.game_code, "ABCD", "v{version}");
+            assert_eq!(state.current_question_number, 2, "v{version}");
+            assert_eq!(state.teams.len(), 2, "v{version}");
+            assert_eq!(state.teams[0].score.question_points, 1, "v{version}");
+            assert_eq!(state.teams[1].score.override_points, 2, "v{version}");
+            let answers = &state.questions[0].answers;
+            assert_eq!(answers.len(), 2, "v{version}");
+            assert!(!answers[0].late, "v{version}");
+            assert_eq!(
+                state.game_settings.late_grace_seconds,
+                GameSettings::DEFAULT_LATE_GRACE_SECONDS,
+                "v{version}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_encode_writes_current_version() {
+        let (state, meta) = decode(&fixture(1)).unwrap();
+        assert_eq!(meta, SnapshotMeta::default());
+        let meta = SnapshotMeta {
+            event_seq: 7,
+            created_at_ms: Some(1_000),
+            saved_at_ms: Some(2_000),
+        };
+        let saved: Value = serde_json::from_slice(&encode(&state, &meta).unwrap()).unwrap();
+        assert_eq!(saved["version"], SAVE_FORMAT_VERSION);
+
+        let (reloaded, reloaded_meta) = decode(&encode(&state, &meta).unwrap()).unwrap();
+        assert_eq!(reloaded.teams[0].team_name, state.teams[0].team_name);
+        assert_eq!(reloaded_meta, meta);
+    }
+
+    #[test]
+    fn test_newer_version_is_rejected() {
+        let saved = json!({ "version": SAVE_FORMAT_VERSION + 1, "state": {} });
+        let result = decode(&serde_json::to_vec(&saved).unwrap());
+        assert!(matches!(result, Err(FormatError::TooNew(_))));
+    }
+
+    #[test]
+    fn test_garbage_is_invalid() {
+        assert!(matches!(decode(b"[1, 2]"), Err(FormatError::Invalid(_))));
+        assert!(matches!(
+            decode(br#"{"version": 0, "state": {}}"#),
+            Err(FormatError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,141 @@
+use super::GameStore;
+use anyhow::Result;
+use futures_util::future::BoxFuture;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+/// Saved games as JSON files under a directory, one subdirectory per user
+pub struct FsStore {
+    root: PathBuf,
+}
+
+impl FsStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FsStore { root: root.into() }
+    }
+}
+
+impl GameStore for FsStore {
+    fn put(&self, key: String, body: Vec<u8>) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            let path = self.root.join(&key);
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            // Write then rename, so a crash mid-write never leaves a truncated save
+            let tmp = path.with_extension("json.tmp");
+            tokio::fs::write(&tmp, body).await?;
+            tokio::fs::rename(&tmp, &path).await?;
+            Ok(())
+        })
+    }
+
+    fn get(&self, key: String) -> BoxFuture<'_, Result<Option<Vec<u8>>>> {
+        Box::pin(async move {
+            match tokio::fs::read(self.root.join(&key)).await {
+                Ok(body) => Ok(Some(body)),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+
+    fn list(&self, prefix: String) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move {
+            let mut entries = match tokio::fs::read_dir(self.root.join(&prefix)).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+                Err(e) => return Err(e.into()),
+            };
+            let mut keys = Vec::new();
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name();
+                // Skip subdirectories and writes still in progress
+                if !entry.file_type().await?.is_file() || name.to_string_lossy().ends_with(".tmp") {
+                    continue;
+                }
+                keys.push(format!("{prefix}{}", name.to_string_lossy()));
+            }
+            keys.sort();
+            Ok(keys)
+        })
+    }
+
+    fn delete(&self, key: String) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            match tokio::fs::remove_file(self.root.join(&key)).await {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(name: &str) -> FsStore {
+        let root = std::env::temp_dir().join(format!("fs-store-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        FsStore::new(root)
+    }
+
+    #[tokio::test]
+    async fn test_put_then_get() {
+        let store = temp_store("roundtrip");
+        store
+            .put("user/ABCD.json".into(), b"{}".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get("user/ABCD.json".into()).await.unwrap(),
+            Some(b"{}".to_vec())
+        );
+
+        store
+            .put("user/ABCD.json".into(), b"[]".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get("user/ABCD.json".into()).await.unwrap(),
+            Some(b"[]".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_keys_under_prefix() {
+        let store = temp_store("list");
+        for key in [
+            "user/G/events/2.jsonl",
+            "user/G/events/1.jsonl",
+            "user/G.json",
+        ] {
+            store.put(key.into(), Vec::new()).await.unwrap();
+        }
+        assert_eq!(
+            store.list("user/G/events/".into()).await.unwrap(),
+            vec!["user/G/events/1.jsonl", "user/G/events/2.jsonl"]
+        );
+        assert!(store.list("user/NONE/".into()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delete_then_get() {
+        let store = temp_store("delete");
+        store
+            .put("user/ABCD.json".into(), b"{}".to_vec())
+            .await
+            .unwrap();
+        store.delete("user/ABCD.json".into()).await.unwrap();
+        assert_eq!(store.get("user/ABCD.json".into()).await.unwrap(), None);
+        // Deleting what isn't there is fine
+        store.delete("user/ABCD.json".into()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_missing_key_is_none() {
+        let store = temp_store("missing");
+        assert_eq!(store.get("user/NONE.json".into()).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,51 @@
+use super::GameStore;
+use anyhow::Result;
+use futures_util::future::{self, BoxFuture};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Saved games held in memory, for tests and throwaway servers.
+/// Share one store between servers to simulate a restart.
+#[derive(Default)]
+pub struct MemoryStore {
+    objects: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl GameStore for MemoryStore {
+    fn put(&self, key: String, body: Vec<u8>) -> BoxFuture<'_, Result<()>> {
+        self.objects.lock().unwrap().insert(key, body);
+        Box::pin(future::ready(Ok(())))
+    }
+
+    fn get(&self, key: String) -> BoxFuture<'_, Result<Option<Vec<u8>>>> {
+        let body = self.objects.lock().unwrap().get(&key).cloned();
+        Box::pin(future::ready(Ok(body)))
+    }
+
+    fn list(&self, prefix: String) -> BoxFuture<'_, Result<Vec<String>>> {
+        let mut keys: Vec<String> = self
+            .objects
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|key| {
+                key.strip_prefix(&prefix)
+                    .is_some_and(|name| !name.contains('/'))
+            })
+            .cloned()
+            .collect();
+        keys.sort();
+        Box::pin(future::ready(Ok(keys)))
+    }
+
+    fn delete(&self, key: String) -> BoxFuture<'_, Result<()>> {
+        self.objects.lock().unwrap().remove(&key);
+        Box::pin(future::ready(Ok(())))
+    }
+}
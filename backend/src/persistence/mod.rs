@@ -0,0 +1,480 @@
+//! Saved game storage.
+//!
+//! `PersistenceClient` turns games into keyed documents and hands them to a `GameStore`
+//! backend, chosen by the `GAME_STORE` env var: S3, a local directory, or memory. With no
+//! backend configured, saves are no-ops and loads find nothing.
+//!
+//! Each game has a versioned snapshot (see `format`) at `{user_id}/{game_code}.json` and an
+//! append-only event log of JSON-lines batches at
+//! `{user_id}/{game_code}/events/{first_seq}.jsonl`. Loading returns the snapshot plus the
+//! events logged after it; saving a snapshot deletes the batches it covers.
+//!
+//! A host's saved games are the snapshots directly under their `{user_id}/` prefix. Each
+//! also has a `{user_id}/{game_code}/summary.json`, rewritten as the game changes, so
+//! listing them doesn't load every game.
+//! `.active_games.json` indexes the games to bring back into memory when the server
+//! starts; user IDs can't start with `.`, so it never collides with a user's prefix.
+
+mod format;
+mod fs;
+mod memory;
+mod s3;
+
+pub use format::{SAVE_FORMAT_VERSION, SnapshotMeta};
+pub use fs::FsStore;
+pub use memory::MemoryStore;
+pub use s3::S3Store;
+
+use crate::game_timer::now_ms;
+use crate::infra;
+use crate::model::event::{self, GameEvent};
+use crate::model::server_message::{GameState, SavedGameSummary};
+use anyhow::{Result, anyhow, bail};
+use futures_util::future::BoxFuture;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+const ACTIVE_GAMES_KEY: &str = ".active_games.json";
+
+/// Storage for saved games, addressed by keys like `{user_id}/{game_code}.json`
+pub trait GameStore: Send + Sync {
+    /// Write a document, replacing any previous one under the key
+    fn put(&self, key: String, body: Vec<u8>) -> BoxFuture<'_, Result<()>>;
+
+    /// Read a document; Ok(None) if nothing is stored under the key
+    fn get(&self, key: String) -> BoxFuture<'_, Result<Option<Vec<u8>>>>;
+
+    /// Keys directly under `prefix` (which ends in `/`), in sorted order
+    fn list(&self, prefix: String) -> BoxFuture<'_, Result<Vec<String>>>;
+
+    /// Remove a document; Ok if nothing is stored under the key
+    fn delete(&self, key: String) -> BoxFuture<'_, Result<()>>;
+}
+
+/// A game as last saved: its snapshot and the events logged after it
+pub struct SavedGame {
+    pub state: GameState,
+    /// The last event included in `state`, and when the game was created and saved
+    pub meta: SnapshotMeta,
+    /// Events after `meta.event_seq`, in order
+    pub events: Vec<GameEvent>,
+}
+
+impl SavedGame {
+    /// When the game last changed: its last logged event, or else its snapshot
+    pub fn last_played_ms(&self) -> Option<u64> {
+        self.events
+            .last()
+            .map(|event| event.at_ms)
+            .or(self.meta.saved_at_ms)
+    }
+}
+
+/// An entry in the index of games to restore at startup
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveGame {
+    pub user_id: String,
+    pub game_code: String,
+}
+
+pub struct PersistenceClient {
+    store: Option<Arc<dyn GameStore>>,
+    /// Held across each read-modify-write of the active games index
+    index_lock: Mutex<()>,
+}
+
+impl PersistenceClient {
+    /// Initialize from environment variables.
+    /// `GAME_STORE` picks the backend: `s3` (needs S3_BUCKET_NAME), `fs` (a directory of
+    /// JSON files at GAME_STORE_DIR), `memory`, or `none`. Unset means S3 when a bucket is
+    /// configured and we're not running locally, otherwise none.
+    pub async fn new() -> Self {
+        let bucket_name = env::var("S3_BUCKET_NAME").unwrap_or_default();
+        let kind = env::var("GAME_STORE").unwrap_or_else(|_| {
+            if infra::is_local() || bucket_name.is_empty() {
+                "none".to_string()
+            } else {
+                "s3".to_string()
+            }
+        });
+
+        let store: Option<Arc<dyn GameStore>> = match kind.as_str() {
+            "s3" if bucket_name.is_empty() => {
+                warn!("GAME_STORE=s3 but S3_BUCKET_NAME not set, persistence disabled");
+                None
+            }
+            "s3" => {
+                info!("S3 persistence enabled with bucket: {bucket_name}");
+                Some(Arc::new(S3Store::new(bucket_name).await))
+            }
+            "fs" => {
+                let dir = env::var("GAME_STORE_DIR").unwrap_or_else(|_| "saved_games".into());
+                info!("Filesystem persistence enabled in: {dir}");
+                Some(Arc::new(FsStore::new(dir)))
+            }
+            "memory" => {
+                info!("In-memory persistence enabled; saves are lost on restart");
+                Some(Arc::new(MemoryStore::new()))
+            }
+            "none" => {
+                info!("Persistence disabled");
+                None
+            }
+            other => {
+                warn!("Unknown GAME_STORE={other}, persistence disabled");
+                None
+            }
+        };
+        PersistenceClient {
+            store,
+            index_lock: Mutex::new(()),
+        }
+    }
+
+    /// Save to and load from the given store
+    pub fn with_store(store: Arc<dyn GameStore>) -> Self {
+        PersistenceClient {
+            store: Some(store),
+            index_lock: Mutex::new(()),
+        }
+    }
+
+    /// Build the key prefix for a game: {user_id}/{game_code}
+    fn build_key(user_id: &str, game_code: &str) -> Result<String> {
+        Self::check_key_part(game_code)?;
+        Ok(format!("{}{}", Self::user_prefix(user_id)?, game_code))
+    }
+
+    fn user_prefix(user_id: &str) -> Result<String> {
+        Self::check_key_part(user_id)?;
+        Ok(format!("{user_id}/"))
+    }
+
+    /// User IDs come from tokens and game codes from clients; neither may escape its place
+    /// in the key
+    fn check_key_part(part: &str) -> Result<()> {
+        if part.is_empty() || part.contains(['/', '\\']) || part.starts_with('.') {
+            bail!("Invalid saved game key part: {part:?}");
+        }
+        Ok(())
+    }
+
+    fn snapshot_key(user_id: &str, game_code: &str) -> Result<String> {
+        Ok(format!("{}.json", Self::build_key(user_id, game_code)?))
+    }
+
+    fn events_prefix(user_id: &str, game_code: &str) -> Result<String> {
+        Ok(format!("{}/events/", Self::build_key(user_id, game_code)?))
+    }
+
+    fn summary_key(user_id: &str, game_code: &str) -> Result<String> {
+        Ok(format!(
+            "{}/summary.json",
+            Self::build_key(user_id, game_code)?
+        ))
+    }
+
+    /// Save a snapshot of game state that includes events up to `event_seq`.
+    /// If no store is configured, returns Ok immediately.
+    pub async fn save_game_state(
+        &self,
+        user_id: &str,
+        game_code: &str,
+        state: &GameState,
+        event_seq: u64,
+        created_at_ms: Option<u64>,
+    ) -> Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+
+        let key = Self::snapshot_key(user_id, game_code)?;
+        let meta = SnapshotMeta {
+            event_seq,
+            created_at_ms,
+            saved_at_ms: Some(now_ms()),
+        };
+        let body = format::encode(state, &meta)?;
+        store.put(key.clone(), body).await?;
+
+        info!("Saved game state: {key}");
+        // The snapshot is saved either way; leftover batches are only skipped on load
+        if let Err(e) = Self::compact_events(store.as_ref(), user_id, game_code, event_seq).await {
+            warn!("Failed to compact event log for {key}: {e}");
+        }
+        Ok(())
+    }
+
+    /// Delete the event batches a snapshot at `event_seq` covers. A batch is covered once
+    /// the batch after it starts no later than `event_seq + 1`; the last batch is kept.
+    async fn compact_events(
+        store: &dyn GameStore,
+        user_id: &str,
+        game_code: &str,
+        event_seq: u64,
+    ) -> Result<()> {
+        let keys = store.list(Self::events_prefix(user_id, game_code)?).await?;
+        for (key, next_key) in keys.iter().zip(keys.iter().skip(1)) {
+            if Self::batch_first_seq(next_key).is_some_and(|seq| seq <= event_seq + 1) {
+                store.delete(key.clone()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The sequence number of a batch's first event, from its key
+    fn batch_first_seq(key: &str) -> Option<u64> {
+        key.rsplit('/').next()?.strip_suffix(".jsonl")?.parse().ok()
+    }
+
+    /// Append a batch of events to a game's log. Batches are keyed by their first sequence
+    /// number, so retrying a batch (even with more events added) replaces it.
+    pub async fn append_events(
+        &self,
+        user_id: &str,
+        game_code: &str,
+        events: &[GameEvent],
+    ) -> Result<()> {
+        let (Some(store), Some(first)) = (&self.store, events.first()) else {
+            return Ok(());
+        };
+
+        let key = format!(
+            "{}{:012}.jsonl",
+            Self::events_prefix(user_id, game_code)?,
+            first.seq
+        );
+        store.put(key, event::encode_batch(events)?).await
+    }
+
+    /// Save the summary a game is listed with.
+    /// If no store is configured, returns Ok immediately.
+    pub async fn save_summary(
+        &self,
+        user_id: &str,
+        game_code: &str,
+        summary: &SavedGameSummary,
+    ) -> Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        let key = Self::summary_key(user_id, game_code)?;
+        store.put(key, serde_json::to_vec(summary)?).await
+    }
+
+    /// Load the summary a game is listed with.
+    /// Returns Ok(None) if none is saved, e.g. for games saved before summaries were, or if
+    /// no store is configured.
+    pub async fn load_summary(
+        &self,
+        user_id: &str,
+        game_code: &str,
+    ) -> Result<Option<SavedGameSummary>> {
+        let Some(store) = &self.store else {
+            return Ok(None);
+        };
+        match store.get(Self::summary_key(user_id, game_code)?).await? {
+            Some(body) => Ok(Some(serde_json::from_slice(&body)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Load a game's snapshot and the events logged after it.
+    /// If no store is configured, returns Ok(None).
+    /// Returns Ok(None) if no snapshot is saved under the key.
+    /// Snapshots from older format versions are migrated.
+    /// Returns Err on deserialization failure with a user-friendly message.
+    pub async fn load_game(&self, user_id: &str, game_code: &str) -> Result<Option<SavedGame>> {
+        let Some(store) = &self.store else {
+            return Ok(None);
+        };
+
+        let key = Self::snapshot_key(user_id, game_code)?;
+        let bytes = match store.get(key.clone()).await {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => {
+                info!("No saved game state found: {key}");
+                return Ok(None);
+            }
+            Err(e) => {
+                warn!("Error loading game state {key}: {e}");
+                return Err(anyhow!("Failed to load saved game state"));
+            }
+        };
+
+        let (state, meta) = format::decode(&bytes).map_err(|e| {
+            warn!("Failed to deserialize game state {key}: {}", e.detail());
+            anyhow!("{e}")
+        })?;
+
+        let events = Self::load_events_after(store.as_ref(), user_id, game_code, meta.event_seq)
+            .await
+            .map_err(|e| {
+                warn!("Error loading event log for {key}: {e}");
+                anyhow!("Failed to load saved game state")
+            })?;
+        info!(
+            "Loaded game state: {key}, with {} later events",
+            events.len()
+        );
+        Ok(Some(SavedGame {
+            state,
+            meta,
+            events,
+        }))
+    }
+
+    /// Codes of the games a user has saved, in sorted order.
+    /// If no store is configured, returns an empty list.
+    pub async fn list_games(&self, user_id: &str) -> Result<Vec<String>> {
+        let Some(store) = &self.store else {
+            return Ok(Vec::new());
+        };
+
+        let prefix = Self::user_prefix(user_id)?;
+        let keys = store.list(prefix.clone()).await?;
+        Ok(keys
+            .iter()
+            .filter_map(|key| key.strip_prefix(&prefix)?.strip_suffix(".json"))
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Delete a game's snapshot, summary and event log.
+    /// Returns Ok(false) if no snapshot is saved under the key, or no store is configured.
+    pub async fn delete_game(&self, user_id: &str, game_code: &str) -> Result<bool> {
+        let Some(store) = &self.store else {
+            return Ok(false);
+        };
+
+        let key = Self::snapshot_key(user_id, game_code)?;
+        if store.get(key.clone()).await?.is_none() {
+            return Ok(false);
+        }
+        // The log goes first: a snapshot left behind by a failed delete still loads, but
+        // stale events left behind would be replayed onto a new game with the same code
+        for event_key in store.list(Self::events_prefix(user_id, game_code)?).await? {
+            store.delete(event_key).await?;
+        }
+        store.delete(Self::summary_key(user_id, game_code)?).await?;
+        store.delete(key.clone()).await?;
+
+        info!("Deleted saved game: {key}");
+        Ok(true)
+    }
+
+    /// The games to restore at startup.
+    /// If no store is configured, returns an empty list.
+    pub async fn active_games(&self) -> Result<Vec<ActiveGame>> {
+        let Some(store) = &self.store else {
+            return Ok(Vec::new());
+        };
+        Self::read_active_games(store.as_ref()).await
+    }
+
+    /// Add a game to the index of games to restore at startup. Codes only need to be unique
+    /// per user in storage, but games in memory are found by code alone, so returns
+    /// Ok(false), leaving the index alone, if another user's game there has the same code.
+    /// If no store is configured, returns Ok(true).
+    pub async fn add_active_game(&self, user_id: &str, game_code: &str) -> Result<bool> {
+        let game = ActiveGame {
+            user_id: user_id.to_string(),
+            game_code: game_code.to_string(),
+        };
+        let mut claimed = true;
+        self.update_active_games(|games| {
+            if games
+                .iter()
+                .any(|other| other.game_code == game.game_code && other.user_id != game.user_id)
+            {
+                claimed = false;
+                return false;
+            }
+            if games.contains(&game) {
+                return false;
+            }
+            games.push(game);
+            true
+        })
+        .await?;
+        Ok(claimed)
+    }
+
+    /// Take games out of the index of games to restore at startup
+    pub async fn remove_active_games(&self, removed: &[ActiveGame]) -> Result<()> {
+        self.update_active_games(|games| {
+            let before = games.len();
+            games.retain(|game| !removed.contains(game));
+            games.len() != before
+        })
+        .await
+    }
+
+    /// Edit the index, writing it back if `edit` reports a change
+    async fn update_active_games(
+        &self,
+        edit: impl FnOnce(&mut Vec<ActiveGame>) -> bool,
+    ) -> Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+
+        let _index = self.index_lock.lock().await;
+        let mut games = Self::read_active_games(store.as_ref()).await?;
+        if edit(&mut games) {
+            store
+                .put(ACTIVE_GAMES_KEY.to_string(), serde_json::to_vec(&games)?)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn read_active_games(store: &dyn GameStore) -> Result<Vec<ActiveGame>> {
+        match store.get(ACTIVE_GAMES_KEY.to_string()).await? {
+            Some(body) => Ok(serde_json::from_slice(&body)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Events after `event_seq`, stopping at the first gap in the log
+    async fn load_events_after(
+        store: &dyn GameStore,
+        user_id: &str,
+        game_code: &str,
+        event_seq: u64,
+    ) -> Result<Vec<GameEvent>> {
+        let mut events = Vec::new();
+        let mut next_seq = event_seq + 1;
+        let keys = store.list(Self::events_prefix(user_id, game_code)?).await?;
+        // Skip reading batches that end before `next_seq`, left over if compaction failed
+        let first_needed = keys
+            .iter()
+            .rposition(|key| Self::batch_first_seq(key).is_some_and(|seq| seq <= next_seq))
+            .unwrap_or(0);
+        for key in keys.into_iter().skip(first_needed) {
+            let Some(body) = store.get(key).await? else {
+                continue;
+            };
+            for event in event::decode_batch(&body)? {
+                if event.seq < next_seq {
+                    continue;
+                }
+                if event.seq > next_seq {
+                    // A batch that was never written; later events built on it
+                    warn!(
+                        "Event log for {user_id}/{game_code} has a gap before {}",
+                        event.seq
+                    );
+                    return Ok(events);
+                }
+                next_seq += 1;
+                events.push(event);
+            }
+        }
+        Ok(events)
+    }
+}
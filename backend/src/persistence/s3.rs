@@ -0,0 +1,115 @@
+use super::GameStore;
+use anyhow::Result;
+use aws_config::BehaviorVersion;
+use aws_config::meta::region::RegionProviderChain;
+use aws_sdk_s3::Client as S3Client;
+use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::operation::get_object::GetObjectError;
+use futures_util::future::BoxFuture;
+
+/// Saved games as objects in an S3 bucket
+pub struct S3Store {
+    client: S3Client,
+    bucket_name: String,
+}
+
+impl S3Store {
+    /// Connect using the default AWS credential and region chain
+    pub async fn new(bucket_name: String) -> Self {
+        let region_provider = RegionProviderChain::default_provider();
+        let config = aws_config::defaults(BehaviorVersion::latest())
+            .region(region_provider)
+            .load()
+            .await;
+
+        S3Store {
+            client: S3Client::new(&config),
+            bucket_name,
+        }
+    }
+}
+
+impl GameStore for S3Store {
+    fn put(&self, key: String, body: Vec<u8>) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            self.client
+                .put_object()
+                .bucket(&self.bucket_name)
+                .key(&key)
+                .body(body.into())
+                .content_type("application/json")
+                .send()
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn get(&self, key: String) -> BoxFuture<'_, Result<Option<Vec<u8>>>> {
+        Box::pin(async move {
+            let result = self
+                .client
+                .get_object()
+                .bucket(&self.bucket_name)
+                .key(&key)
+                .send()
+                .await;
+
+            match result {
+                Ok(output) => {
+                    let body = output.body.collect().await?;
+                    Ok(Some(body.into_bytes().to_vec()))
+                }
+                Err(SdkError::ServiceError(err))
+                    if matches!(err.err(), GetObjectError::NoSuchKey(_)) =>
+                {
+                    Ok(None)
+                }
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+
+    fn list(&self, prefix: String) -> BoxFuture<'_, Result<Vec<String>>> {
+        Box::pin(async move {
+            let mut keys = Vec::new();
+            let mut continuation_token = None;
+            loop {
+                let output = self
+                    .client
+                    .list_objects_v2()
+                    .bucket(&self.bucket_name)
+                    .prefix(&prefix)
+                    .delimiter("/")
+                    .set_continuation_token(continuation_token)
+                    .send()
+                    .await?;
+                keys.extend(
+                    output
+                        .contents()
+                        .iter()
+                        .filter_map(|object| object.key().map(str::to_string)),
+                );
+                match output.next_continuation_token() {
+                    Some(token) => continuation_token = Some(token.to_string()),
+                    None => break,
+                }
+            }
+            // S3 lists in UTF-8 binary order already; sort anyway to match the other stores
+            keys.sort();
+            Ok(keys)
+        })
+    }
+
+    fn delete(&self, key: String) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            // S3 reports success for keys that don't exist
+            self.client
+                .delete_object()
+                .bucket(&self.bucket_name)
+                .key(&key)
+                .send()
+                .await?;
+            Ok(())
+        })
+    }
+}
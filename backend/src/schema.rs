@@ -0,0 +1,45 @@
+//! JSON Schema for the WebSocket protocol, derived from the serde types so the frontend
+//! can be checked against what the server actually sends and accepts.
+//!
+//! Regenerate the committed copy after changing any wire type:
+//! `cargo run --bin write_schema`
+
+use schemars::JsonSchema;
+
+use crate::model::{client_message::ClientEnvelope, server_message::ServerMessage};
+
+/// Where the committed schema lives, relative to the crate root
+pub const SCHEMA_PATH: &str = "schema/protocol.schema.json";
+
+/// Any message sent over the socket, in either direction
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+enum ProtocolMessage {
+    Client(ClientEnvelope),
+    Server(ServerMessage),
+}
+
+/// The protocol schema as pretty-printed JSON, as it should be committed
+pub fn protocol_schema() -> String {
+    let schema = schemars::schema_for!(ProtocolMessage);
+    let mut json = serde_json::to_string_pretty(&schema).expect("Schema serializes to JSON");
+    json.push('\n');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_committed_schema_is_up_to_date() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(SCHEMA_PATH);
+        let committed = std::fs::read_to_string(&path).unwrap_or_default();
+        assert!(
+            committed == protocol_schema(),
+            "{SCHEMA_PATH} is out of date with the wire types; run `cargo run --bin write_schema`"
+        );
+    }
+}
@@ -1,47 +1,87 @@
 use crate::{
-    auth::{AuthResult, JwtValidator},
+    auth::{self, AuthResult, JwtValidator},
+    autosave::{Autosave, SAVE_DEBOUNCE},
+    game_timer::now_ms,
     handler::{host, team, watcher},
     infra,
+    limits::Limits,
     model::{
-        client_message::{ClientMessage, HostAction, TeamAction, WatcherAction},
+        client_message::{ClientEnvelope, ClientMessage, HostAction, TeamAction, WatcherAction},
+        error::ErrorCode,
         game::Game,
+        protocol::{self, Encoding, Handshake},
         server_message::ServerMessage,
     },
-    persistence::PersistenceClient,
+    outbox,
+    persistence::{ActiveGame, PersistenceClient, SavedGame},
     timer::ShutdownTimer,
 };
-use futures_util::{SinkExt, StreamExt};
+use futures_util::{SinkExt, StreamExt, future::join_all};
 use log::*;
-use rand::Rng;
-use std::{collections::HashMap, sync::Arc};
+use std::{cmp::Reverse, collections::HashMap, sync::Arc, time::Duration};
 use tokio::{
     net::{TcpListener, TcpStream},
-    sync::{Mutex, mpsc},
+    sync::{Mutex, watch},
+    task::JoinSet,
+    time::Instant,
 };
 use tokio_tungstenite::{
-    accept_hdr_async,
+    WebSocketStream, accept_hdr_async_with_config,
     tungstenite::{
         Error, Message, Result,
         handshake::server::{Request, Response},
+        http::{HeaderValue, header::SEC_WEBSOCKET_PROTOCOL},
     },
 };
 
-pub type Tx = mpsc::UnboundedSender<Message>;
-pub type Rx = mpsc::UnboundedReceiver<Message>;
+pub type Tx = outbox::Sender;
+pub type Rx = outbox::Receiver;
+
+/// How long a shutdown may take to save games and close sockets before connections are
+/// dropped. ECS allows 30 seconds between SIGTERM and SIGKILL.
+pub const SHUTDOWN_DEADLINE: Duration = Duration::from_secs(20);
+
+/// Games idle for longer than this aren't restored at startup, and leave the active games
+/// index; their hosts can still reclaim them from storage
+pub const ACTIVE_GAME_MAX_IDLE: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Subprotocol clients offer alongside their token, and the one the server selects.
+/// Browsers require the server to select one of the values offered, and the token itself
+/// must never be echoed back.
+pub const SUBPROTOCOL: &str = "trivia-wizard";
+
+/// Prefix of the subprotocol value carrying a client's token: `token.<jwt>`
+pub const TOKEN_SUBPROTOCOL_PREFIX: &str = "token.";
+
+const RESTARTING_MESSAGE: &str =
+    "The server is restarting. Your game has been saved; reconnect in a moment.";
+
+/// A game behind its own lock, so activity in one game never blocks another
+pub type SharedGame = Arc<Mutex<Game>>;
 
 pub struct AppState {
-    pub games: Mutex<HashMap<String, Game>>,
+    /// Lookup only: held just long enough to find or insert a game, never while locking one
+    pub games: Mutex<HashMap<String, SharedGame>>,
     pub timer: Mutex<ShutdownTimer>,
     pub validator: Arc<dyn JwtValidator>,
     pub persistence: Arc<PersistenceClient>,
+    pub autosave: Arc<Autosave>,
+    pub limits: Limits,
+    /// Accept `?token=` in the URL from clients that predate other ways of sending it.
+    /// Off by default: URLs end up in proxy and load balancer logs.
+    pub allow_query_token: bool,
 }
 
-fn generate_code() -> String {
-    rand::rng()
-        .sample_iter(&rand::distr::Alphabetic)
-        .take(4)
-        .map(|c| (c as char).to_ascii_uppercase())
-        .collect()
+impl AppState {
+    /// Look up a game by code. The map lock is released before returning.
+    pub async fn game(&self, game_code: &str) -> Option<SharedGame> {
+        self.games.lock().await.get(game_code).cloned()
+    }
+
+    /// All games currently in memory
+    pub async fn all_games(&self) -> Vec<SharedGame> {
+        self.games.lock().await.values().cloned().collect()
+    }
 }
 
 async fn accept_connection(stream: TcpStream, app_state: Arc<AppState>) {
@@ -52,8 +92,8 @@ async fn accept_connection(stream: TcpStream, app_state: Arc<AppState>) {
         }
     }
 
-    for game in app_state.games.lock().await.values() {
-        if game.host_tx.is_some() {
+    for game in app_state.all_games().await {
+        if game.lock().await.has_host_connection() {
             return;
         }
     }
@@ -62,7 +102,19 @@ async fn accept_connection(stream: TcpStream, app_state: Arc<AppState>) {
     app_state.timer.lock().await.start_timer().await;
 }
 
-fn extract_token_from_request(request: &Request) -> Option<String> {
+/// The subprotocols a client offered in its `Sec-WebSocket-Protocol` headers
+fn offered_subprotocols(request: &Request) -> Vec<&str> {
+    request
+        .headers()
+        .get_all(SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect()
+}
+
+fn extract_token_from_query(request: &Request) -> Option<String> {
     let uri = request.uri();
     let query = uri.query()?;
     for pair in query.split('&') {
@@ -76,214 +128,363 @@ fn extract_token_from_request(request: &Request) -> Option<String> {
 }
 
 async fn handle_connection(stream: TcpStream, app_state: Arc<AppState>) -> Result<()> {
-    // In local dev mode (not tests), skip auth entirely and treat all connections as authenticated hosts
-    let skip_auth = infra::is_local() && !infra::is_test();
+    // In local dev mode (not tests), skip auth entirely and treat all connections as
+    // authenticated hosts, unless a self-hosted server asked for local tokens
+    let skip_auth = infra::is_local() && !infra::is_test() && !auth::is_local_auth();
+
+    // The handshake callback can't wait, so it only picks out the token; it's validated
+    // once the socket is accepted. A token in `Hello` replaces this one.
+    let mut token = None;
+    let allow_query_token = app_state.allow_query_token;
+
+    // The error type is fixed by tungstenite's handshake callback
+    #[allow(clippy::result_large_err)]
+    let callback = |request: &Request, mut response: Response| {
+        let offered = offered_subprotocols(request);
+        token = offered
+            .iter()
+            .find_map(|protocol| protocol.strip_prefix(TOKEN_SUBPROTOCOL_PREFIX))
+            .map(str::to_string);
+        if offered.contains(&SUBPROTOCOL) {
+            response.headers_mut().insert(
+                SEC_WEBSOCKET_PROTOCOL,
+                HeaderValue::from_static(SUBPROTOCOL),
+            );
+        }
+        if token.is_none() {
+            let query_token = extract_token_from_query(request);
+            if allow_query_token {
+                token = query_token;
+            } else if query_token.is_some() {
+                warn!("Ignoring token in URL query; set ALLOW_QUERY_TOKEN to accept it");
+            }
+        }
+        Ok(response)
+    };
+
+    let config = app_state.limits.websocket_config();
+    let mut ws_stream = accept_hdr_async_with_config(stream, callback, Some(config))
+        .await
+        .expect("Failed to accept");
+
+    let Some(mut frame) = next_frame(&mut ws_stream).await? else {
+        return Ok(());
+    };
 
-    let mut auth_result: Option<AuthResult> = if skip_auth {
+    // Clients that predate the handshake open with their first action. Raw frames are never
+    // logged, since they may carry a token; parsed messages redact it.
+    let mut handshake = Handshake::LEGACY;
+    let mut expected_kind = None;
+    if let Some(Ok(ClientEnvelope {
+        message:
+            ClientMessage::Hello {
+                protocol_version: requested,
+                client_kind,
+                encoding,
+                token: hello_token,
+            },
+        ..
+    })) = protocol::decode_client_message(&frame, protocol::PROTOCOL_VERSION)
+    {
+        // The reply to Hello is always JSON; the requested encoding applies after it
+        if !protocol::is_supported(requested) {
+            info!("Rejecting {client_kind:?} client on unsupported protocol version {requested}");
+            let response = ServerMessage::UnsupportedProtocol {
+                message: protocol::REFRESH_MESSAGE.to_string(),
+                supported_versions: protocol::supported_versions(),
+            };
+            ws_stream.send(Encoding::Json.encode(&response)).await?;
+            return Ok(());
+        }
+
+        handshake = Handshake {
+            protocol_version: requested,
+            encoding,
+        };
+        expected_kind = Some(client_kind);
+        let response = ServerMessage::Welcome {
+            protocol_version: requested,
+            supported_versions: protocol::supported_versions(),
+            encoding,
+        };
+        ws_stream.send(Encoding::Json.encode(&response)).await?;
+
+        let Some(next) = next_frame(&mut ws_stream).await? else {
+            return Ok(());
+        };
+        frame = next;
+        if let Some(hello_token) = hello_token {
+            token = Some(hello_token.as_str().to_string());
+        }
+    }
+
+    let auth_result: Option<AuthResult> = if skip_auth {
         info!("Local dev mode: skipping auth, treating connection as authenticated host");
         Some(AuthResult {
             user_id: "local-dev".to_string(),
             is_host: true,
+            expires_at_ms: None,
         })
+    } else if let Some(token) = token {
+        match app_state.validator.validate(&token).await {
+            Ok(result) => {
+                info!("Token validated for user: {}", result.user_id);
+                Some(result)
+            }
+            Err(e) => {
+                warn!("Token validation failed: {}", e);
+                None
+            }
+        }
     } else {
         None
     };
 
-    let validator = app_state.validator.clone();
-
-    let callback = |request: &Request, response: Response| {
-        // Only validate tokens when not skipping auth
-        if !skip_auth && let Some(token) = extract_token_from_request(request) {
-            match validator.validate(&token) {
-                Ok(result) => {
-                    info!("Token validated for user: {}", result.user_id);
-                    auth_result = Some(result);
-                }
-                Err(e) => {
-                    warn!("Token validation failed: {}", e);
-                }
+    match protocol::decode_client_message(&frame, handshake.protocol_version)
+        .expect("next_frame only returns data frames")
+    {
+        Ok(ClientEnvelope {
+            message: client_message,
+            request_id,
+        }) => {
+            info!("Parsed message: {client_message:?}");
+            if let Some(kind) = expected_kind
+                && client_message.kind() != Some(kind)
+            {
+                warn!("Client announced itself as {kind:?} but sent {client_message:?}");
+                let error_message = ServerMessage::error(
+                    ErrorCode::UnexpectedAction,
+                    format!("First action doesn't match client kind {kind:?}"),
+                )
+                .for_request(request_id);
+                ws_stream
+                    .send(handshake.encoding.encode(&error_message))
+                    .await?;
+                return Ok(());
             }
-        }
-        Ok(response)
-    };
-
-    let mut ws_stream = accept_hdr_async(stream, callback)
-        .await
-        .expect("Failed to accept");
-
-    if let Some(msg) = ws_stream.next().await {
-        let msg = msg?;
-        if let Message::Text(text) = msg {
-            info!("Received message: {text}");
-            match serde_json::from_str::<ClientMessage>(&text) {
-                Ok(client_message) => {
-                    info!("Parsed message: {client_message:?}");
-                    match client_message {
-                        ClientMessage::Host(action) => {
-                            // Host actions require authentication
-                            match &auth_result {
-                                Some(auth) if auth.is_host => {
-                                    if let HostAction::CreateGame { game_code } = action {
-                                        let code = game_code.unwrap_or_else(generate_code);
-                                        host::create_game(
-                                            app_state,
-                                            ws_stream,
-                                            code,
-                                            auth.user_id.clone(),
+            match client_message {
+                ClientMessage::Host(action) => {
+                    // Host actions require authentication. Staff joining someone else's
+                    // game only need to be invited, not in the host group.
+                    let joining_as_staff = matches!(action, HostAction::JoinAsStaff { .. });
+                    match &auth_result {
+                        Some(auth) if auth.is_host || joining_as_staff => {
+                            host::start_host(
+                                app_state,
+                                ws_stream,
+                                auth.clone(),
+                                handshake,
+                                action,
+                                request_id,
+                            )
+                            .await?;
+                        }
+                        Some(_) => {
+                            warn!("User authenticated but not in Trivia-Hosts group");
+                            let error_message = ServerMessage::error(
+                                ErrorCode::NotAuthorized,
+                                "User is not authorized as a host",
+                            )
+                            .for_request(request_id);
+                            ws_stream
+                                .send(handshake.encoding.encode(&error_message))
+                                .await?;
+                        }
+                        None => {
+                            info!("Host action attempted without authentication");
+                            let error_message = ServerMessage::error(
+                                ErrorCode::AuthenticationRequired,
+                                "Authentication required for host actions",
+                            )
+                            .for_request(request_id);
+                            ws_stream
+                                .send(handshake.encoding.encode(&error_message))
+                                .await?;
+                        }
+                    }
+                }
+                ClientMessage::Team(action) => {
+                    info!("Team message: {action:?}");
+                    if let TeamAction::ValidateJoin {
+                        game_code,
+                        team_name,
+                    } = action
+                    {
+                        // Validate game code and team name
+                        let response = {
+                            if let Some(game) = app_state.game(&game_code).await {
+                                let game = game.lock().await;
+                                // Check if team exists and its connection status
+                                if let Some(team_data) = game.find_team(&team_name) {
+                                    if team_data.connected {
+                                        ServerMessage::error(
+                                            ErrorCode::TeamNameTaken,
+                                            "Team name already in use",
                                         )
-                                        .await;
                                     } else {
-                                        warn!(
-                                            "Expected CreateGame from new Host connection, instead got: {action:?}"
-                                        );
-                                        let error_message =
-                                            ServerMessage::error("First action must be CreateGame");
-                                        let msg = serde_json::to_string(&error_message).unwrap();
-                                        ws_stream.send(Message::text(msg)).await?;
+                                        // Rejoin path - return TeamGameState
+                                        game.to_team_game_state(&team_name)
+                                            .map(|state| ServerMessage::TeamGameState {
+                                                seq: 0,
+                                                state,
+                                            })
+                                            .unwrap_or_else(|| {
+                                                ServerMessage::error(
+                                                    ErrorCode::Internal,
+                                                    "Failed to get team state",
+                                                )
+                                            })
                                     }
+                                } else {
+                                    ServerMessage::JoinValidated
                                 }
-                                Some(_) => {
-                                    warn!("User authenticated but not in Trivia-Hosts group");
-                                    let error_message =
-                                        ServerMessage::error("User is not authorized as a host");
-                                    let msg = serde_json::to_string(&error_message).unwrap();
-                                    ws_stream.send(Message::text(msg)).await?;
-                                }
-                                None => {
-                                    info!("Host action attempted without authentication");
-                                    let error_message = ServerMessage::error(
-                                        "Authentication required for host actions",
-                                    );
-                                    let msg = serde_json::to_string(&error_message).unwrap();
-                                    ws_stream.send(Message::text(msg)).await?;
-                                }
+                            } else {
+                                ServerMessage::error(ErrorCode::GameNotFound, "Game code not found")
                             }
                         }
-                        ClientMessage::Team(action) => {
-                            info!("Team message: {action:?}");
-                            if let TeamAction::ValidateJoin {
-                                game_code,
-                                team_name,
-                            } = action
-                            {
-                                // Validate game code and team name
-                                let response = {
-                                    let games_map = app_state.games.lock().await;
-                                    if let Some(game) = games_map.get(&game_code) {
-                                        // Check if team exists and its connection status
-                                        if let Some(team_data) = game.find_team(&team_name) {
-                                            if team_data.connected {
-                                                ServerMessage::error("Team name already in use")
-                                            } else {
-                                                // Rejoin path - return TeamGameState
-                                                game.to_team_game_state(&team_name)
-                                                    .map(|state| ServerMessage::TeamGameState {
-                                                        state,
-                                                    })
-                                                    .unwrap_or_else(|| {
-                                                        ServerMessage::error(
-                                                            "Failed to get team state",
-                                                        )
-                                                    })
-                                            }
-                                        } else {
-                                            ServerMessage::JoinValidated
-                                        }
-                                    } else {
-                                        ServerMessage::error("Game code not found")
-                                    }
-                                };
+                        .for_request(request_id.clone());
 
-                                // Send response
-                                let msg = serde_json::to_string(&response).unwrap();
-                                ws_stream.send(Message::text(msg)).await?;
+                        // Send response
+                        ws_stream.send(handshake.encoding.encode(&response)).await?;
 
-                                match response {
-                                    ServerMessage::Error { .. } => {
-                                        // Error case: terminate connection
-                                        return Ok(());
-                                    }
-                                    ServerMessage::TeamGameState { .. } => {
-                                        // Rejoin case: enter game loop immediately
-                                        team::rejoin_game(
-                                            app_state, ws_stream, game_code, team_name,
-                                        )
-                                        .await;
-                                    }
-                                    ServerMessage::JoinValidated => {
-                                        // New team case: wait for JoinGame message
-                                        if let Some(Ok(msg)) = ws_stream.next().await {
-                                            if let Message::Text(text) = msg {
-                                                match serde_json::from_str::<ClientMessage>(&text) {
-                                                    Ok(ClientMessage::Team(
-                                                        TeamAction::JoinGame {
-                                                            game_code,
-                                                            team_name,
-                                                            color_hex,
-                                                            color_name,
-                                                            team_members,
-                                                        },
-                                                    )) => {
-                                                        team::join_game(
-                                                            app_state,
-                                                            ws_stream,
-                                                            game_code,
-                                                            team_name,
-                                                            color_hex,
-                                                            color_name,
-                                                            team_members,
-                                                        )
-                                                        .await;
-                                                    }
-                                                    _ => {
-                                                        let error = ServerMessage::error(
-                                                            "Expected JoinGame after ValidateJoin",
-                                                        );
-                                                        let msg =
-                                                            serde_json::to_string(&error).unwrap();
-                                                        ws_stream.send(Message::text(msg)).await?;
-                                                    }
-                                                }
-                                            }
+                        if let ServerMessage::Error { .. } = response {
+                            // Error case: terminate connection
+                            return Ok(());
+                        }
+                        if let Some(request_id) = request_id {
+                            let ack = ServerMessage::Ack { request_id };
+                            ws_stream.send(handshake.encoding.encode(&ack)).await?;
+                        }
+
+                        match response {
+                            ServerMessage::TeamGameState { .. } => {
+                                // Rejoin case: enter game loop immediately
+                                team::rejoin_game(
+                                    app_state, ws_stream, game_code, team_name, handshake,
+                                )
+                                .await;
+                            }
+                            ServerMessage::JoinValidated => {
+                                // New team case: wait for JoinGame message
+                                if let Some(frame) = next_frame(&mut ws_stream).await? {
+                                    match protocol::decode_client_message(
+                                        &frame,
+                                        handshake.protocol_version,
+                                    ) {
+                                        Some(Ok(ClientEnvelope {
+                                            message:
+                                                ClientMessage::Team(TeamAction::JoinGame {
+                                                    game_code,
+                                                    team_name,
+                                                    color_hex,
+                                                    color_name,
+                                                    team_members,
+                                                }),
+                                            request_id,
+                                        })) => {
+                                            team::join_game(
+                                                app_state,
+                                                ws_stream,
+                                                game_code,
+                                                team_name,
+                                                color_hex,
+                                                color_name,
+                                                team_members,
+                                                handshake,
+                                                request_id,
+                                            )
+                                            .await;
+                                        }
+                                        _ => {
+                                            let error = ServerMessage::error(
+                                                ErrorCode::UnexpectedAction,
+                                                "Expected JoinGame after ValidateJoin",
+                                            );
+                                            ws_stream
+                                                .send(handshake.encoding.encode(&error))
+                                                .await?;
                                         }
                                     }
-                                    _ => {}
                                 }
-                            } else {
-                                error!(
-                                    "Expected ValidateJoin from new Team connection, instead got: {action:?}"
-                                );
-                                let error_message =
-                                    ServerMessage::error("First action must be ValidateJoin");
-                                let msg = serde_json::to_string(&error_message).unwrap();
-                                ws_stream.send(Message::text(msg)).await?;
                             }
+                            _ => {}
                         }
-                        ClientMessage::Watcher(action) => {
-                            // Watchers don't require authentication (public access)
-                            info!("Watcher message: {action:?}");
-                            let WatcherAction::WatchGame { game_code } = action;
-                            watcher::watch_game(app_state, ws_stream, game_code).await;
-                        }
+                    } else {
+                        error!(
+                            "Expected ValidateJoin from new Team connection, instead got: {action:?}"
+                        );
+                        let error_message = ServerMessage::error(
+                            ErrorCode::UnexpectedAction,
+                            "First action must be ValidateJoin",
+                        )
+                        .for_request(request_id);
+                        ws_stream
+                            .send(handshake.encoding.encode(&error_message))
+                            .await?;
                     }
                 }
-                Err(e) => {
-                    error!("Failed to parse message: {e}");
-                    let error_message = ServerMessage::error(format!("Invalid JSON: {e}"));
-                    let msg = serde_json::to_string(&error_message).unwrap();
-                    ws_stream.send(Message::text(msg)).await?;
+                ClientMessage::Hello { .. } => {
+                    let error_message = ServerMessage::error(
+                        ErrorCode::UnexpectedAction,
+                        "Hello may only be sent once",
+                    )
+                    .for_request(request_id);
+                    ws_stream
+                        .send(handshake.encoding.encode(&error_message))
+                        .await?;
+                }
+                ClientMessage::Watcher(action) => {
+                    // Watchers don't require authentication (public access)
+                    info!("Watcher message: {action:?}");
+                    let WatcherAction::WatchGame { game_code } = action;
+                    watcher::watch_game(
+                        app_state,
+                        ws_stream,
+                        game_code,
+                        handshake.encoding,
+                        request_id,
+                    )
+                    .await;
                 }
             }
         }
+        Err(e) => {
+            error!("Failed to parse message: {e}");
+            // Most likely a client on a protocol version it didn't announce
+            let error_message = ServerMessage::error(
+                ErrorCode::InvalidMessage,
+                format!("{e}. If this keeps happening, please refresh the page."),
+            );
+            ws_stream
+                .send(handshake.encoding.encode(&error_message))
+                .await?;
+        }
     }
     Ok(())
 }
 
+/// Read the next data frame (text or binary). None if the client closed the connection or
+/// sent a control frame.
+pub async fn next_frame(ws_stream: &mut WebSocketStream<TcpStream>) -> Result<Option<Message>> {
+    match ws_stream.next().await {
+        Some(msg) => match msg? {
+            frame @ (Message::Text(_) | Message::Binary(_)) => Ok(Some(frame)),
+            _ => Ok(None),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Accept connections until `shutdown` becomes true, then save every game and close all
+/// sockets within `SHUTDOWN_DEADLINE`
 pub async fn start_ws_server(
     listener: TcpListener,
     timer: ShutdownTimer,
     validator: Arc<dyn JwtValidator>,
     persistence: Arc<PersistenceClient>,
+    limits: Limits,
+    allow_query_token: bool,
+    mut shutdown: watch::Receiver<bool>,
 ) {
     let addr = listener.local_addr().expect("Failed to get local address");
     info!("Listening on: {addr}");
@@ -292,15 +493,138 @@ pub async fn start_ws_server(
         games: Mutex::new(HashMap::new()),
         timer: Mutex::new(timer),
         validator,
+        autosave: Arc::new(Autosave::new(persistence.clone(), SAVE_DEBOUNCE)),
         persistence,
+        limits,
+        allow_query_token,
     });
 
-    while let Ok((stream, _)) = listener.accept().await {
-        let peer = stream
-            .peer_addr()
-            .expect("connected streams should have a peer address");
-        info!("Peer address: {peer}");
+    // Before accepting, so teams can rejoin restored games as soon as they reconnect
+    restore_active_games(&app_state).await;
+
+    let mut connections = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let Ok((stream, _)) = accepted else {
+                    break;
+                };
+                let peer = stream
+                    .peer_addr()
+                    .expect("connected streams should have a peer address");
+                info!("Peer address: {peer}");
 
-        tokio::spawn(accept_connection(stream, app_state.clone()));
+                connections.spawn(accept_connection(stream, app_state.clone()));
+                // Reap finished connections so the set doesn't grow forever
+                while connections.try_join_next().is_some() {}
+            }
+            _ = shutdown.wait_for(|stopping| *stopping) => break,
+        }
     }
+
+    // Stop accepting before draining, so no new games start mid-shutdown
+    drop(listener);
+    let deadline = Instant::now() + SHUTDOWN_DEADLINE;
+    if tokio::time::timeout_at(deadline, drain_games(&app_state))
+        .await
+        .is_err()
+    {
+        warn!("Shutdown deadline passed before every game was saved");
+    }
+
+    // Connection handlers exit as their clients acknowledge the close; any still open at
+    // the deadline are dropped when the set is
+    let closed = async { while connections.join_next().await.is_some() {} };
+    if tokio::time::timeout_at(deadline, closed).await.is_err() {
+        warn!(
+            "Dropping {} connections still open at the shutdown deadline",
+            connections.len()
+        );
+    }
+    info!("WebSocket server stopped");
+}
+
+/// Load the games in the active games index into memory, without hosts and with every
+/// team disconnected. Games that are gone or idle too long are dropped from the index.
+async fn restore_active_games(app_state: &Arc<AppState>) {
+    let active = match app_state.persistence.active_games().await {
+        Ok(active) => active,
+        Err(e) => {
+            error!("Failed to read the active games index: {e}");
+            return;
+        }
+    };
+
+    let oldest = now_ms().saturating_sub(ACTIVE_GAME_MAX_IDLE.as_millis() as u64);
+    let loads = active.into_iter().map(|entry| async move {
+        let saved = app_state
+            .persistence
+            .load_game(&entry.user_id, &entry.game_code)
+            .await;
+        (entry, saved)
+    });
+    let mut loaded = join_all(loads).await;
+    // An index written before codes were checked across users can hold one code twice; the
+    // most recently played game keeps it
+    loaded.sort_by_key(|(_, saved)| {
+        let saved = saved.as_ref().ok().and_then(Option::as_ref);
+        Reverse(saved.and_then(SavedGame::last_played_ms))
+    });
+    let mut stale = Vec::new();
+    for (entry, saved) in loaded {
+        let ActiveGame { user_id, game_code } = &entry;
+        match saved {
+            Ok(Some(saved)) if saved.last_played_ms().is_some_and(|ms| ms >= oldest) => {
+                let mut games = app_state.games.lock().await;
+                if games.contains_key(game_code) {
+                    warn!("Not restoring game {game_code} for user {user_id}: code in use");
+                    stale.push(entry);
+                    continue;
+                }
+                info!(
+                    "Restoring active game {game_code} for user {user_id}, replaying {} events",
+                    saved.events.len()
+                );
+                let game = Game::restore(user_id.clone(), game_code.clone(), None, saved);
+                games.insert(game_code.clone(), Arc::new(Mutex::new(game)));
+            }
+            Ok(_) => stale.push(entry),
+            // Left in the index, in case storage recovers before the next start
+            Err(e) => warn!("Failed to restore active game {game_code}: {e}"),
+        }
+    }
+
+    if !stale.is_empty() {
+        info!(
+            "Dropping {} idle games from the active games index",
+            stale.len()
+        );
+        if let Err(e) = app_state.persistence.remove_active_games(&stale).await {
+            warn!("Failed to update the active games index: {e}");
+        }
+    }
+}
+
+/// Tell every client the server is restarting, save every game, then close the sockets
+async fn drain_games(app_state: &Arc<AppState>) {
+    let games = app_state.all_games().await;
+    info!("Saving {} games before shutdown", games.len());
+
+    join_all(games.into_iter().map(|game| async move {
+        let game_code = {
+            let game = game.lock().await;
+            game.broadcast_to_all(ServerMessage::ServerRestarting {
+                message: RESTARTING_MESSAGE.to_string(),
+            });
+            game.game_code.clone()
+        };
+
+        match app_state.autosave.save_now(&game).await {
+            Ok(()) => info!("Saved game {game_code} for shutdown"),
+            Err(e) => error!("Failed to save game {game_code} for shutdown: {e}"),
+        }
+
+        game.lock().await.close_all_connections("Server restarting");
+    }))
+    .await;
 }
@@ -1,12 +1,19 @@
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use backend::auth::{self, TEST_CLIENT_ID, TEST_ISSUER};
-use backend::model::client_message::{ClientMessage, HostAction, TeamAction, WatcherAction};
+use axum::{Json, Router, routing::get};
+use backend::auth::{self, JwtValidator, TEST_CLIENT_ID, TEST_ISSUER};
+use backend::limits::Limits;
+use backend::model::client_message::{
+    ClientKind, ClientMessage, HostAction, TeamAction, WatcherAction,
+};
+use backend::model::patch::{self, PatchOp};
+use backend::model::protocol::{Encoding, PROTOCOL_VERSION};
 use backend::model::server_message::ServerMessage;
 use backend::model::types::{McConfig, McOptionType};
-use backend::persistence::PersistenceClient;
-use backend::server::start_ws_server;
+use backend::persistence::{GameStore, PersistenceClient};
+use backend::server::{SUBPROTOCOL, TOKEN_SUBPROTOCOL_PREFIX, start_ws_server};
 use backend::timer::ShutdownTimer;
 use futures_util::{
     SinkExt, StreamExt,
@@ -15,15 +22,68 @@ use futures_util::{
 use jsonwebtoken::{Algorithm, EncodingKey, Header};
 use serde::{Serialize, de::DeserializeOwned};
 use serde_json::json;
-use tokio::{net::TcpListener, sync::mpsc};
-use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite::Message};
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, watch},
+};
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream, connect_async,
+    tungstenite::{
+        Message,
+        client::IntoClientRequest,
+        http::{HeaderValue, header::SEC_WEBSOCKET_PROTOCOL},
+        protocol::{CloseFrame, frame::coding::CloseCode},
+    },
+};
 
 mod integ;
 
+/// Records the server's log lines, from every test, for tests that check what gets logged.
+/// Dependencies' logs (like websocket frame traces) are left out.
+struct LogCapture(Mutex<Vec<String>>);
+
+impl log::Log for LogCapture {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target().starts_with("backend")
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOG_CAPTURE: OnceLock<&'static LogCapture> = OnceLock::new();
+
+/// Start capturing logs, if not already capturing
+pub fn capture_logs() {
+    LOG_CAPTURE.get_or_init(|| {
+        let capture = Box::leak(Box::new(LogCapture(Mutex::new(Vec::new()))));
+        log::set_logger(capture).expect("No other logger is installed in tests");
+        log::set_max_level(log::LevelFilter::Trace);
+        capture
+    });
+}
+
+/// Everything logged since `capture_logs` was first called
+pub fn captured_logs() -> Vec<String> {
+    LOG_CAPTURE
+        .get()
+        .expect("capture_logs wasn't called")
+        .0
+        .lock()
+        .unwrap()
+        .clone()
+}
+
 pub struct TestServer {
     pub ws_port: u16,
     _shutdown_tx: mpsc::Sender<()>,
     pub shutdown_rx: mpsc::Receiver<()>,
+    stop_tx: watch::Sender<bool>,
 }
 
 impl TestServer {
@@ -32,6 +92,50 @@ impl TestServer {
     }
 
     pub async fn start_with_shutdown_duration(shutdown_duration: Duration) -> Self {
+        Self::start_with(shutdown_duration, Limits::default(), None, None, false).await
+    }
+
+    pub async fn start_with_limits(limits: Limits) -> Self {
+        Self::start_with(Duration::from_secs(2), limits, None, None, false).await
+    }
+
+    /// Start a server that saves to and restores from `store`. Starting a second server on
+    /// the same store simulates a restart.
+    pub async fn start_with_store(store: Arc<dyn GameStore>) -> Self {
+        Self::start_with(
+            Duration::from_secs(2),
+            Limits::default(),
+            Some(store),
+            None,
+            false,
+        )
+        .await
+    }
+
+    /// Start a server that checks tokens with `validator` instead of the test key
+    pub async fn start_with_validator(validator: Arc<dyn JwtValidator>) -> Self {
+        Self::start_with(
+            Duration::from_secs(2),
+            Limits::default(),
+            None,
+            Some(validator),
+            false,
+        )
+        .await
+    }
+
+    /// Start a server that accepts tokens in the URL query, like one with ALLOW_QUERY_TOKEN
+    pub async fn start_with_query_tokens() -> Self {
+        Self::start_with(Duration::from_secs(2), Limits::default(), None, None, true).await
+    }
+
+    async fn start_with(
+        shutdown_duration: Duration,
+        limits: Limits,
+        store: Option<Arc<dyn GameStore>>,
+        validator: Option<Arc<dyn JwtValidator>>,
+        allow_query_token: bool,
+    ) -> Self {
         dotenvy::dotenv().expect("Couldn't load .env for tests");
         let ws_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
         let ws_port = ws_listener.local_addr().unwrap().port();
@@ -39,10 +143,24 @@ impl TestServer {
         let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
 
         let timer = ShutdownTimer::new(shutdown_tx.clone(), shutdown_duration);
-        let validator = Arc::new(auth::TestValidator::with_test_keys());
-        let persistence = Arc::new(PersistenceClient::new().await);
+        let validator =
+            validator.unwrap_or_else(|| Arc::new(auth::TestValidator::with_test_keys()));
+        let persistence = Arc::new(match store {
+            Some(store) => PersistenceClient::with_store(store),
+            None => PersistenceClient::new().await,
+        });
+        let (stop_tx, stop_rx) = watch::channel(false);
         tokio::spawn(async move {
-            start_ws_server(ws_listener, timer, validator, persistence).await;
+            start_ws_server(
+                ws_listener,
+                timer,
+                validator,
+                persistence,
+                limits,
+                allow_query_token,
+                stop_rx,
+            )
+            .await;
         });
 
         // Give the server a moment to start
@@ -52,19 +170,89 @@ impl TestServer {
             ws_port,
             _shutdown_tx: shutdown_tx,
             shutdown_rx,
+            stop_tx,
         }
     }
 
+    /// Begin a graceful shutdown, as on SIGTERM
+    pub fn shutdown(&self) {
+        self.stop_tx.send(true).unwrap();
+    }
+
     pub fn ws_url(&self) -> String {
         format!("ws://127.0.0.1:{}", self.ws_port)
     }
 }
 
+/// A local stand-in for Cognito's JWKS endpoint, serving the test public key under
+/// whichever key IDs a test chooses
+pub struct JwksServer {
+    pub url: String,
+    kids: Arc<std::sync::Mutex<Vec<String>>>,
+    fetches: Arc<AtomicUsize>,
+}
+
+impl JwksServer {
+    pub async fn start(kids: &[&str]) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}/.well-known/jwks.json",
+            listener.local_addr().unwrap()
+        );
+        let server = JwksServer {
+            url,
+            kids: Arc::new(std::sync::Mutex::new(Vec::new())),
+            fetches: Arc::new(AtomicUsize::new(0)),
+        };
+        server.set_kids(kids);
+
+        let (kids, fetches) = (server.kids.clone(), server.fetches.clone());
+        let app = Router::new().route(
+            "/.well-known/jwks.json",
+            get(move || async move {
+                fetches.fetch_add(1, Ordering::SeqCst);
+                let keys: Vec<_> = kids
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|kid| {
+                        let mut jwk: serde_json::Value =
+                            serde_json::from_str(TEST_PUBLIC_JWK).unwrap();
+                        jwk["kid"] = json!(kid);
+                        jwk
+                    })
+                    .collect();
+                Json(json!({ "keys": keys }))
+            }),
+        );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        server
+    }
+
+    /// Serve the test key under these key IDs from now on
+    pub fn set_kids(&self, kids: &[&str]) {
+        *self.kids.lock().unwrap() = kids.iter().map(|kid| kid.to_string()).collect();
+    }
+
+    /// How many times the key set has been requested
+    pub fn fetches(&self) -> usize {
+        self.fetches.load(Ordering::SeqCst)
+    }
+}
+
 type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
 
 pub struct TestClient {
     write: SplitSink<WsStream, Message>,
     read: SplitStream<WsStream>,
+    // Last state snapshot and its sequence number, kept up to date by applying patches
+    state: Option<(u64, serde_json::Value)>,
+    // Whether the opening message has been sent, which decides the protocol version
+    opened: bool,
+    // Encoding requested in the automatic Hello and used for sent actions
+    encoding: Encoding,
+    // Token sent in the automatic Hello
+    hello_token: Option<String>,
 }
 
 impl TestClient {
@@ -72,34 +260,194 @@ impl TestClient {
         Self::connect_with_token(url, None).await
     }
 
+    /// Connect with the token offered as a `Sec-WebSocket-Protocol` value, as browsers do
     pub async fn connect_with_token(url: &str, token: Option<&str>) -> Self {
-        let url_with_token = if let Some(token) = token {
-            let mut parsed_url = url::Url::parse(url).expect("Invalid URL");
-            parsed_url.query_pairs_mut().append_pair("token", token);
-            parsed_url.to_string()
-        } else {
-            url.to_string()
-        };
-        let (ws_stream, _) = connect_async(url_with_token)
-            .await
-            .expect("Failed to connect");
+        let mut request = url.into_client_request().expect("Invalid URL");
+        if let Some(token) = token {
+            let protocols = format!("{SUBPROTOCOL}, {TOKEN_SUBPROTOCOL_PREFIX}{token}");
+            request.headers_mut().insert(
+                SEC_WEBSOCKET_PROTOCOL,
+                HeaderValue::from_str(&protocols).unwrap(),
+            );
+        }
+        let (ws_stream, response) = connect_async(request).await.expect("Failed to connect");
+        if token.is_some() {
+            assert_eq!(
+                response.headers().get(SEC_WEBSOCKET_PROTOCOL).unwrap(),
+                SUBPROTOCOL,
+                "The server should select the app subprotocol, never the token"
+            );
+        }
         let (write, read) = ws_stream.split();
-        Self { write, read }
+        Self {
+            write,
+            read,
+            state: None,
+            opened: false,
+            encoding: Encoding::Json,
+            hello_token: None,
+        }
+    }
+
+    /// Connect with the token in the URL query, the way older clients send it
+    pub async fn connect_with_query_token(url: &str, token: &str) -> Self {
+        let mut parsed_url = url::Url::parse(url).expect("Invalid URL");
+        parsed_url.query_pairs_mut().append_pair("token", token);
+        Self::connect(parsed_url.as_str()).await
+    }
+
+    /// Connect without a token, and send it in the automatic `Hello`
+    pub async fn connect_with_hello_token(url: &str, token: &str) -> Self {
+        let mut client = Self::connect(url).await;
+        client.hello_token = Some(token.to_string());
+        client
     }
 
+    /// Ask for `encoding` in the automatic `Hello` and send later actions in it.
+    /// Messages received in either encoding are read the same way.
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.encoding = encoding;
+    }
+
+    /// Send a message. The first one is preceded by a `Hello` on the current protocol
+    /// version, naming the client kind from the message.
     pub async fn send_json<T: Serialize>(&mut self, msg: &T) {
-        let json = serde_json::to_string(msg).unwrap();
-        self.write.send(Message::Text(json.into())).await.unwrap();
+        let json = serde_json::to_value(msg).unwrap();
+        if !self.opened {
+            let has_key = |key: &str| json.get(key).is_some();
+            let client_kind = if has_key("host") {
+                ClientKind::Host
+            } else if has_key("team") {
+                ClientKind::Team
+            } else {
+                ClientKind::Watcher
+            };
+            self.hello(PROTOCOL_VERSION, client_kind).await;
+            match self.recv_json().await {
+                ServerMessage::Welcome { .. } => {}
+                other => panic!("Expected Welcome message, got {other:?}"),
+            }
+        }
+        let frame = match self.encoding {
+            Encoding::Json => Message::Text(json.to_string().into()),
+            Encoding::MessagePack => {
+                Message::Binary(rmp_serde::to_vec_named(&json).unwrap().into())
+            }
+        };
+        self.write.send(frame).await.unwrap();
+    }
+
+    /// Open the connection with a `Hello`, leaving the reply to the caller
+    pub async fn hello(&mut self, protocol_version: u32, client_kind: ClientKind) {
+        self.send_raw_text(
+            &serde_json::to_string(&ClientMessage::Hello {
+                protocol_version,
+                client_kind,
+                encoding: self.encoding,
+                token: self.hello_token.clone().map(Into::into),
+            })
+            .unwrap(),
+        )
+        .await;
     }
 
+    /// Close the connection normally, as a client leaving on purpose does
+    pub async fn close(&mut self) {
+        let frame = CloseFrame {
+            code: CloseCode::Normal,
+            reason: "".into(),
+        };
+        self.write.send(Message::Close(Some(frame))).await.unwrap();
+    }
+
+    /// Send text as is. Opening a connection this way skips the handshake, so the
+    /// server treats the client as speaking the legacy protocol.
     pub async fn send_raw_text(&mut self, text: &str) {
+        self.opened = true;
         self.write
             .send(Message::Text(text.to_string().into()))
             .await
             .unwrap();
     }
 
+    /// Receive the next message. State patches are applied to the last snapshot, like a
+    /// real client would, and returned as the full `GameState`/`TeamGameState` they produce.
     pub async fn recv_json<T: DeserializeOwned>(&mut self) -> T {
+        let message = self.recv_tracked().await;
+        serde_json::from_value(self.expand_patch(message)).unwrap()
+    }
+
+    /// Receive the next message as sent, without expanding state patches
+    pub async fn recv_raw_json<T: DeserializeOwned>(&mut self) -> T {
+        let message = self.recv_tracked().await;
+        self.expand_patch(message.clone());
+        serde_json::from_value(message).unwrap()
+    }
+
+    async fn recv_tracked(&mut self) -> serde_json::Value {
+        // Timer events arrive alongside state updates; tests that expect them use recv_timer_event
+        let text = self.recv_text_matching(|text| !is_timer_event(text)).await;
+        serde_json::from_str(&text).unwrap()
+    }
+
+    /// Track snapshots and apply patches, returning patches as the snapshot they produce
+    fn expand_patch(&mut self, mut message: serde_json::Value) -> serde_json::Value {
+        let message_type = message["type"].as_str().unwrap_or_default().to_string();
+        match message_type.as_str() {
+            "gameState" | "teamGameState" => {
+                let seq = message["seq"].as_u64().unwrap_or_default();
+                self.state = Some((seq, message["state"].clone()));
+                message
+            }
+            "gameStatePatch" | "teamGameStatePatch" => {
+                let seq = message["seq"].as_u64().expect("Patch should have a seq");
+                let ops: Vec<PatchOp> = serde_json::from_value(message["ops"].take()).unwrap();
+                let (last_seq, state) = self
+                    .state
+                    .as_mut()
+                    .expect("Received a state patch before any snapshot");
+                assert_eq!(seq, *last_seq + 1, "Gap in state patch sequence");
+                patch::apply(state, &ops).expect("Failed to apply state patch");
+                *last_seq = seq;
+                json!({
+                    "type": message_type.trim_end_matches("Patch"),
+                    "seq": seq,
+                    "state": state.clone(),
+                })
+            }
+            _ => message,
+        }
+    }
+
+    /// The last state received, with any patches applied
+    pub fn last_state(&self) -> Option<&serde_json::Value> {
+        self.state.as_ref().map(|(_, state)| state)
+    }
+
+    /// Receive the next timer event, skipping any other messages
+    pub async fn recv_timer_event(&mut self) -> ServerMessage {
+        let text = self.recv_text_matching(is_timer_event).await;
+        serde_json::from_str(&text).unwrap()
+    }
+
+    /// Receive the next message as JSON text; MessagePack frames are converted
+    async fn recv_text_matching(&mut self, matches: impl Fn(&str) -> bool) -> String {
+        loop {
+            let text = match self.recv_frame().await {
+                Message::Text(text) => text.to_string(),
+                Message::Binary(bytes) => rmp_serde::from_slice::<serde_json::Value>(&bytes)
+                    .expect("Binary frames should be MessagePack")
+                    .to_string(),
+                _ => unreachable!("recv_frame only returns data frames"),
+            };
+            if matches(&text) {
+                return text;
+            }
+        }
+    }
+
+    /// Receive the next text or binary frame as sent
+    pub async fn recv_frame(&mut self) -> Message {
         let timeout_duration = Duration::from_secs(2);
         let deadline = tokio::time::Instant::now() + timeout_duration;
 
@@ -110,10 +458,8 @@ impl TestClient {
             }
 
             match tokio::time::timeout(remaining, self.read.next()).await {
-                Ok(Some(Ok(Message::Text(text)))) => {
-                    return serde_json::from_str(&text).unwrap();
-                }
-                Ok(Some(Ok(_))) => continue, // Skip Ping, Pong, Binary, Close
+                Ok(Some(Ok(frame @ (Message::Text(_) | Message::Binary(_))))) => return frame,
+                Ok(Some(Ok(_))) => continue, // Skip Ping, Pong, Close
                 Ok(Some(Err(e))) => panic!("WebSocket error: {e}"),
                 Ok(None) => panic!("WebSocket stream closed"),
                 Err(_) => panic!("Timeout waiting for message from server"),
@@ -121,17 +467,30 @@ impl TestClient {
         }
     }
 
+    /// Wait for the server to close the connection, skipping any messages still in flight
+    pub async fn assert_closed(&mut self) {
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+        loop {
+            match tokio::time::timeout_at(deadline, self.read.next()).await {
+                Ok(Some(Ok(Message::Close(_)))) | Ok(Some(Err(_))) | Ok(None) => return,
+                Ok(Some(Ok(_))) => continue,
+                Err(_) => panic!("Timeout waiting for the server to close the connection"),
+            }
+        }
+    }
+
     /// Send CreateGame and return the game code
     /// Note: Requires the client to have connected with a valid host token
     pub async fn create_game(&mut self) -> String {
         self.send_json(&ClientMessage::Host(HostAction::CreateGame {
             game_code: None,
+            copy_settings_from: None,
         }))
         .await;
 
         let response: ServerMessage = self.recv_json().await;
         match response {
-            ServerMessage::GameState { state } => state.game_code,
+            ServerMessage::GameState { state, .. } => state.game_code,
             other => panic!("Expected GameState message, got {other:?}"),
         }
     }
@@ -181,7 +540,7 @@ impl TestClient {
 
         let response: ServerMessage = self.recv_json().await;
         match response {
-            ServerMessage::TeamGameState { state } => {
+            ServerMessage::TeamGameState { state, .. } => {
                 assert_eq!(state.game_code, game_code, "Game codes should match");
             }
             other => panic!("Expected TeamGameState message, got {other:?}"),
@@ -190,19 +549,41 @@ impl TestClient {
 }
 
 const TEST_PRIVATE_KEY: &str = include_str!("./keys/test_private.pem");
+const TEST_PUBLIC_JWK: &str = include_str!("./keys/test_public.jwk.json");
 
 /// Generate a test JWT for authentication tests
 pub fn create_test_jwt(user_id: &str, groups: &[&str], expired: bool) -> String {
+    create_test_jwt_with_kid(user_id, groups, expired, None)
+}
+
+/// Generate a test JWT whose header names the signing key, as Cognito's do
+pub fn create_test_jwt_with_kid(
+    user_id: &str,
+    groups: &[&str],
+    expired: bool,
+    kid: Option<&str>,
+) -> String {
     let exp = if expired {
         0 // Expired in the past
     } else {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
-            + 3600 // 1 hour from now
+        now_secs() + 3600 // 1 hour from now
     };
+    sign_test_jwt(user_id, groups, exp, kid)
+}
 
+/// Generate a host token that expires `secs` from now
+pub fn create_host_token_expiring_in(user_id: &str, secs: u64) -> String {
+    sign_test_jwt(user_id, &["Trivia-Hosts"], now_secs() + secs, None)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn sign_test_jwt(user_id: &str, groups: &[&str], exp: u64, kid: Option<&str>) -> String {
     let claims = json!({
         "sub": user_id,
         "cognito:groups": groups,
@@ -215,8 +596,9 @@ pub fn create_test_jwt(user_id: &str, groups: &[&str], expired: bool) -> String
     let encoding_key = EncodingKey::from_rsa_pem(TEST_PRIVATE_KEY.as_bytes())
         .expect("Test private key should be valid");
 
-    jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &encoding_key)
-        .expect("JWT encoding should succeed")
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = kid.map(str::to_string);
+    jsonwebtoken::encode(&header, &claims, &encoding_key).expect("JWT encoding should succeed")
 }
 
 /// Generate a valid host token (user in Trivia-Hosts group)
@@ -238,6 +620,7 @@ pub fn create_expired_token() -> String {
 /// - Team submits answer
 /// - Team receives TeamGameState confirmation
 /// - Host receives GameState with the answer
+///
 /// Note: Requires timer to be running (submissions open)
 pub async fn assert_answer_submission_flow(
     team: &mut TestClient,
@@ -264,7 +647,7 @@ pub async fn assert_answer_submission_flow(
     // Host should receive the updated game state
     let host_response: ServerMessage = host.recv_json().await;
     match host_response {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             // Verify the answer was added to the current question
             let question = state
                 .questions
@@ -289,3 +672,8 @@ pub fn default_mc_config() -> McConfig {
         custom_options: None,
     }
 }
+
+fn is_timer_event(text: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(text)
+        .is_ok_and(|value| matches!(value["type"].as_str(), Some("timerEvent" | "timerTick")))
+}
@@ -1,7 +1,8 @@
 use crate::{TestClient, TestServer, assert_answer_submission_flow};
 
-use backend::model::client_message::{ClientMessage, HostAction};
-use backend::model::server_message::ServerMessage;
+use backend::model::client_message::{ClientMessage, HostAction, TeamAction};
+use backend::model::server_message::{GameState, ServerMessage};
+use backend::model::types::ScoreData;
 
 #[tokio::test]
 async fn team_submits_answer_host_receives_it() {
@@ -21,3 +22,133 @@ async fn team_submits_answer_host_receives_it() {
 
     assert_answer_submission_flow(&mut team, &mut host, "Test Team", "42").await;
 }
+
+/// Start the timer and immediately shorten it so it expires within a second.
+/// Consumes the resulting state broadcasts, including the one sent on expiry.
+async fn run_timer_to_expiry(host: &mut TestClient, teams: &mut [&mut TestClient]) {
+    host.send_json(&ClientMessage::Host(HostAction::StartTimer))
+        .await;
+    let _: ServerMessage = host.recv_json().await;
+    for team in teams.iter_mut() {
+        let _: ServerMessage = team.recv_json().await;
+    }
+
+    host.send_json(&ClientMessage::Host(HostAction::AdjustTimer {
+        delta_seconds: -29,
+    }))
+    .await;
+    // Adjust broadcast, then expiry broadcast
+    for _ in 0..2 {
+        let _: ServerMessage = host.recv_json().await;
+        for team in teams.iter_mut() {
+            let _: ServerMessage = team.recv_json().await;
+        }
+    }
+}
+
+async fn submit(team: &mut TestClient, host: &mut TestClient, team_name: &str, answer: &str) {
+    team.send_json(&ClientMessage::Team(TeamAction::SubmitAnswer {
+        team_name: team_name.to_string(),
+        answer: answer.to_string(),
+    }))
+    .await;
+    let _: ServerMessage = team.recv_json().await;
+    let _: ServerMessage = host.recv_json().await;
+}
+
+async fn resolve_late_answer(host: &mut TestClient, team_name: &str, accept: bool) -> GameState {
+    host.send_json(&ClientMessage::Host(HostAction::ResolveLateAnswer {
+        question_number: 1,
+        team_name: team_name.to_string(),
+        accept,
+    }))
+    .await;
+    match host.recv_json::<ServerMessage>().await {
+        ServerMessage::GameState { state, .. } => state,
+        other => panic!("Expected GameState, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn accepted_late_answer_is_auto_scored() {
+    let server = TestServer::start().await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    let mut team1 = TestClient::connect(&server.ws_url()).await;
+    team1.join_game(&game_code, "Team1").await;
+    let _: ServerMessage = host.recv_json().await;
+    let mut team2 = TestClient::connect(&server.ws_url()).await;
+    team2.join_game(&game_code, "Team2").await;
+    let _: ServerMessage = host.recv_json().await;
+
+    host.send_json(&ClientMessage::Host(HostAction::StartTimer))
+        .await;
+    let _: ServerMessage = host.recv_json().await;
+    let _: ServerMessage = team1.recv_json().await;
+    let _: ServerMessage = team2.recv_json().await;
+    submit(&mut team1, &mut host, "Team1", "Steve").await;
+
+    // Restarting a running timer keeps its remaining time
+    run_timer_to_expiry(&mut host, &mut [&mut team1, &mut team2]).await;
+    submit(&mut team2, &mut host, "Team2", "steve").await;
+
+    host.send_json(&ClientMessage::Host(HostAction::ScoreAnswer {
+        question_number: 1,
+        team_name: "Team1".to_string(),
+        score: ScoreData {
+            question_points: 50,
+            bonus_points: 0,
+            override_points: 0,
+            speed_bonus_points: 0,
+        },
+    }))
+    .await;
+    match host.recv_json::<ServerMessage>().await {
+        ServerMessage::GameState { state, .. } => {
+            let team2_answer = &state.questions[0].answers[1];
+            assert!(team2_answer.late);
+            assert_eq!(
+                team2_answer.score.question_points, 0,
+                "Late answers are not auto-scored until accepted"
+            );
+        }
+        other => panic!("Expected GameState, got {other:?}"),
+    }
+    // Team2's state is unchanged, so only Team1 gets an update
+    let _: ServerMessage = team1.recv_json().await;
+
+    let state = resolve_late_answer(&mut host, "Team2", true).await;
+    let team2_answer = &state.questions[0].answers[1];
+    assert!(!team2_answer.late);
+    assert_eq!(team2_answer.score.question_points, 50);
+    let team2 = state.teams.iter().find(|t| t.team_name == "Team2").unwrap();
+    assert_eq!(team2.score.question_points, 50);
+}
+
+#[tokio::test]
+async fn rejected_late_answer_is_removed() {
+    let server = TestServer::start().await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Test Team").await;
+    let _: ServerMessage = host.recv_json().await;
+
+    run_timer_to_expiry(&mut host, &mut [&mut team]).await;
+    submit(&mut team, &mut host, "Test Team", "42").await;
+
+    let state = resolve_late_answer(&mut host, "Test Team", false).await;
+    assert!(state.questions[0].answers.is_empty());
+
+    // Resolving again fails since there's no late answer left
+    host.send_json(&ClientMessage::Host(HostAction::ResolveLateAnswer {
+        question_number: 1,
+        team_name: "Test Team".to_string(),
+        accept: true,
+    }))
+    .await;
+    match host.recv_json::<ServerMessage>().await {
+        ServerMessage::Error { .. } => (),
+        other => panic!("Expected Error, got {other:?}"),
+    }
+}
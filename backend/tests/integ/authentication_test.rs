@@ -1,4 +1,6 @@
-use crate::{TestClient, TestServer, create_expired_token, create_non_host_token};
+use crate::{
+    TestClient, TestServer, create_expired_token, create_host_token, create_non_host_token,
+};
 
 use backend::model::client_message::{ClientMessage, HostAction};
 use backend::model::server_message::ServerMessage;
@@ -11,6 +13,7 @@ async fn host_without_token_cannot_create_game() {
     client
         .send_json(&ClientMessage::Host(HostAction::CreateGame {
             game_code: None,
+            copy_settings_from: None,
         }))
         .await;
 
@@ -35,6 +38,7 @@ async fn host_with_expired_token_cannot_create_game() {
     client
         .send_json(&ClientMessage::Host(HostAction::CreateGame {
             game_code: None,
+            copy_settings_from: None,
         }))
         .await;
 
@@ -59,6 +63,7 @@ async fn user_not_in_hosts_group_cannot_create_game() {
     client
         .send_json(&ClientMessage::Host(HostAction::CreateGame {
             game_code: None,
+            copy_settings_from: None,
         }))
         .await;
 
@@ -84,3 +89,56 @@ async fn team_can_join_without_token() {
     team.join_game(&game_code, "Unauthenticated Team").await;
     // If we get here without panic, the test passes
 }
+
+async fn assert_can_create_game(client: &mut TestClient) {
+    client
+        .send_json(&ClientMessage::Host(HostAction::CreateGame {
+            game_code: None,
+            copy_settings_from: None,
+        }))
+        .await;
+    let response: ServerMessage = client.recv_json().await;
+    assert!(
+        matches!(response, ServerMessage::GameState { .. }),
+        "Expected GameState, got {response:?}"
+    );
+}
+
+#[tokio::test]
+async fn host_with_token_in_hello_can_create_game() {
+    let server = TestServer::start().await;
+    let token = create_host_token();
+    let mut client = TestClient::connect_with_hello_token(&server.ws_url(), &token).await;
+    assert_can_create_game(&mut client).await;
+}
+
+#[tokio::test]
+async fn token_in_url_query_is_ignored_by_default() {
+    let server = TestServer::start().await;
+    let token = create_host_token();
+    let mut client = TestClient::connect_with_query_token(&server.ws_url(), &token).await;
+
+    client
+        .send_json(&ClientMessage::Host(HostAction::CreateGame {
+            game_code: None,
+            copy_settings_from: None,
+        }))
+        .await;
+    match client.recv_json().await {
+        ServerMessage::Error { message, .. } => {
+            assert!(
+                message.contains("Authentication required"),
+                "Error should mention authentication required, got: {message}"
+            );
+        }
+        other => panic!("Expected Error message, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn token_in_url_query_is_accepted_when_allowed() {
+    let server = TestServer::start_with_query_tokens().await;
+    let token = create_host_token();
+    let mut client = TestClient::connect_with_query_token(&server.ws_url(), &token).await;
+    assert_can_create_game(&mut client).await;
+}
@@ -1,8 +1,8 @@
-use crate::{TestClient, TestServer};
+use crate::{TestClient, TestServer, default_mc_config};
 
 use backend::model::client_message::{ClientMessage, HostAction, TeamAction};
-use backend::model::server_message::ServerMessage;
-use backend::model::types::ScoreData;
+use backend::model::server_message::{GameState, ServerMessage};
+use backend::model::types::{QuestionKind, ScoreData, SpeedBonusMode};
 
 /// Helper to set up a game with host and multiple teams, timer NOT started yet
 async fn setup_game_with_teams(
@@ -74,7 +74,7 @@ async fn score_answer(
     team_name: &str,
     question_points: i32,
     bonus_points: i32,
-) -> backend::model::server_message::GameState {
+) -> GameState {
     host.send_json(&ClientMessage::Host(HostAction::ScoreAnswer {
         question_number,
         team_name: team_name.to_string(),
@@ -88,17 +88,44 @@ async fn score_answer(
     .await;
 
     // Host receives GameState
-    let host_response: ServerMessage = host.recv_json().await;
+    let state = match host.recv_json::<ServerMessage>().await {
+        ServerMessage::GameState { state, .. } => state,
+        other => panic!("Expected GameState, got {other:?}"),
+    };
 
-    // All teams receive TeamGameState
+    // Teams whose scores changed receive TeamGameState
     for team in teams.iter_mut() {
-        let _: ServerMessage = team.recv_json().await;
+        if team_score_changed(team, &state) {
+            let _: ServerMessage = team.recv_json().await;
+        }
     }
 
-    match host_response {
-        ServerMessage::GameState { state } => state,
-        other => panic!("Expected GameState, got {other:?}"),
+    state
+}
+
+/// Whether `state` has scores for the team that differ from what it last received
+fn team_score_changed(team: &TestClient, state: &GameState) -> bool {
+    let seen = team.last_state().expect("Team should have received state");
+    let team_name = seen["team"]["teamName"].as_str().unwrap();
+    let team_data = state
+        .teams
+        .iter()
+        .find(|t| t.team_name == team_name)
+        .unwrap();
+    if serde_json::to_value(&team_data.score).unwrap() != seen["team"]["score"] {
+        return true;
     }
+    state
+        .questions
+        .iter()
+        .zip(seen["questions"].as_array().unwrap())
+        .any(|(question, seen_question)| {
+            question
+                .answers
+                .iter()
+                .find(|a| a.team_name == team_name)
+                .is_some_and(|a| serde_json::to_value(&a.score).unwrap() != seen_question["score"])
+        })
 }
 
 #[tokio::test]
@@ -253,8 +280,13 @@ async fn new_submission_auto_scored_if_matches_existing_correct() {
 
     pause_timer(&mut host, &mut teams).await;
 
-    // Trigger another state update to verify (score Team1 again with same score)
-    let state = score_answer(&mut host, &mut teams, 1, "Team1", 50, 5).await;
+    // Fetch the current state to verify
+    host.send_json(&ClientMessage::Host(HostAction::Resync))
+        .await;
+    let state = match host.recv_json::<ServerMessage>().await {
+        ServerMessage::GameState { state, .. } => state,
+        other => panic!("Expected GameState, got {other:?}"),
+    };
 
     // Team2 should have been auto-scored on submission (including bonus)
     let team2_answer = state.questions[0]
@@ -397,3 +429,99 @@ async fn already_scored_answers_sync_to_latest_score() {
         "Team2's bonus should sync to Team1's"
     );
 }
+
+/// Enable time-curve speed bonuses worth up to `first_place_points` on 30s questions
+async fn enable_time_curve(
+    host: &mut TestClient,
+    teams: &mut [TestClient],
+    first_place_points: u32,
+) {
+    host.send_json(&ClientMessage::Host(HostAction::UpdateGameSettings {
+        default_timer_duration: 30,
+        default_question_points: 50,
+        default_bonus_increment: 5,
+        default_question_type: QuestionKind::Standard,
+        default_mc_config: default_mc_config(),
+        speed_bonus_enabled: true,
+        speed_bonus_num_teams: 2,
+        speed_bonus_first_place_points: first_place_points,
+        late_grace_seconds: 3,
+        speed_bonus_mode: SpeedBonusMode::TimeCurve,
+        timer_warning_seconds: vec![10],
+    }))
+    .await;
+    let _: ServerMessage = host.recv_json().await;
+    for team in teams.iter_mut() {
+        let _: ServerMessage = team.recv_json().await;
+    }
+}
+
+#[tokio::test]
+async fn time_curve_speed_bonus_uses_answer_latency() {
+    let server = TestServer::start().await;
+    let (mut host, _game_code, mut teams) =
+        setup_game_with_teams(&server, &["Team1", "Team2"]).await;
+
+    // 10 points decaying over the 30s timer
+    enable_time_curve(&mut host, &mut teams, 10).await;
+
+    start_timer(&mut host, &mut teams).await;
+    submit_answer(&mut teams, &mut host, 0, "Team1", "Answer").await;
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    submit_answer(&mut teams, &mut host, 1, "Team2", "Other").await;
+    pause_timer(&mut host, &mut teams).await;
+
+    let _ = score_answer(&mut host, &mut teams, 1, "Team1", 50, 0).await;
+    let state = score_answer(&mut host, &mut teams, 1, "Team2", 50, 0).await;
+
+    let team1_answer = &state.questions[0].answers[0];
+    let team2_answer = &state.questions[0].answers[1];
+    let team1_latency = team1_answer.answer_latency_ms.expect("Latency recorded");
+    let team2_latency = team2_answer.answer_latency_ms.expect("Latency recorded");
+    assert!(team2_latency >= team1_latency + 1500);
+
+    // Team1 answered almost instantly; Team2 lost about half a point per 1.5s
+    assert!(team1_answer.score.speed_bonus_points >= 9);
+    assert_eq!(team2_answer.score.speed_bonus_points, 9);
+}
+
+#[tokio::test]
+async fn time_curve_speed_bonus_uses_adjusted_timer() {
+    let server = TestServer::start().await;
+    let (mut host, _game_code, mut teams) = setup_game_with_teams(&server, &["Team1"]).await;
+    enable_time_curve(&mut host, &mut teams, 100).await;
+
+    // Extending the 30s timer by 30s makes the bonus decay over 60s
+    start_timer(&mut host, &mut teams).await;
+    host.send_json(&ClientMessage::Host(HostAction::AdjustTimer {
+        delta_seconds: 30,
+    }))
+    .await;
+    let _: ServerMessage = host.recv_json().await;
+    let _: ServerMessage = teams[0].recv_json().await;
+
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    teams[0]
+        .send_json(&ClientMessage::Team(TeamAction::SubmitAnswer {
+            team_name: "Team1".to_string(),
+            answer: "Answer".to_string(),
+        }))
+        .await;
+    // Latency is for the host only
+    let team_state: serde_json::Value = teams[0].recv_json().await;
+    assert!(
+        team_state["state"]["questions"][0]
+            .get("answerLatencyMs")
+            .is_none()
+    );
+    let _: ServerMessage = host.recv_json().await;
+
+    let state = score_answer(&mut host, &mut teams, 1, "Team1", 50, 0).await;
+    let window_ms = state.questions[0].timer_window_ms.expect("Window recorded");
+    assert!(
+        (59_000..=60_000).contains(&window_ms),
+        "Window was {window_ms}ms"
+    );
+    // About 97 points after 1.5s of 60s; a 30s window would give at most 95
+    assert!(state.questions[0].answers[0].score.speed_bonus_points >= 96);
+}
@@ -1,7 +1,9 @@
-use crate::{TestClient, TestServer};
+use crate::{TestClient, TestServer, default_mc_config};
 
-use backend::model::client_message::{ClientMessage, HostAction};
-use backend::model::server_message::ServerMessage;
+use backend::model::client_message::{ClientMessage, HostAction, TeamAction};
+use backend::model::error::ErrorCode;
+use backend::model::server_message::{ServerMessage, TimerEventKind};
+use backend::model::types::{QuestionKind, SpeedBonusMode};
 
 #[tokio::test]
 async fn timer_start_opens_submissions_and_broadcasts_state() {
@@ -14,7 +16,7 @@ async fn timer_start_opens_submissions_and_broadcasts_state() {
 
     let response: ServerMessage = host.recv_json().await;
     match response {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             assert!(state.timer_running, "Timer should be running");
             assert!(
                 state.timer_seconds_remaining.is_some(),
@@ -41,7 +43,7 @@ async fn timer_pause_closes_submissions() {
 
     let response: ServerMessage = host.recv_json().await;
     match response {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             assert!(!state.timer_running, "Timer should not be running");
             assert!(
                 state.timer_seconds_remaining.is_some(),
@@ -68,7 +70,7 @@ async fn timer_reset_stops_timer_and_resets_to_default() {
 
     let response: ServerMessage = host.recv_json().await;
     match response {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             assert!(!state.timer_running, "Timer should not be running");
             assert_eq!(
                 state.timer_seconds_remaining,
@@ -81,7 +83,7 @@ async fn timer_reset_stops_timer_and_resets_to_default() {
 }
 
 #[tokio::test]
-async fn timer_ticks_broadcast_to_all_clients() {
+async fn timer_start_sends_deadline_to_all_clients() {
     let server = TestServer::start().await;
     let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
 
@@ -95,66 +97,439 @@ async fn timer_ticks_broadcast_to_all_clients() {
     host.send_json(&ClientMessage::Host(HostAction::StartTimer))
         .await;
 
-    // Both should receive initial GameState
+    let host_deadline = match host.recv_json::<ServerMessage>().await {
+        ServerMessage::GameState { state, .. } => {
+            assert!(state.timer_running);
+            let deadline = state.timer_deadline_ms.expect("Deadline should be set");
+            assert!((29_000..=30_000).contains(&(deadline - state.server_time_ms)));
+            deadline
+        }
+        other => panic!("Host expected GameState, got {other:?}"),
+    };
+
+    match team.recv_json::<ServerMessage>().await {
+        ServerMessage::TeamGameState { state, .. } => {
+            assert!(state.timer_running);
+            assert_eq!(state.timer_deadline_ms, Some(host_deadline));
+        }
+        other => panic!("Team expected TeamGameState, got {other:?}"),
+    }
+
+    // Clients count down locally, so no per-second messages are sent
+    let timeout_result = tokio::time::timeout(
+        std::time::Duration::from_millis(1500),
+        host.recv_json::<ServerMessage>(),
+    )
+    .await;
+    assert!(timeout_result.is_err(), "Should not receive timer ticks");
+}
+
+#[tokio::test]
+async fn timer_pause_freezes_remaining_time() {
+    let server = TestServer::start().await;
+    let (mut host, _) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    host.send_json(&ClientMessage::Host(HostAction::StartTimer))
+        .await;
+    let _: ServerMessage = host.recv_json().await; // consume initial GameState
+
+    tokio::time::sleep(std::time::Duration::from_millis(1200)).await;
+
+    host.send_json(&ClientMessage::Host(HostAction::PauseTimer))
+        .await;
+    match host.recv_json::<ServerMessage>().await {
+        ServerMessage::GameState { state, .. } => {
+            assert!(!state.timer_running);
+            assert_eq!(state.timer_deadline_ms, None);
+            assert_eq!(state.timer_seconds_remaining, Some(29));
+        }
+        other => panic!("Expected GameState, got {other:?}"),
+    }
+
+    // Resuming picks up from the frozen value
+    host.send_json(&ClientMessage::Host(HostAction::StartTimer))
+        .await;
+    match host.recv_json::<ServerMessage>().await {
+        ServerMessage::GameState { state, .. } => {
+            assert!(state.timer_running);
+            assert_eq!(state.timer_seconds_remaining, Some(29));
+        }
+        other => panic!("Expected GameState, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn timer_expiry_accepts_late_submissions_in_grace_window() {
+    let server = TestServer::start().await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Test Team").await;
+    let _: ServerMessage = host.recv_json().await; // team joined
+
+    host.send_json(&ClientMessage::Host(HostAction::StartTimer))
+        .await;
     let _: ServerMessage = host.recv_json().await;
     let _: ServerMessage = team.recv_json().await;
 
-    // Both should receive timer ticks (check first 2 ticks)
-    for expected_remaining in [29, 28] {
-        let host_tick: ServerMessage = host.recv_json().await;
-        let team_tick: ServerMessage = team.recv_json().await;
+    // Shorten the timer to 1 second
+    host.send_json(&ClientMessage::Host(HostAction::AdjustTimer {
+        delta_seconds: -29,
+    }))
+    .await;
+    let _: ServerMessage = host.recv_json().await;
+    let _: ServerMessage = team.recv_json().await;
 
-        match host_tick {
-            ServerMessage::TimerTick { seconds_remaining } => {
-                assert_eq!(seconds_remaining, expected_remaining);
-            }
-            other => panic!("Host expected TimerTick, got {other:?}"),
+    // Expiry broadcasts the closed state
+    match host.recv_json::<ServerMessage>().await {
+        ServerMessage::GameState { state, .. } => {
+            assert!(!state.timer_running);
+            assert_eq!(state.timer_seconds_remaining, Some(0));
         }
+        other => panic!("Expected GameState, got {other:?}"),
+    }
+    let _: ServerMessage = team.recv_json().await;
 
-        match team_tick {
-            ServerMessage::TimerTick { seconds_remaining } => {
-                assert_eq!(seconds_remaining, expected_remaining);
-            }
-            other => panic!("Team expected TimerTick, got {other:?}"),
+    // Default grace window stores the answer as late instead of rejecting it
+    team.send_json(&ClientMessage::Team(TeamAction::SubmitAnswer {
+        team_name: "Test Team".to_string(),
+        answer: "Just late".to_string(),
+    }))
+    .await;
+    // Only the host sees that it was late
+    let team_state: serde_json::Value = team.recv_json().await;
+    assert_eq!(team_state["type"], "teamGameState");
+    let answer = &team_state["state"]["questions"][0];
+    assert_eq!(answer["content"]["answerText"], "Just late");
+    assert!(answer.get("late").is_none());
+    match host.recv_json::<ServerMessage>().await {
+        ServerMessage::GameState { state, .. } => {
+            assert!(state.questions[0].answers[0].late);
         }
+        other => panic!("Expected GameState, got {other:?}"),
     }
+}
+
+#[tokio::test]
+async fn timer_expiry_rejects_submissions_after_grace_window() {
+    let server = TestServer::start().await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Test Team").await;
+    let _: ServerMessage = host.recv_json().await; // team joined
+
+    // Disable the grace window
+    host.send_json(&ClientMessage::Host(HostAction::UpdateGameSettings {
+        default_timer_duration: 1,
+        default_question_points: 50,
+        default_bonus_increment: 5,
+        default_question_type: QuestionKind::Standard,
+        default_mc_config: default_mc_config(),
+        speed_bonus_enabled: false,
+        speed_bonus_num_teams: 2,
+        speed_bonus_first_place_points: 10,
+        late_grace_seconds: 0,
+        speed_bonus_mode: SpeedBonusMode::Placement,
+        timer_warning_seconds: vec![],
+    }))
+    .await;
+    let _: ServerMessage = host.recv_json().await;
+    let _: ServerMessage = team.recv_json().await;
+
+    host.send_json(&ClientMessage::Host(HostAction::StartTimer))
+        .await;
+    let _: ServerMessage = host.recv_json().await;
+    let _: ServerMessage = team.recv_json().await;
 
-    // Note: Full timer expiration testing requires UpdateQuestionSettings to set shorter durations
+    // Wait for expiry
+    let _: ServerMessage = host.recv_json().await;
+    let _: ServerMessage = team.recv_json().await;
+
+    team.send_json(&ClientMessage::Team(TeamAction::SubmitAnswer {
+        team_name: "Test Team".to_string(),
+        answer: "Too late".to_string(),
+    }))
+    .await;
+    match team.recv_json::<ServerMessage>().await {
+        ServerMessage::Error { message, .. } => {
+            assert_eq!(message, "Submissions are closed");
+        }
+        other => panic!("Expected Error, got {other:?}"),
+    }
 }
 
 #[tokio::test]
-async fn timer_pause_prevents_further_ticks() {
+async fn timer_adjust_extends_running_timer_without_closing_submissions() {
     let server = TestServer::start().await;
     let (mut host, _) = TestClient::connect_as_host_and_create_game(&server).await;
 
     // Start timer (uses question's default timer_duration of 30s)
+    host.send_json(&ClientMessage::Host(HostAction::StartTimer))
+        .await;
+    let start_deadline = match host.recv_json::<ServerMessage>().await {
+        ServerMessage::GameState { state, .. } => state.timer_deadline_ms.unwrap(),
+        other => panic!("Expected GameState, got {other:?}"),
+    };
+
+    // Add 15 seconds
+    host.send_json(&ClientMessage::Host(HostAction::AdjustTimer {
+        delta_seconds: 15,
+    }))
+    .await;
+
+    match host.recv_json::<ServerMessage>().await {
+        ServerMessage::GameState { state, .. } => {
+            assert!(state.timer_running, "Timer should still be running");
+            assert_eq!(state.timer_seconds_remaining, Some(45));
+            assert_eq!(state.timer_deadline_ms, Some(start_deadline + 15_000));
+        }
+        other => panic!("Expected GameState, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn timer_adjust_clamps_at_zero_and_closes_submissions() {
+    let server = TestServer::start().await;
+    let (mut host, _) = TestClient::connect_as_host_and_create_game(&server).await;
+
     host.send_json(&ClientMessage::Host(HostAction::StartTimer))
         .await;
     let _: ServerMessage = host.recv_json().await; // consume initial GameState
 
-    // Wait for one tick
-    let tick: ServerMessage = host.recv_json().await;
-    match tick {
-        ServerMessage::TimerTick { seconds_remaining } => {
-            assert_eq!(seconds_remaining, 29);
+    // Subtract more time than remains
+    host.send_json(&ClientMessage::Host(HostAction::AdjustTimer {
+        delta_seconds: -100,
+    }))
+    .await;
+
+    match host.recv_json::<ServerMessage>().await {
+        ServerMessage::GameState { state, .. } => {
+            assert!(!state.timer_running, "Timer should have expired");
+            assert_eq!(state.timer_seconds_remaining, Some(0));
+            assert_eq!(state.timer_deadline_ms, None);
+        }
+        other => panic!("Expected GameState, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn timer_adjust_rejected_when_not_running() {
+    let server = TestServer::start().await;
+    let (mut host, _) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    host.send_json(&ClientMessage::Host(HostAction::AdjustTimer {
+        delta_seconds: 15,
+    }))
+    .await;
+
+    match host.recv_json::<ServerMessage>().await {
+        ServerMessage::Error { code, state, .. } => {
+            assert_eq!(code, ErrorCode::UnexpectedAction);
+            let state = state.expect("Error should carry state for rollback");
+            assert!(!state.timer_running);
+            assert_eq!(state.timer_seconds_remaining, Some(30));
+        }
+        other => panic!("Expected Error, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn clock_sync_echoes_client_time() {
+    let server = TestServer::start().await;
+    let (mut host, _) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    host.send_json(&ClientMessage::Host(HostAction::SyncClock {
+        client_time_ms: 1234,
+    }))
+    .await;
+
+    match host.recv_json::<ServerMessage>().await {
+        ServerMessage::ClockSync {
+            client_time_ms,
+            server_time_ms,
+        } => {
+            assert_eq!(client_time_ms, 1234);
+            assert!(server_time_ms > 0);
+        }
+        other => panic!("Expected ClockSync, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn timer_events_reach_host_teams_and_watchers() {
+    let server = TestServer::start().await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Test Team").await;
+    let mut watcher = TestClient::connect(&server.ws_url()).await;
+    watcher.watch_game(&game_code).await;
+
+    // 2 second timer with a warning at 1 second remaining
+    host.send_json(&ClientMessage::Host(HostAction::UpdateGameSettings {
+        default_timer_duration: 2,
+        default_question_points: 50,
+        default_bonus_increment: 5,
+        default_question_type: QuestionKind::Standard,
+        default_mc_config: default_mc_config(),
+        speed_bonus_enabled: false,
+        speed_bonus_num_teams: 2,
+        speed_bonus_first_place_points: 10,
+        late_grace_seconds: 3,
+        speed_bonus_mode: SpeedBonusMode::Placement,
+        timer_warning_seconds: vec![1],
+    }))
+    .await;
+
+    host.send_json(&ClientMessage::Host(HostAction::StartTimer))
+        .await;
+
+    for client in [&mut host, &mut team, &mut watcher] {
+        match client.recv_timer_event().await {
+            ServerMessage::TimerEvent {
+                event,
+                seconds_remaining,
+                deadline_ms,
+                ..
+            } => {
+                assert_eq!(event, TimerEventKind::Started);
+                assert_eq!(seconds_remaining, 2);
+                assert!(deadline_ms.is_some(), "Deadline should be set");
+            }
+            other => panic!("Expected TimerEvent, got {other:?}"),
         }
-        other => panic!("Expected TimerTick, got {other:?}"),
     }
 
-    // Pause the timer
+    for client in [&mut host, &mut team, &mut watcher] {
+        match client.recv_timer_event().await {
+            ServerMessage::TimerEvent {
+                event,
+                seconds_remaining,
+                ..
+            } => {
+                assert_eq!(
+                    event,
+                    TimerEventKind::Warning {
+                        threshold_seconds: 1
+                    }
+                );
+                assert_eq!(seconds_remaining, 1);
+            }
+            other => panic!("Expected TimerEvent, got {other:?}"),
+        }
+    }
+
+    for client in [&mut host, &mut team, &mut watcher] {
+        match client.recv_timer_event().await {
+            ServerMessage::TimerEvent {
+                event,
+                seconds_remaining,
+                deadline_ms,
+                ..
+            } => {
+                assert_eq!(event, TimerEventKind::Expired);
+                assert_eq!(seconds_remaining, 0);
+                assert_eq!(deadline_ms, None);
+            }
+            other => panic!("Expected TimerEvent, got {other:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn timer_pause_resume_and_adjust_send_events() {
+    let server = TestServer::start().await;
+    let (mut host, _) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    let expect_event = |message: ServerMessage, expected: TimerEventKind| match message {
+        ServerMessage::TimerEvent { event, .. } => assert_eq!(event, expected),
+        other => panic!("Expected TimerEvent, got {other:?}"),
+    };
+
+    host.send_json(&ClientMessage::Host(HostAction::StartTimer))
+        .await;
+    expect_event(host.recv_timer_event().await, TimerEventKind::Started);
+
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
     host.send_json(&ClientMessage::Host(HostAction::PauseTimer))
         .await;
-    let _: ServerMessage = host.recv_json().await; // consume GameState from pause
+    expect_event(host.recv_timer_event().await, TimerEventKind::Paused);
 
-    // Wait a bit and verify no more ticks arrive
-    let timeout_result = tokio::time::timeout(
-        std::time::Duration::from_millis(1500),
-        host.recv_json::<ServerMessage>(),
-    )
+    host.send_json(&ClientMessage::Host(HostAction::StartTimer))
+        .await;
+    expect_event(host.recv_timer_event().await, TimerEventKind::Resumed);
+
+    host.send_json(&ClientMessage::Host(HostAction::AdjustTimer {
+        delta_seconds: 5,
+    }))
     .await;
+    expect_event(
+        host.recv_timer_event().await,
+        TimerEventKind::Adjusted { delta_seconds: 5 },
+    );
 
-    assert!(
-        timeout_result.is_err(),
-        "Should not receive any more messages after pause"
+    // Adjusting past zero expires the timer
+    host.send_json(&ClientMessage::Host(HostAction::AdjustTimer {
+        delta_seconds: -100,
+    }))
+    .await;
+    expect_event(
+        host.recv_timer_event().await,
+        TimerEventKind::Adjusted {
+            delta_seconds: -100,
+        },
     );
+    expect_event(host.recv_timer_event().await, TimerEventKind::Expired);
+}
+
+#[tokio::test]
+async fn timer_adjust_past_warning_sends_warning() {
+    let server = TestServer::start().await;
+    let (mut host, _) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    // 30 second timer with a warning at 20 seconds remaining
+    host.send_json(&ClientMessage::Host(HostAction::UpdateGameSettings {
+        default_timer_duration: 30,
+        default_question_points: 50,
+        default_bonus_increment: 5,
+        default_question_type: QuestionKind::Standard,
+        default_mc_config: default_mc_config(),
+        speed_bonus_enabled: false,
+        speed_bonus_num_teams: 2,
+        speed_bonus_first_place_points: 10,
+        late_grace_seconds: 3,
+        speed_bonus_mode: SpeedBonusMode::Placement,
+        timer_warning_seconds: vec![20],
+    }))
+    .await;
+
+    host.send_json(&ClientMessage::Host(HostAction::StartTimer))
+        .await;
+    match host.recv_timer_event().await {
+        ServerMessage::TimerEvent { event, .. } => assert_eq!(event, TimerEventKind::Started),
+        other => panic!("Expected TimerEvent, got {other:?}"),
+    }
+
+    // Skipping from 30 to 15 seconds crosses the warning without waiting for it
+    host.send_json(&ClientMessage::Host(HostAction::AdjustTimer {
+        delta_seconds: -15,
+    }))
+    .await;
+    match host.recv_timer_event().await {
+        ServerMessage::TimerEvent { event, .. } => {
+            assert_eq!(event, TimerEventKind::Adjusted { delta_seconds: -15 })
+        }
+        other => panic!("Expected TimerEvent, got {other:?}"),
+    }
+    match host.recv_timer_event().await {
+        ServerMessage::TimerEvent { event, .. } => assert_eq!(
+            event,
+            TimerEventKind::Warning {
+                threshold_seconds: 20
+            }
+        ),
+        other => panic!("Expected TimerEvent, got {other:?}"),
+    }
 }
@@ -14,7 +14,7 @@ async fn host_disconnects_and_reconnects_teams_remain() {
     // Host should receive GameState with the new team
     let host_update: ServerMessage = host.recv_json().await;
     match host_update {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             assert_eq!(state.teams.len(), 1, "Should have 1 team");
             assert_eq!(state.teams[0].team_name, "Test Team");
         }
@@ -32,11 +32,12 @@ async fn host_disconnects_and_reconnects_teams_remain() {
     let mut host = TestClient::connect_with_token(&server.ws_url(), Some(&token)).await;
     host.send_json(&ClientMessage::Host(HostAction::CreateGame {
         game_code: Some(game_code.clone()),
+        copy_settings_from: None,
     }))
     .await;
     let response: ServerMessage = host.recv_json().await;
     let reconnected_game_code = match response {
-        ServerMessage::GameState { state } => state.game_code,
+        ServerMessage::GameState { state, .. } => state.game_code,
         other => panic!("Didn't receive GameState when reclaiming game, got {other:?}"),
     };
 
@@ -60,6 +61,7 @@ async fn cannot_reclaim_game_with_active_host() {
     intruder
         .send_json(&ClientMessage::Host(HostAction::CreateGame {
             game_code: Some(game_code.clone()),
+            copy_settings_from: None,
         }))
         .await;
 
@@ -0,0 +1,104 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{JwksServer, TestClient, TestServer, create_test_jwt_with_kid};
+
+use backend::auth::{CognitoValidator, TEST_CLIENT_ID};
+use backend::jwks::{JWKS_REFETCH_COOLDOWN, JWKS_TTL, JwksCache};
+use backend::model::client_message::{ClientMessage, HostAction};
+use backend::model::server_message::ServerMessage;
+
+/// Start a server whose validator fetches keys from `jwks`, like production does from Cognito
+async fn start_server(jwks: &JwksServer, ttl: Duration, refetch_cooldown: Duration) -> TestServer {
+    let cache = JwksCache::new(jwks.url.clone(), ttl, refetch_cooldown);
+    let validator = CognitoValidator::with_jwks(
+        "us-east-1".to_string(),
+        "test-pool".to_string(),
+        TEST_CLIENT_ID.to_string(),
+        cache,
+    );
+    TestServer::start_with_validator(Arc::new(validator)).await
+}
+
+/// Connect as a host with a token signed under `kid`, and try to create a game
+async fn create_game_with_kid(server: &TestServer, kid: &str) -> ServerMessage {
+    let token = create_test_jwt_with_kid("test-host-user", &["Trivia-Hosts"], false, Some(kid));
+    let mut client = TestClient::connect_with_token(&server.ws_url(), Some(&token)).await;
+    client
+        .send_json(&ClientMessage::Host(HostAction::CreateGame {
+            game_code: None,
+            copy_settings_from: None,
+        }))
+        .await;
+    client.recv_json().await
+}
+
+fn assert_created(response: ServerMessage) {
+    assert!(
+        matches!(response, ServerMessage::GameState { .. }),
+        "Expected GameState, got {response:?}"
+    );
+}
+
+fn assert_rejected(response: ServerMessage) {
+    match response {
+        ServerMessage::Error { message, .. } => {
+            assert!(
+                message.contains("Authentication required"),
+                "Error should mention authentication required, got: {message}"
+            );
+        }
+        other => panic!("Expected Error message, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn keys_are_fetched_once_for_many_connections() {
+    let jwks = JwksServer::start(&["key-1"]).await;
+    let server = start_server(&jwks, JWKS_TTL, JWKS_REFETCH_COOLDOWN).await;
+
+    for _ in 0..3 {
+        assert_created(create_game_with_kid(&server, "key-1").await);
+    }
+    assert_eq!(jwks.fetches(), 1);
+}
+
+#[tokio::test]
+async fn unknown_kid_refetches_rotated_keys() {
+    let jwks = JwksServer::start(&["key-1"]).await;
+    let server = start_server(&jwks, JWKS_TTL, Duration::ZERO).await;
+    assert_created(create_game_with_kid(&server, "key-1").await);
+
+    jwks.set_kids(&["key-1", "key-2"]);
+    assert_created(create_game_with_kid(&server, "key-2").await);
+    assert_eq!(jwks.fetches(), 2);
+
+    // The rotated keys are cached too
+    assert_created(create_game_with_kid(&server, "key-2").await);
+    assert_eq!(jwks.fetches(), 2);
+}
+
+#[tokio::test]
+async fn unknown_kid_within_cooldown_is_rejected_without_refetching() {
+    let jwks = JwksServer::start(&["key-1"]).await;
+    let server = start_server(&jwks, JWKS_TTL, JWKS_REFETCH_COOLDOWN).await;
+    assert_created(create_game_with_kid(&server, "key-1").await);
+
+    jwks.set_kids(&["key-1", "key-2"]);
+    assert_rejected(create_game_with_kid(&server, "key-2").await);
+    assert_rejected(create_game_with_kid(&server, "made-up").await);
+    assert_eq!(jwks.fetches(), 1);
+}
+
+#[tokio::test]
+async fn stale_keys_are_used_while_refreshing() {
+    let jwks = JwksServer::start(&["key-1"]).await;
+    let server = start_server(&jwks, Duration::ZERO, JWKS_REFETCH_COOLDOWN).await;
+    assert_created(create_game_with_kid(&server, "key-1").await);
+
+    // The stale keys still validate, and trigger a refresh behind the scenes
+    jwks.set_kids(&[]);
+    assert_created(create_game_with_kid(&server, "key-1").await);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(jwks.fetches(), 2);
+}
@@ -0,0 +1,129 @@
+use crate::{TestClient, TestServer};
+
+use backend::limits::{Limits, RateLimit};
+use backend::model::client_message::{ClientEnvelope, ClientMessage, HostAction, TeamAction};
+use backend::model::error::ErrorCode;
+use backend::model::server_message::ServerMessage;
+
+/// Teams may send two messages, then get no more tokens for the rest of the test
+fn strict_team_limits(max_violations: u32) -> Limits {
+    Limits {
+        team_rate: RateLimit {
+            burst: 2,
+            per_second: 0.01,
+        },
+        max_violations,
+        ..Limits::default()
+    }
+}
+
+fn sync_clock(request_id: &str) -> ClientEnvelope {
+    ClientEnvelope {
+        message: ClientMessage::Team(TeamAction::SyncClock { client_time_ms: 1 }),
+        request_id: Some(request_id.to_string()),
+    }
+}
+
+#[tokio::test]
+async fn team_over_rate_limit_gets_typed_error() {
+    let server = TestServer::start_with_limits(strict_team_limits(10)).await;
+    let (_host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    // ValidateJoin and JoinGame happen before the team's limiter starts
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Test Team").await;
+
+    for i in 0..2 {
+        team.send_json(&sync_clock(&format!("sync-{i}"))).await;
+        match team.recv_json::<ServerMessage>().await {
+            ServerMessage::ClockSync { .. } => {}
+            other => panic!("Expected ClockSync message, got {other:?}"),
+        }
+        let _: ServerMessage = team.recv_json().await; // Ack
+    }
+
+    team.send_json(&sync_clock("sync-2")).await;
+    match team.recv_json::<ServerMessage>().await {
+        ServerMessage::Error {
+            code, request_id, ..
+        } => {
+            assert_eq!(code, ErrorCode::RateLimited);
+            assert_eq!(request_id.as_deref(), Some("sync-2"));
+        }
+        other => panic!("Expected Error message, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn team_that_keeps_spamming_is_disconnected() {
+    let server = TestServer::start_with_limits(strict_team_limits(3)).await;
+    let (_host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Test Team").await;
+
+    for i in 0..6 {
+        team.send_json(&sync_clock(&format!("sync-{i}"))).await;
+    }
+
+    team.assert_closed().await;
+}
+
+#[tokio::test]
+async fn oversized_message_closes_connection_with_typed_error() {
+    let server = TestServer::start_with_limits(Limits {
+        max_frame_bytes: 1024,
+        max_message_bytes: 1024,
+        ..Limits::default()
+    })
+    .await;
+    let (mut host, _) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    host.send_json(&ClientMessage::Host(HostAction::OverrideTeamScore {
+        team_name: "x".repeat(2048),
+        override_points: 0,
+    }))
+    .await;
+
+    match host.recv_json::<ServerMessage>().await {
+        ServerMessage::Error { code, .. } => assert_eq!(code, ErrorCode::MessageTooLarge),
+        other => panic!("Expected Error message, got {other:?}"),
+    }
+    host.assert_closed().await;
+}
+
+#[tokio::test]
+async fn answer_over_length_limit_is_rejected() {
+    let server = TestServer::start_with_limits(Limits {
+        max_standard_answer_chars: 10,
+        ..Limits::default()
+    })
+    .await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Test Team").await;
+    let _: ServerMessage = host.recv_json().await;
+
+    host.send_json(&ClientMessage::Host(HostAction::StartTimer))
+        .await;
+    let _: ServerMessage = host.recv_json().await;
+    let _: ServerMessage = team.recv_json().await;
+
+    team.send_json(&ClientMessage::Team(TeamAction::SubmitAnswer {
+        team_name: "Test Team".to_string(),
+        answer: "a much too long answer".to_string(),
+    }))
+    .await;
+
+    match team.recv_json::<ServerMessage>().await {
+        ServerMessage::Error {
+            code, team_state, ..
+        } => {
+            assert_eq!(code, ErrorCode::AnswerTooLong);
+            let team_state = team_state.expect("Error should carry the team's state");
+            assert!(team_state.questions[0].content.is_none());
+        }
+        other => panic!("Expected Error message, got {other:?}"),
+    }
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{TestClient, TestServer};
+
+use backend::local_auth::{LocalValidator, TokenMinter, TokenSettings};
+use backend::model::client_message::{ClientMessage, HostAction};
+use backend::model::server_message::ServerMessage;
+
+const SECRET: &[u8] = b"bar-trivia-secret";
+const TEST_PRIVATE_KEY: &str = include_str!("../keys/test_private.pem");
+const TEST_PUBLIC_KEY: &str = include_str!("../keys/test_public.pem");
+const VALID_FOR: Duration = Duration::from_secs(60 * 60);
+
+/// Connect with `token` and try to create a game
+async fn create_game_with_token(server: &TestServer, token: &str) -> ServerMessage {
+    let mut client = TestClient::connect_with_token(&server.ws_url(), Some(token)).await;
+    client
+        .send_json(&ClientMessage::Host(HostAction::CreateGame {
+            game_code: None,
+            copy_settings_from: None,
+        }))
+        .await;
+    client.recv_json().await
+}
+
+fn assert_error_contains(response: ServerMessage, expected: &str) {
+    match response {
+        ServerMessage::Error { message, .. } => {
+            assert!(
+                message.contains(expected),
+                "Error should mention {expected:?}, got: {message}"
+            );
+        }
+        other => panic!("Expected Error message, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn minted_token_can_host() {
+    let validator = LocalValidator::hs256(SECRET, TokenSettings::default());
+    let server = TestServer::start_with_validator(Arc::new(validator)).await;
+    let token = TokenMinter::hs256(SECRET, TokenSettings::default())
+        .mint_host_token("bar-owner", VALID_FOR)
+        .unwrap();
+
+    let response = create_game_with_token(&server, &token).await;
+    assert!(
+        matches!(response, ServerMessage::GameState { .. }),
+        "Expected GameState, got {response:?}"
+    );
+}
+
+#[tokio::test]
+async fn token_signed_with_another_secret_is_rejected() {
+    let validator = LocalValidator::hs256(SECRET, TokenSettings::default());
+    let server = TestServer::start_with_validator(Arc::new(validator)).await;
+    let token = TokenMinter::hs256(b"some-other-secret", TokenSettings::default())
+        .mint_host_token("bar-owner", VALID_FOR)
+        .unwrap();
+
+    let response = create_game_with_token(&server, &token).await;
+    assert_error_contains(response, "Authentication required");
+}
+
+#[tokio::test]
+async fn token_outside_host_group_is_not_a_host() {
+    let validator = LocalValidator::hs256(SECRET, TokenSettings::default());
+    let server = TestServer::start_with_validator(Arc::new(validator)).await;
+    let settings = TokenSettings {
+        host_group: "Regulars".to_string(),
+        ..TokenSettings::default()
+    };
+    let token = TokenMinter::hs256(SECRET, settings)
+        .mint_host_token("regular", VALID_FOR)
+        .unwrap();
+
+    let response = create_game_with_token(&server, &token).await;
+    assert_error_contains(response, "not authorized as a host");
+}
+
+#[tokio::test]
+async fn rsa_key_with_custom_host_claim() {
+    let settings = TokenSettings {
+        issuer: "the-local".to_string(),
+        host_claim: "roles".to_string(),
+        host_group: "quizmaster".to_string(),
+    };
+    let validator = LocalValidator::rs256(TEST_PUBLIC_KEY.as_bytes(), settings.clone()).unwrap();
+    let server = TestServer::start_with_validator(Arc::new(validator)).await;
+    let token = TokenMinter::rs256(TEST_PRIVATE_KEY.as_bytes(), settings)
+        .unwrap()
+        .mint_host_token("bar-owner", VALID_FOR)
+        .unwrap();
+
+    let response = create_game_with_token(&server, &token).await;
+    assert!(
+        matches!(response, ServerMessage::GameState { .. }),
+        "Expected GameState, got {response:?}"
+    );
+
+    // A token from the default issuer doesn't count
+    let token = TokenMinter::rs256(TEST_PRIVATE_KEY.as_bytes(), TokenSettings::default())
+        .unwrap()
+        .mint_host_token("bar-owner", VALID_FOR)
+        .unwrap();
+    let response = create_game_with_token(&server, &token).await;
+    assert_error_contains(response, "Authentication required");
+}
+
+#[test]
+fn token_lifetime_past_the_clock_is_rejected() {
+    let result = TokenMinter::hs256(SECRET, TokenSettings::default())
+        .mint_host_token("bar-owner", Duration::MAX);
+    assert!(result.is_err());
+}
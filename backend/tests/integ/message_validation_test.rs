@@ -1,6 +1,7 @@
 use crate::{TestClient, TestServer};
 
-use backend::model::client_message::{ClientMessage, HostAction, TeamAction};
+use backend::model::client_message::{ClientEnvelope, ClientMessage, HostAction, TeamAction};
+use backend::model::error::ErrorCode;
 use backend::model::server_message::ServerMessage;
 
 #[tokio::test]
@@ -64,6 +65,7 @@ async fn team_sends_unexpected_message_type() {
     // Now send an unexpected Host message
     team.send_json(&ClientMessage::Host(HostAction::CreateGame {
         game_code: None,
+        copy_settings_from: None,
     }))
     .await;
 
@@ -78,3 +80,84 @@ async fn team_sends_unexpected_message_type() {
         }
     }
 }
+
+#[tokio::test]
+async fn action_with_request_id_is_acknowledged() {
+    let server = TestServer::start().await;
+    let (mut host, _) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    host.send_json(&ClientEnvelope {
+        message: ClientMessage::Host(HostAction::NextQuestion),
+        request_id: Some("next-1".to_string()),
+    })
+    .await;
+
+    let _: ServerMessage = host.recv_json().await;
+    match host.recv_json::<ServerMessage>().await {
+        ServerMessage::Ack { request_id } => assert_eq!(request_id, "next-1"),
+        other => panic!("Expected Ack message, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn failed_host_action_returns_code_and_rollback_state() {
+    let server = TestServer::start().await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    host.send_json(&ClientEnvelope {
+        message: ClientMessage::Host(HostAction::PrevQuestion),
+        request_id: Some("prev-1".to_string()),
+    })
+    .await;
+
+    match host.recv_json::<ServerMessage>().await {
+        ServerMessage::Error {
+            code,
+            request_id,
+            state,
+            ..
+        } => {
+            assert_eq!(code, ErrorCode::AlreadyAtFirstQuestion);
+            assert_eq!(request_id.as_deref(), Some("prev-1"));
+            let state = state.expect("Error should carry the host's state for rollback");
+            assert_eq!(state.game_code, game_code);
+            assert_eq!(state.current_question_number, 1);
+        }
+        other => panic!("Expected Error message, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn failed_team_action_returns_code_and_team_state() {
+    let server = TestServer::start().await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Test Team").await;
+    let _: ServerMessage = host.recv_json().await;
+
+    // Timer isn't running, so submissions are closed
+    team.send_json(&ClientEnvelope {
+        message: ClientMessage::Team(TeamAction::SubmitAnswer {
+            team_name: "Test Team".to_string(),
+            answer: "42".to_string(),
+        }),
+        request_id: Some("answer-1".to_string()),
+    })
+    .await;
+
+    match team.recv_json::<ServerMessage>().await {
+        ServerMessage::Error {
+            code,
+            request_id,
+            team_state,
+            ..
+        } => {
+            assert_eq!(code, ErrorCode::SubmissionsClosed);
+            assert_eq!(request_id.as_deref(), Some("answer-1"));
+            let team_state = team_state.expect("Error should carry the team's state");
+            assert!(team_state.questions[0].content.is_none());
+        }
+        other => panic!("Expected Error message, got {other:?}"),
+    }
+}
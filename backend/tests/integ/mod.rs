@@ -4,10 +4,19 @@ mod auto_scoring_test;
 mod connection_and_game_creation_test;
 mod game_timer_test;
 mod host_reconnection_test;
+mod jwks_test;
+mod limits_test;
+mod local_auth_test;
 mod message_validation_test;
+mod persistence_test;
+mod protocol_test;
 mod question_navigation_test;
+mod reauth_test;
+mod saved_games_test;
 mod server_lifecycle_test;
 mod settings_test;
+mod staff_test;
+mod state_patch_test;
 mod team_name_case_test;
 mod team_reconnection_test;
 mod watcher_test;
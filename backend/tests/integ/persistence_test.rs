@@ -0,0 +1,336 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{TestClient, TestServer, create_host_token};
+
+use backend::model::client_message::{ClientMessage, HostAction, TeamAction};
+use backend::model::event::{EventKind, GameEvent};
+use backend::model::server_message::ServerMessage;
+use backend::model::types::{AnswerContent, ScoreData};
+use backend::persistence::{ActiveGame, GameStore, MemoryStore, PersistenceClient, SavedGame};
+
+/// Load a test host's game from the store, as a restarted server would
+async fn saved_game(store: &Arc<MemoryStore>, game_code: &str) -> Option<SavedGame> {
+    PersistenceClient::with_store(store.clone())
+        .load_game("test-host-user", game_code)
+        .await
+        .unwrap()
+}
+
+/// Start a new server on `store` and reclaim the game as its host
+async fn restart_and_reclaim(
+    store: Arc<MemoryStore>,
+    game_code: &str,
+) -> (TestServer, ServerMessage) {
+    let server = TestServer::start_with_store(store).await;
+    let token = create_host_token();
+    let mut host = TestClient::connect_with_token(&server.ws_url(), Some(&token)).await;
+    host.send_json(&ClientMessage::Host(HostAction::CreateGame {
+        game_code: Some(game_code.to_string()),
+        copy_settings_from: None,
+    }))
+    .await;
+    let response = host.recv_json().await;
+    (server, response)
+}
+
+#[tokio::test]
+async fn game_is_restored_after_server_restart() {
+    let store = Arc::new(MemoryStore::new());
+    let server = TestServer::start_with_store(store.clone()).await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Saved Team").await;
+    let _: ServerMessage = host.recv_json().await; // team join
+
+    host.send_json(&ClientMessage::Host(HostAction::NextQuestion))
+        .await;
+    let _: ServerMessage = host.recv_json().await;
+
+    // Shutting down saves every game
+    server.shutdown();
+    let _: ServerMessage = host.recv_raw_json().await; // ServerRestarting
+    host.assert_closed().await;
+
+    let (_server, response) = restart_and_reclaim(store, &game_code).await;
+    match response {
+        ServerMessage::GameState { state, .. } => {
+            assert_eq!(state.game_code, game_code);
+            assert_eq!(state.current_question_number, 2);
+            assert_eq!(state.teams.len(), 1);
+            assert_eq!(state.teams[0].team_name, "Saved Team");
+        }
+        other => panic!("Expected restored GameState, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn changes_are_saved_without_waiting_for_disconnect() {
+    let store = Arc::new(MemoryStore::new());
+    let server = TestServer::start_with_store(store.clone()).await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    // The first change is saved right away
+    host.send_json(&ClientMessage::Host(HostAction::NextQuestion))
+        .await;
+    let _: ServerMessage = host.recv_json().await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let saved = saved_game(&store, &game_code)
+        .await
+        .expect("Game should be saved");
+    assert_eq!(saved.state.current_question_number, 2);
+    assert!(saved.events.is_empty());
+
+    // Changes inside the debounce window are caught by the trailing save, which only
+    // appends them to the log
+    host.send_json(&ClientMessage::Host(HostAction::NextQuestion))
+        .await;
+    let _: ServerMessage = host.recv_json().await;
+    host.send_json(&ClientMessage::Host(HostAction::NextQuestion))
+        .await;
+    let _: ServerMessage = host.recv_json().await;
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    let saved = saved_game(&store, &game_code).await.unwrap();
+    assert_eq!(saved.state.current_question_number, 2);
+    assert_eq!(saved.events.len(), 2);
+    assert_eq!(saved.events[0].seq, saved.meta.event_seq + 1);
+}
+
+#[tokio::test]
+async fn game_is_rebuilt_from_event_log_after_crash() {
+    let store = Arc::new(MemoryStore::new());
+    let server = TestServer::start_with_store(store.clone()).await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Logged Team").await;
+    let _: ServerMessage = host.recv_json().await; // team join
+
+    host.send_json(&ClientMessage::Host(HostAction::StartTimer))
+        .await;
+    let _: ServerMessage = host.recv_json().await;
+    let _: ServerMessage = team.recv_json().await;
+
+    team.send_json(&ClientMessage::Team(TeamAction::SubmitAnswer {
+        team_name: "Logged Team".to_string(),
+        answer: "Paris".to_string(),
+    }))
+    .await;
+    let _: ServerMessage = team.recv_json().await;
+    let _: ServerMessage = host.recv_json().await;
+
+    host.send_json(&ClientMessage::Host(HostAction::ScoreAnswer {
+        question_number: 1,
+        team_name: "Logged Team".to_string(),
+        score: ScoreData {
+            question_points: 50,
+            bonus_points: 0,
+            override_points: 0,
+            speed_bonus_points: 0,
+        },
+    }))
+    .await;
+    let _: ServerMessage = host.recv_json().await;
+    host.send_json(&ClientMessage::Host(HostAction::NextQuestion))
+        .await;
+    let _: ServerMessage = host.recv_json().await;
+
+    // Let the trailing save append the log, then "crash": the old server never saves again
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    let saved = saved_game(&store, &game_code).await.unwrap();
+    assert!(
+        !saved.events.is_empty(),
+        "Later actions should only be in the log"
+    );
+
+    let (_server, response) = restart_and_reclaim(store, &game_code).await;
+    match response {
+        ServerMessage::GameState { state, .. } => {
+            assert_eq!(state.current_question_number, 2);
+            assert_eq!(state.teams[0].team_name, "Logged Team");
+            assert_eq!(state.teams[0].score.question_points, 50);
+            assert!(!state.teams[0].connected);
+            let answer = &state.questions[0].answers[0];
+            assert!(matches!(
+                &answer.content,
+                Some(AnswerContent::Standard { answer_text }) if answer_text == "Paris"
+            ));
+            assert_eq!(answer.score.question_points, 50);
+            assert!(!answer.late);
+            assert!(answer.answer_latency_ms.is_some());
+        }
+        other => panic!("Expected restored GameState, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn teams_rejoin_active_games_before_host_returns() {
+    let store = Arc::new(MemoryStore::new());
+    let server = TestServer::start_with_store(store.clone()).await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Early Team").await;
+    let _: ServerMessage = host.recv_json().await; // team join
+    host.send_json(&ClientMessage::Host(HostAction::NextQuestion))
+        .await;
+    let _: ServerMessage = host.recv_json().await;
+    tokio::time::sleep(Duration::from_millis(2500)).await; // trailing save
+
+    // The server dies without a shutdown; the new one loads the game at startup
+    let server = TestServer::start_with_store(store).await;
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.send_json(&ClientMessage::Team(TeamAction::ValidateJoin {
+        team_name: "Early Team".to_string(),
+        game_code: game_code.clone(),
+    }))
+    .await;
+    match team.recv_json().await {
+        ServerMessage::TeamGameState { state, .. } => {
+            assert_eq!(state.current_question_number, 2);
+            assert_eq!(state.team.team_name, "Early Team");
+        }
+        other => panic!("Expected TeamGameState for the rejoin, got {other:?}"),
+    }
+
+    // The host reclaims the game already in memory
+    let token = create_host_token();
+    let mut host = TestClient::connect_with_token(&server.ws_url(), Some(&token)).await;
+    host.send_json(&ClientMessage::Host(HostAction::CreateGame {
+        game_code: Some(game_code.clone()),
+        copy_settings_from: None,
+    }))
+    .await;
+    match host.recv_json().await {
+        ServerMessage::GameState { state, .. } => {
+            assert_eq!(state.game_code, game_code);
+            assert!(state.teams[0].connected);
+        }
+        other => panic!("Expected GameState, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn snapshots_delete_the_event_batches_they_cover() {
+    let store = Arc::new(MemoryStore::new());
+    let server = TestServer::start_with_store(store.clone()).await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+    host.send_json(&ClientMessage::Host(HostAction::NextQuestion))
+        .await;
+    let _: ServerMessage = host.recv_json().await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let state = saved_game(&store, &game_code).await.unwrap().state;
+
+    // Log batches for another game so the running server doesn't write to them
+    let persistence = PersistenceClient::with_store(store.clone());
+    let event = |seq| GameEvent {
+        seq,
+        at_ms: 0,
+        kind: EventKind::Host {
+            action: HostAction::NextQuestion,
+        },
+    };
+    for batch in [
+        vec![event(1), event(2)],
+        vec![event(3), event(4)],
+        vec![event(5)],
+    ] {
+        persistence
+            .append_events("test-host-user", "LOGGED", &batch)
+            .await
+            .unwrap();
+    }
+
+    persistence
+        .save_game_state("test-host-user", "LOGGED", &state, 4, None)
+        .await
+        .unwrap();
+
+    let batches = store
+        .list("test-host-user/LOGGED/events/".to_string())
+        .await
+        .unwrap();
+    assert_eq!(
+        batches,
+        vec!["test-host-user/LOGGED/events/000000000005.jsonl"]
+    );
+    let saved = persistence
+        .load_game("test-host-user", "LOGGED")
+        .await
+        .unwrap()
+        .unwrap();
+    let seqs: Vec<u64> = saved.events.iter().map(|event| event.seq).collect();
+    assert_eq!(seqs, vec![5]);
+}
+
+#[tokio::test]
+async fn host_leaving_removes_game_from_active_index() {
+    let store = Arc::new(MemoryStore::new());
+    let server = TestServer::start_with_store(store.clone()).await;
+    let persistence = PersistenceClient::with_store(store.clone());
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+    let entry = ActiveGame {
+        user_id: "test-host-user".to_string(),
+        game_code: game_code.clone(),
+    };
+    assert_eq!(
+        persistence.active_games().await.unwrap(),
+        vec![entry.clone()]
+    );
+
+    host.close().await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(persistence.active_games().await.unwrap().is_empty());
+
+    // Coming back indexes it again
+    let token = create_host_token();
+    let mut host = TestClient::connect_with_token(&server.ws_url(), Some(&token)).await;
+    host.send_json(&ClientMessage::Host(HostAction::CreateGame {
+        game_code: Some(game_code.clone()),
+        copy_settings_from: None,
+    }))
+    .await;
+    let _: ServerMessage = host.recv_json().await;
+    assert_eq!(persistence.active_games().await.unwrap(), vec![entry]);
+}
+
+#[tokio::test]
+async fn colliding_active_games_restore_the_most_recent() {
+    let store = Arc::new(MemoryStore::new());
+    let server = TestServer::start_with_store(store.clone()).await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+    host.send_json(&ClientMessage::Host(HostAction::NextQuestion))
+        .await;
+    let _: ServerMessage = host.recv_json().await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let state = saved_game(&store, &game_code).await.unwrap().state;
+
+    // Two users' games share a code in an index written before codes were checked
+    let persistence = PersistenceClient::with_store(store.clone());
+    for user_id in ["older-user", "test-host-user"] {
+        persistence
+            .save_game_state(user_id, "SAME", &state, 0, None)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let entries = ["older-user", "test-host-user"].map(|user_id| ActiveGame {
+        user_id: user_id.to_string(),
+        game_code: "SAME".to_string(),
+    });
+    store
+        .put(
+            ".active_games.json".to_string(),
+            serde_json::to_vec(&entries).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    // The more recently played game is restored and the other leaves the index
+    let (_server, response) = restart_and_reclaim(store, "SAME").await;
+    assert!(matches!(response, ServerMessage::GameState { .. }));
+    let active = persistence.active_games().await.unwrap();
+    assert!(!active.contains(&entries[0]));
+    assert!(active.contains(&entries[1]));
+}
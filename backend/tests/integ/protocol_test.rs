@@ -0,0 +1,202 @@
+use crate::{TestClient, TestServer, assert_answer_submission_flow, create_host_token};
+
+use backend::model::client_message::{ClientKind, ClientMessage, HostAction, TeamAction};
+use backend::model::protocol::{self, Encoding, LEGACY_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use backend::model::server_message::{ServerMessage, TimerEventKind};
+use backend::model::types::GameSettings;
+use tokio_tungstenite::tungstenite::Message;
+
+#[tokio::test]
+async fn hello_is_answered_with_welcome() {
+    let server = TestServer::start().await;
+    let mut client = TestClient::connect(&server.ws_url()).await;
+
+    client.hello(PROTOCOL_VERSION, ClientKind::Team).await;
+
+    match client.recv_json::<ServerMessage>().await {
+        ServerMessage::Welcome {
+            protocol_version,
+            supported_versions,
+            encoding,
+        } => {
+            assert_eq!(protocol_version, PROTOCOL_VERSION);
+            assert_eq!(encoding, Encoding::Json);
+            assert!(supported_versions.contains(&LEGACY_PROTOCOL_VERSION));
+            assert!(supported_versions.contains(&PROTOCOL_VERSION));
+        }
+        other => panic!("Expected Welcome message, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn message_pack_client_gets_binary_frames() {
+    let server = TestServer::start().await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.set_encoding(Encoding::MessagePack);
+    team.join_game(&game_code, "Test Team").await;
+    let _: ServerMessage = host.recv_json().await;
+
+    // Each client gets its own encoding
+    team.send_json(&ClientMessage::Team(TeamAction::SyncClock {
+        client_time_ms: 1,
+    }))
+    .await;
+    assert!(matches!(team.recv_frame().await, Message::Binary(_)));
+    host.send_json(&ClientMessage::Host(HostAction::SyncClock {
+        client_time_ms: 1,
+    }))
+    .await;
+    assert!(matches!(host.recv_frame().await, Message::Text(_)));
+
+    host.send_json(&ClientMessage::Host(HostAction::StartTimer))
+        .await;
+    let _: ServerMessage = host.recv_json().await;
+
+    // Answers sent as MessagePack are read like JSON ones
+    assert_answer_submission_flow(&mut team, &mut host, "Test Team", "42").await;
+}
+
+#[tokio::test]
+async fn unsupported_version_asks_client_to_refresh() {
+    let server = TestServer::start().await;
+    let mut client = TestClient::connect(&server.ws_url()).await;
+
+    client.hello(PROTOCOL_VERSION + 1, ClientKind::Team).await;
+
+    match client.recv_json::<ServerMessage>().await {
+        ServerMessage::UnsupportedProtocol {
+            message,
+            supported_versions,
+        } => {
+            assert!(
+                message.contains("refresh"),
+                "Error should ask the user to refresh, got: {message}"
+            );
+            assert_eq!(supported_versions, protocol::supported_versions());
+        }
+        other => panic!("Expected UnsupportedProtocol message, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn first_action_must_match_client_kind() {
+    let server = TestServer::start().await;
+    let token = create_host_token();
+    let mut client = TestClient::connect_with_token(&server.ws_url(), Some(&token)).await;
+
+    client.hello(PROTOCOL_VERSION, ClientKind::Team).await;
+    let _: ServerMessage = client.recv_json().await;
+    client
+        .send_raw_text(r#"{"host": {"type": "createGame"}}"#)
+        .await;
+
+    match client.recv_json::<ServerMessage>().await {
+        ServerMessage::Error { message, .. } => {
+            assert!(
+                message.contains("client kind"),
+                "Unexpected error: {message}"
+            );
+        }
+        other => panic!("Expected Error message, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn legacy_host_without_hello_gets_full_snapshots() {
+    let server = TestServer::start().await;
+    let token = create_host_token();
+    let mut host = TestClient::connect_with_token(&server.ws_url(), Some(&token)).await;
+
+    host.send_raw_text(r#"{"host": {"type": "createGame"}}"#)
+        .await;
+    let _: ServerMessage = host.recv_json().await;
+
+    host.send_raw_text(r#"{"host": {"type": "startTimer"}}"#)
+        .await;
+
+    match host.recv_raw_json::<ServerMessage>().await {
+        ServerMessage::GameState { state, .. } => assert!(state.timer_running),
+        other => panic!("Legacy clients should get snapshots, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn legacy_clients_get_timer_ticks() {
+    let server = TestServer::start().await;
+    let token = create_host_token();
+    let mut host = TestClient::connect_with_token(&server.ws_url(), Some(&token)).await;
+    host.send_raw_text(r#"{"host": {"type": "createGame"}}"#)
+        .await;
+    let game_code = match host.recv_json::<ServerMessage>().await {
+        ServerMessage::GameState { state, .. } => state.game_code,
+        other => panic!("Expected GameState message, got {other:?}"),
+    };
+
+    // A current client alongside gets no ticks, just events
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Test Team").await;
+
+    host.send_raw_text(r#"{"host": {"type": "startTimer"}}"#)
+        .await;
+    let mut ticks = Vec::new();
+    while ticks.len() < 2 {
+        match host.recv_timer_event().await {
+            ServerMessage::TimerTick { seconds_remaining } => ticks.push(seconds_remaining),
+            ServerMessage::TimerEvent { .. } => {}
+            other => panic!("Expected TimerTick, got {other:?}"),
+        }
+    }
+    assert_eq!(ticks, vec![30, 29]);
+
+    host.send_raw_text(r#"{"host": {"type": "pauseTimer"}}"#)
+        .await;
+    loop {
+        match team.recv_timer_event().await {
+            ServerMessage::TimerEvent {
+                event: TimerEventKind::Paused,
+                ..
+            } => break,
+            ServerMessage::TimerEvent { .. } => {}
+            other => panic!("Current clients shouldn't get ticks, got {other:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn legacy_settings_update_keeps_defaults_for_newer_settings() {
+    let server = TestServer::start().await;
+    let token = create_host_token();
+    let mut host = TestClient::connect_with_token(&server.ws_url(), Some(&token)).await;
+
+    host.send_raw_text(r#"{"host": {"type": "createGame"}}"#)
+        .await;
+    let _: ServerMessage = host.recv_json().await;
+
+    // Version 1 had no late grace, speed bonus mode or timer warning settings
+    host.send_raw_text(
+        r#"{"host": {"type": "updateGameSettings", "defaultTimerDuration": 45,
+            "defaultQuestionPoints": 50, "defaultBonusIncrement": 5,
+            "defaultQuestionType": "standard",
+            "defaultMcConfig": {"optionType": "letters", "numOptions": 4},
+            "speedBonusEnabled": false, "speedBonusNumTeams": 2,
+            "speedBonusFirstPlacePoints": 10}}"#,
+    )
+    .await;
+
+    match host.recv_json::<ServerMessage>().await {
+        ServerMessage::GameState { state, .. } => {
+            assert_eq!(state.game_settings.default_timer_duration, 45);
+            assert_eq!(
+                state.game_settings.late_grace_seconds,
+                GameSettings::DEFAULT_LATE_GRACE_SECONDS
+            );
+            assert_eq!(
+                state.game_settings.timer_warning_seconds,
+                GameSettings::default_timer_warning_seconds()
+            );
+        }
+        other => panic!("Expected GameState message, got {other:?}"),
+    }
+}
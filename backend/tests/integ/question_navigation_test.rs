@@ -104,7 +104,7 @@ async fn team_can_answer_earlier_question_after_skipping() {
 
     // === Verify final state ===
     match response {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             // Q1 should have Team A's answer with score
             let q1_answers = &state.questions[0].answers;
             assert_eq!(q1_answers.len(), 1, "Q1 should have 1 answer");
@@ -163,7 +163,7 @@ async fn next_question_increments_question_number_and_creates_new_question() {
 
     let response: ServerMessage = host.recv_json().await;
     match response {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             assert_eq!(state.current_question_number, 2);
             assert_eq!(state.questions.len(), 2, "New question should be created");
             assert_eq!(
@@ -266,7 +266,7 @@ async fn navigation_preserves_answers_and_scores_across_questions() {
 
     // Verify Q1 still has both answers and Team Alpha's score
     match response {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             assert_eq!(state.current_question_number, 1);
             assert_eq!(state.questions.len(), 2, "Should still have 2 questions");
 
@@ -308,7 +308,7 @@ async fn navigation_preserves_answers_and_scores_across_questions() {
 
     // Verify Q2 still has Team Alpha's answer
     match response {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             assert_eq!(state.current_question_number, 2);
             assert_eq!(state.questions.len(), 2, "Should not create Q3");
 
@@ -342,7 +342,7 @@ async fn prev_question_decrements_question_number() {
 
     let response: ServerMessage = host.recv_json().await;
     match response {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             assert_eq!(state.current_question_number, 1);
             assert_eq!(
                 state.timer_seconds_remaining,
@@ -391,7 +391,7 @@ async fn next_question_stops_running_timer() {
 
     let response: ServerMessage = host.recv_json().await;
     match response {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             assert!(
                 !state.timer_running,
                 "Timer should be stopped after navigation"
@@ -430,7 +430,7 @@ async fn question_navigation_broadcasts_to_teams() {
     let team_response: ServerMessage = team.recv_json().await;
 
     match team_response {
-        ServerMessage::TeamGameState { state } => {
+        ServerMessage::TeamGameState { state, .. } => {
             assert_eq!(state.current_question_number, 2);
         }
         other => panic!("Expected TeamGameState, got {other:?}"),
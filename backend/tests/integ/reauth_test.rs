@@ -0,0 +1,148 @@
+use std::time::Duration;
+
+use crate::{
+    TestClient, TestServer, capture_logs, captured_logs, create_host_token,
+    create_host_token_expiring_in,
+};
+
+use backend::model::client_message::{ClientMessage, HostAction};
+use backend::model::error::ErrorCode;
+use backend::model::server_message::ServerMessage;
+
+const HOST: &str = "test-host-user";
+
+/// Connect with a token that expires in two seconds, well inside the warning window
+async fn connect_expiring_host(server: &TestServer) -> (TestClient, String) {
+    let token = create_host_token_expiring_in(HOST, 2);
+    let mut host = TestClient::connect_with_token(&server.ws_url(), Some(&token)).await;
+    let game_code = host.create_game().await;
+    match host.recv_json().await {
+        ServerMessage::AuthExpiring { .. } => (),
+        other => panic!("Expected AuthExpiring, got {other:?}"),
+    }
+    (host, game_code)
+}
+
+async fn reauthenticate(host: &mut TestClient, token: String) -> ServerMessage {
+    host.send_json(&ClientMessage::Host(HostAction::Reauthenticate {
+        token: token.into(),
+    }))
+    .await;
+    host.recv_json().await
+}
+
+async fn recv_question_number(host: &mut TestClient) -> usize {
+    match host.recv_json().await {
+        ServerMessage::GameState { state, .. } => state.current_question_number,
+        other => panic!("Expected GameState, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn expired_host_is_read_only_until_reauthenticated() {
+    let server = TestServer::start().await;
+    let (mut host, game_code) = connect_expiring_host(&server).await;
+
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    match host.recv_json().await {
+        ServerMessage::AuthExpired => (),
+        other => panic!("Expected AuthExpired, got {other:?}"),
+    }
+
+    // The game carries on, and the host still sees it
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Quizzly Bears").await;
+    match host.recv_json().await {
+        ServerMessage::GameState { state, .. } => assert_eq!(state.teams.len(), 1),
+        other => panic!("Expected GameState, got {other:?}"),
+    }
+
+    // But can't change it
+    host.send_json(&ClientMessage::Host(HostAction::NextQuestion))
+        .await;
+    match host.recv_json().await {
+        ServerMessage::Error { code, state, .. } => {
+            assert_eq!(code, ErrorCode::AuthenticationExpired);
+            assert_eq!(
+                state
+                    .expect("Error should carry state")
+                    .current_question_number,
+                1
+            );
+        }
+        other => panic!("Expected Error, got {other:?}"),
+    }
+
+    // Another user's token doesn't help
+    let other_user = create_host_token_expiring_in("another-host", 3600);
+    match reauthenticate(&mut host, other_user).await {
+        ServerMessage::Error { code, .. } => assert_eq!(code, ErrorCode::NotAuthorized),
+        other => panic!("Expected Error, got {other:?}"),
+    }
+
+    match reauthenticate(&mut host, create_host_token()).await {
+        ServerMessage::Reauthenticated { expires_at_ms } => assert!(expires_at_ms.is_some()),
+        other => panic!("Expected Reauthenticated, got {other:?}"),
+    }
+    host.send_json(&ClientMessage::Host(HostAction::NextQuestion))
+        .await;
+    assert_eq!(recv_question_number(&mut host).await, 2);
+}
+
+#[tokio::test]
+async fn reauthenticating_before_expiry_keeps_control() {
+    let server = TestServer::start().await;
+    let (mut host, _game_code) = connect_expiring_host(&server).await;
+
+    match reauthenticate(&mut host, "not-a-token".to_string()).await {
+        ServerMessage::Error { code, .. } => assert_eq!(code, ErrorCode::AuthenticationRequired),
+        other => panic!("Expected Error, got {other:?}"),
+    }
+    match reauthenticate(&mut host, create_host_token()).await {
+        ServerMessage::Reauthenticated { .. } => (),
+        other => panic!("Expected Reauthenticated, got {other:?}"),
+    }
+
+    // The old token's expiry passes without cutting the host off
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    host.send_json(&ClientMessage::Host(HostAction::NextQuestion))
+        .await;
+    assert_eq!(recv_question_number(&mut host).await, 2);
+}
+
+#[tokio::test]
+async fn tokens_never_reach_the_logs() {
+    capture_logs();
+    let server = TestServer::start().await;
+    let hello_token = create_host_token_expiring_in(HOST, 3600);
+    let mut host = TestClient::connect_with_hello_token(&server.ws_url(), &hello_token).await;
+    host.create_game().await;
+
+    let reauth_token = create_host_token_expiring_in(HOST, 7200);
+    match reauthenticate(&mut host, reauth_token.clone()).await {
+        ServerMessage::Reauthenticated { .. } => (),
+        other => panic!("Expected Reauthenticated, got {other:?}"),
+    }
+
+    // A frame that doesn't parse isn't logged either
+    let bad_token = "unparsed-frame-token";
+    host.send_raw_text(&format!(
+        r#"{{"host": {{"type": "reauthenticate", "token": "{bad_token}"}}"#
+    ))
+    .await;
+    match host.recv_json().await {
+        ServerMessage::Error { code, .. } => assert_eq!(code, ErrorCode::InvalidMessage),
+        other => panic!("Expected Error, got {other:?}"),
+    }
+
+    let logs = captured_logs();
+    assert!(
+        logs.iter().any(|line| line.contains("Reauthenticate")),
+        "Reauthenticate should be logged, redacted"
+    );
+    for token in [hello_token.as_str(), reauth_token.as_str(), bad_token] {
+        if let Some(line) = logs.iter().find(|line| line.contains(token)) {
+            panic!("Token was logged: {line}");
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{TestClient, TestServer, create_host_token};
+
+use backend::model::client_message::{ClientMessage, HostAction};
+use backend::model::error::ErrorCode;
+use backend::model::server_message::{GamePhase, SavedGameSummary, ServerMessage};
+use backend::persistence::{GameStore, MemoryStore};
+
+/// Connect as the test host without creating a game
+async fn connect_host(server: &TestServer) -> TestClient {
+    let token = create_host_token();
+    TestClient::connect_with_token(&server.ws_url(), Some(&token)).await
+}
+
+async fn list_games(client: &mut TestClient) -> Vec<SavedGameSummary> {
+    client
+        .send_json(&ClientMessage::Host(HostAction::ListGames))
+        .await;
+    match client.recv_json().await {
+        ServerMessage::SavedGames { games } => games,
+        other => panic!("Expected SavedGames, got {other:?}"),
+    }
+}
+
+/// Create a game, move it to question 2 so it's saved, and wait for the save
+async fn create_saved_game(server: &TestServer) -> (TestClient, String) {
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(server).await;
+    host.send_json(&ClientMessage::Host(HostAction::NextQuestion))
+        .await;
+    let _: ServerMessage = host.recv_json().await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    (host, game_code)
+}
+
+#[tokio::test]
+async fn saved_games_are_listed_before_creating_a_game() {
+    let server = TestServer::start_with_store(Arc::new(MemoryStore::new())).await;
+    let (_host, game_code) = create_saved_game(&server).await;
+
+    let mut lobby = connect_host(&server).await;
+    let games = list_games(&mut lobby).await;
+    assert_eq!(games.len(), 1);
+    let game = &games[0];
+    assert_eq!(game.game_code, game_code);
+    assert_eq!(game.question_count, 2);
+    assert_eq!(game.current_question_number, 2);
+    assert_eq!(game.team_count, 0);
+    assert_eq!(game.phase, GamePhase::NotStarted);
+    assert!(game.created_at_ms.is_some());
+    assert!(game.last_played_ms >= game.created_at_ms);
+
+    // The connection can still go on to create a game
+    let new_code = lobby.create_game().await;
+    assert_ne!(new_code, game_code);
+}
+
+#[tokio::test]
+async fn saved_games_are_listed_from_their_summaries() {
+    let store = Arc::new(MemoryStore::new());
+    let server = TestServer::start_with_store(store.clone()).await;
+    let (mut host, game_code) = create_saved_game(&server).await;
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Listed Team").await;
+    let _: ServerMessage = host.recv_json().await; // team join
+
+    // The join is only in the event log, but the summary counts it
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    let mut lobby = connect_host(&server).await;
+    let games = list_games(&mut lobby).await;
+    assert_eq!(games[0].team_count, 1);
+
+    // Games saved without a summary are loaded instead
+    store
+        .delete(format!("test-host-user/{game_code}/summary.json"))
+        .await
+        .unwrap();
+    let games = list_games(&mut lobby).await;
+    assert_eq!(games.len(), 1);
+    assert_eq!(games[0].game_code, game_code);
+    assert_eq!(games[0].team_count, 1);
+}
+
+#[tokio::test]
+async fn settings_are_copied_into_a_new_game() {
+    let server = TestServer::start_with_store(Arc::new(MemoryStore::new())).await;
+    let (mut host, game_code) = create_saved_game(&server).await;
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Original Team").await;
+    let _: ServerMessage = host.recv_json().await; // team join
+
+    let mut copy_host = connect_host(&server).await;
+    copy_host
+        .send_json(&ClientMessage::Host(HostAction::CreateGame {
+            game_code: Some("COPY".to_string()),
+            copy_settings_from: Some(game_code.clone()),
+        }))
+        .await;
+    match copy_host.recv_json().await {
+        ServerMessage::GameState { state, .. } => {
+            assert_eq!(state.game_code, "COPY");
+            assert_eq!(state.questions.len(), 2);
+            assert_eq!(state.current_question_number, 1);
+            assert!(state.teams.is_empty());
+        }
+        other => panic!("Expected GameState for the copy, got {other:?}"),
+    }
+
+    // The copy is saved straight away, and can be listed from inside a game
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let codes: Vec<_> = list_games(&mut copy_host)
+        .await
+        .into_iter()
+        .map(|game| game.game_code)
+        .collect();
+    assert!(codes.contains(&"COPY".to_string()));
+    assert!(codes.contains(&game_code));
+
+    // Copying onto a code that's in use is refused
+    let mut other = connect_host(&server).await;
+    other
+        .send_json(&ClientMessage::Host(HostAction::CreateGame {
+            game_code: Some("COPY".to_string()),
+            copy_settings_from: Some(game_code),
+        }))
+        .await;
+    match other.recv_json().await {
+        ServerMessage::Error { code, .. } => assert_eq!(code, ErrorCode::GameCodeTaken),
+        other => panic!("Expected GameCodeTaken, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn saved_game_is_deleted() {
+    let server = TestServer::start_with_store(Arc::new(MemoryStore::new())).await;
+    let (host, game_code) = create_saved_game(&server).await;
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Deleted Team").await;
+    drop(host);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut lobby = connect_host(&server).await;
+    lobby
+        .send_json(&ClientMessage::Host(HostAction::DeleteGame {
+            game_code: game_code.clone(),
+        }))
+        .await;
+    match lobby.recv_json().await {
+        ServerMessage::GameDeleted { game_code: deleted } => assert_eq!(deleted, game_code),
+        other => panic!("Expected GameDeleted, got {other:?}"),
+    }
+    // Its teams are disconnected and it's no longer listed
+    team.assert_closed().await;
+    assert!(list_games(&mut lobby).await.is_empty());
+
+    lobby
+        .send_json(&ClientMessage::Host(HostAction::DeleteGame {
+            game_code: game_code.clone(),
+        }))
+        .await;
+    match lobby.recv_json().await {
+        ServerMessage::Error { code, .. } => assert_eq!(code, ErrorCode::GameNotFound),
+        other => panic!("Expected GameNotFound, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn hosted_game_cannot_be_deleted() {
+    let server = TestServer::start_with_store(Arc::new(MemoryStore::new())).await;
+    let (_host, game_code) = create_saved_game(&server).await;
+
+    let mut lobby = connect_host(&server).await;
+    lobby
+        .send_json(&ClientMessage::Host(HostAction::DeleteGame {
+            game_code: game_code.clone(),
+        }))
+        .await;
+    match lobby.recv_json().await {
+        ServerMessage::Error { code, .. } => assert_eq!(code, ErrorCode::GameAlreadyHasHost),
+        other => panic!("Expected GameAlreadyHasHost, got {other:?}"),
+    }
+    assert_eq!(list_games(&mut lobby).await.len(), 1);
+}
@@ -1,5 +1,8 @@
 use crate::{TestClient, TestServer};
 
+use backend::model::server_message::ServerMessage;
+use tokio_tungstenite::connect_async;
+
 #[tokio::test]
 async fn timer_closes_server_when_all_hosts_disconnect() {
     // Use a very short shutdown duration for this test (500ms)
@@ -90,3 +93,33 @@ async fn timer_does_not_cancel_when_team_connects() {
         "Shutdown signal should have been sent"
     );
 }
+
+#[tokio::test]
+async fn graceful_shutdown_notifies_clients_and_closes_sockets() {
+    let server = TestServer::start().await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Test Team").await;
+    let _: ServerMessage = host.recv_json().await;
+
+    let mut watcher = TestClient::connect(&server.ws_url()).await;
+    watcher.watch_game(&game_code).await;
+
+    server.shutdown();
+
+    for client in [&mut host, &mut team, &mut watcher] {
+        match client.recv_json::<ServerMessage>().await {
+            ServerMessage::ServerRestarting { message } => {
+                assert!(message.contains("saved"), "Unexpected message: {message}");
+            }
+            other => panic!("Expected ServerRestarting message, got {other:?}"),
+        }
+        client.assert_closed().await;
+    }
+
+    assert!(
+        connect_async(server.ws_url()).await.is_err(),
+        "Server should stop accepting connections once shutdown starts"
+    );
+}
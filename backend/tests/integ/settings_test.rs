@@ -1,7 +1,8 @@
 use crate::{TestClient, TestServer, default_mc_config};
 use backend::model::client_message::{ClientMessage, HostAction, TeamAction};
+use backend::model::error::ErrorCode;
 use backend::model::server_message::ServerMessage;
-use backend::model::types::QuestionKind;
+use backend::model::types::{QuestionKind, SpeedBonusMode};
 
 #[tokio::test]
 async fn update_game_settings_changes_defaults() {
@@ -18,12 +19,15 @@ async fn update_game_settings_changes_defaults() {
         speed_bonus_enabled: false,
         speed_bonus_num_teams: 2,
         speed_bonus_first_place_points: 10,
+        late_grace_seconds: 3,
+        speed_bonus_mode: SpeedBonusMode::Placement,
+        timer_warning_seconds: vec![10],
     }))
     .await;
 
     let response: ServerMessage = host.recv_json().await;
     match response {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             assert_eq!(state.game_settings.default_timer_duration, 60);
             assert_eq!(state.game_settings.default_question_points, 100);
             assert_eq!(state.game_settings.default_bonus_increment, 10);
@@ -56,12 +60,15 @@ async fn update_game_settings_propagates_to_unanswered_questions() {
         speed_bonus_enabled: false,
         speed_bonus_num_teams: 2,
         speed_bonus_first_place_points: 10,
+        late_grace_seconds: 3,
+        speed_bonus_mode: SpeedBonusMode::Placement,
+        timer_warning_seconds: vec![10],
     }))
     .await;
 
     let response: ServerMessage = host.recv_json().await;
     match response {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             // Both questions should be updated
             assert_eq!(state.questions[0].timer_duration, 45);
             assert_eq!(state.questions[0].question_points, 75);
@@ -122,12 +129,15 @@ async fn update_game_settings_does_not_change_answered_questions() {
         speed_bonus_enabled: false,
         speed_bonus_num_teams: 2,
         speed_bonus_first_place_points: 10,
+        late_grace_seconds: 3,
+        speed_bonus_mode: SpeedBonusMode::Placement,
+        timer_warning_seconds: vec![10],
     }))
     .await;
 
     let response: ServerMessage = host.recv_json().await;
     match response {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             // Q1 (has answers) should NOT be updated
             assert_eq!(
                 state.questions[0].timer_duration, q1_original_timer,
@@ -164,7 +174,7 @@ async fn update_question_settings_changes_specific_question() {
 
     let response: ServerMessage = host.recv_json().await;
     match response {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             assert_eq!(state.questions[0].timer_duration, 120);
             assert_eq!(state.questions[0].question_points, 150);
             assert_eq!(state.questions[0].bonus_increment, 20);
@@ -279,6 +289,9 @@ async fn settings_changes_broadcast_to_teams() {
         speed_bonus_enabled: false,
         speed_bonus_num_teams: 2,
         speed_bonus_first_place_points: 10,
+        late_grace_seconds: 3,
+        speed_bonus_mode: SpeedBonusMode::Placement,
+        timer_warning_seconds: vec![10],
     }))
     .await;
 
@@ -308,6 +321,9 @@ async fn new_questions_use_updated_game_settings() {
         speed_bonus_enabled: false,
         speed_bonus_num_teams: 2,
         speed_bonus_first_place_points: 10,
+        late_grace_seconds: 3,
+        speed_bonus_mode: SpeedBonusMode::Placement,
+        timer_warning_seconds: vec![10],
     }))
     .await;
     let _: ServerMessage = host.recv_json().await;
@@ -318,7 +334,7 @@ async fn new_questions_use_updated_game_settings() {
 
     let response: ServerMessage = host.recv_json().await;
     match response {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             // The new Q2 should use the updated defaults
             assert_eq!(state.questions[1].timer_duration, 90);
             assert_eq!(state.questions[1].question_points, 200);
@@ -333,3 +349,47 @@ async fn new_questions_use_updated_game_settings() {
         other => panic!("Expected GameState, got {other:?}"),
     }
 }
+
+#[tokio::test]
+async fn update_game_settings_validates_timer_warnings() {
+    let server = TestServer::start().await;
+    let (mut host, _) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    let update = |timer_warning_seconds: Vec<u32>| {
+        ClientMessage::Host(HostAction::UpdateGameSettings {
+            default_timer_duration: 30,
+            default_question_points: 50,
+            default_bonus_increment: 5,
+            default_question_type: QuestionKind::Standard,
+            default_mc_config: default_mc_config(),
+            speed_bonus_enabled: false,
+            speed_bonus_num_teams: 2,
+            speed_bonus_first_place_points: 10,
+            late_grace_seconds: 3,
+            speed_bonus_mode: SpeedBonusMode::Placement,
+            timer_warning_seconds,
+        })
+    };
+
+    // Zero and warnings longer than the timer are rejected
+    for warnings in [vec![0, 10], vec![10, 31]] {
+        host.send_json(&update(warnings)).await;
+        match host.recv_json::<ServerMessage>().await {
+            ServerMessage::Error { code, state, .. } => {
+                assert_eq!(code, ErrorCode::InvalidSettings);
+                let state = state.expect("Error should carry state for rollback");
+                assert_eq!(state.game_settings.timer_warning_seconds, vec![10]);
+            }
+            other => panic!("Expected Error, got {other:?}"),
+        }
+    }
+
+    // Valid warnings are sorted and deduplicated
+    host.send_json(&update(vec![20, 5, 30, 5])).await;
+    match host.recv_json::<ServerMessage>().await {
+        ServerMessage::GameState { state, .. } => {
+            assert_eq!(state.game_settings.timer_warning_seconds, vec![5, 20, 30]);
+        }
+        other => panic!("Expected GameState, got {other:?}"),
+    }
+}
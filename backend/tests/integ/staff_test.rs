@@ -0,0 +1,166 @@
+use crate::{TestClient, TestServer, create_test_jwt};
+
+use backend::model::client_message::{ClientMessage, HostAction};
+use backend::model::error::ErrorCode;
+use backend::model::server_message::{GameState, ServerMessage};
+use backend::model::types::StaffRole;
+
+/// Users outside the host group, who can only run games they're invited to
+const CO_HOST: &str = "co-host-user";
+const SCOREKEEPER: &str = "scorekeeper-user";
+
+async fn recv_state(client: &mut TestClient) -> GameState {
+    match client.recv_json().await {
+        ServerMessage::GameState { state, .. } => state,
+        other => panic!("Expected GameState, got {other:?}"),
+    }
+}
+
+async fn recv_error(client: &mut TestClient) -> ErrorCode {
+    match client.recv_json().await {
+        ServerMessage::Error { code, .. } => code,
+        other => panic!("Expected Error, got {other:?}"),
+    }
+}
+
+async fn invite(host: &mut TestClient, user_id: &str, role: StaffRole) {
+    host.send_json(&ClientMessage::Host(HostAction::InviteStaff {
+        user_id: user_id.to_string(),
+        role,
+    }))
+    .await;
+    let state = recv_state(host).await;
+    assert!(state.staff.iter().any(|member| member.user_id == user_id));
+}
+
+/// Connect as `user_id` and send JoinAsStaff, leaving the reply to the caller
+async fn connect_staff(server: &TestServer, user_id: &str, game_code: &str) -> TestClient {
+    let token = create_test_jwt(user_id, &[], false);
+    let mut client = TestClient::connect_with_token(&server.ws_url(), Some(&token)).await;
+    client
+        .send_json(&ClientMessage::Host(HostAction::JoinAsStaff {
+            game_code: game_code.to_string(),
+        }))
+        .await;
+    client
+}
+
+#[tokio::test]
+async fn co_host_shares_full_control() {
+    let server = TestServer::start().await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+    invite(&mut host, CO_HOST, StaffRole::CoHost).await;
+
+    let mut co_host = connect_staff(&server, CO_HOST, &game_code).await;
+    let state = recv_state(&mut co_host).await;
+    assert_eq!(state.game_code, game_code);
+    let state = recv_state(&mut host).await;
+    assert!(
+        state.staff[0].connected,
+        "Host should see the co-host connect"
+    );
+
+    // Edits from either side reach both
+    co_host
+        .send_json(&ClientMessage::Host(HostAction::NextQuestion))
+        .await;
+    assert_eq!(recv_state(&mut co_host).await.current_question_number, 2);
+    assert_eq!(recv_state(&mut host).await.current_question_number, 2);
+
+    host.send_json(&ClientMessage::Host(HostAction::PrevQuestion))
+        .await;
+    assert_eq!(recv_state(&mut host).await.current_question_number, 1);
+    assert_eq!(recv_state(&mut co_host).await.current_question_number, 1);
+
+    // Managing staff is left to the owner
+    co_host
+        .send_json(&ClientMessage::Host(HostAction::InviteStaff {
+            user_id: SCOREKEEPER.to_string(),
+            role: StaffRole::Scorekeeper,
+        }))
+        .await;
+    assert_eq!(recv_error(&mut co_host).await, ErrorCode::NotAuthorized);
+
+    // A second connection for the same co-host is refused
+    let mut duplicate = connect_staff(&server, CO_HOST, &game_code).await;
+    assert_eq!(
+        recv_error(&mut duplicate).await,
+        ErrorCode::AlreadyConnected
+    );
+
+    drop(co_host);
+    let state = recv_state(&mut host).await;
+    assert!(
+        !state.staff[0].connected,
+        "Host should see the co-host leave"
+    );
+}
+
+#[tokio::test]
+async fn scorekeeper_can_only_score() {
+    let server = TestServer::start().await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Quizzly Bears").await;
+    let _ = recv_state(&mut host).await; // team join
+    invite(&mut host, SCOREKEEPER, StaffRole::Scorekeeper).await;
+
+    let mut scorekeeper = connect_staff(&server, SCOREKEEPER, &game_code).await;
+    let _ = recv_state(&mut scorekeeper).await;
+    let _ = recv_state(&mut host).await; // scorekeeper connected
+
+    scorekeeper
+        .send_json(&ClientMessage::Host(HostAction::NextQuestion))
+        .await;
+    assert_eq!(recv_error(&mut scorekeeper).await, ErrorCode::NotAuthorized);
+
+    scorekeeper
+        .send_json(&ClientMessage::Host(HostAction::OverrideTeamScore {
+            team_name: "Quizzly Bears".to_string(),
+            override_points: 7,
+        }))
+        .await;
+    let state = recv_state(&mut scorekeeper).await;
+    assert_eq!(state.teams[0].score.override_points, 7);
+    let state = recv_state(&mut host).await;
+    assert_eq!(state.teams[0].score.override_points, 7);
+}
+
+#[tokio::test]
+async fn only_invited_users_join_as_staff() {
+    let server = TestServer::start().await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    let mut stranger = connect_staff(&server, CO_HOST, &game_code).await;
+    assert_eq!(recv_error(&mut stranger).await, ErrorCode::NotAuthorized);
+
+    let mut lost = connect_staff(&server, CO_HOST, "ZZZZ").await;
+    assert_eq!(recv_error(&mut lost).await, ErrorCode::GameNotFound);
+
+    // A user outside the host group still can't create games
+    let token = create_test_jwt(CO_HOST, &[], false);
+    let mut creator = TestClient::connect_with_token(&server.ws_url(), Some(&token)).await;
+    creator
+        .send_json(&ClientMessage::Host(HostAction::CreateGame {
+            game_code: None,
+            copy_settings_from: None,
+        }))
+        .await;
+    assert_eq!(recv_error(&mut creator).await, ErrorCode::NotAuthorized);
+
+    // Removing staff disconnects them and keeps them out
+    invite(&mut host, CO_HOST, StaffRole::CoHost).await;
+    let mut co_host = connect_staff(&server, CO_HOST, &game_code).await;
+    let _ = recv_state(&mut co_host).await;
+    let _ = recv_state(&mut host).await;
+
+    host.send_json(&ClientMessage::Host(HostAction::RemoveStaff {
+        user_id: CO_HOST.to_string(),
+    }))
+    .await;
+    assert!(recv_state(&mut host).await.staff.is_empty());
+    co_host.assert_closed().await;
+
+    let mut rejoin = connect_staff(&server, CO_HOST, &game_code).await;
+    assert_eq!(recv_error(&mut rejoin).await, ErrorCode::NotAuthorized);
+}
@@ -0,0 +1,88 @@
+use crate::{TestClient, TestServer};
+
+use backend::model::client_message::{ClientMessage, HostAction, TeamAction};
+use backend::model::patch::PatchOp;
+use backend::model::server_message::ServerMessage;
+
+#[tokio::test]
+async fn host_receives_patches_after_initial_snapshot() {
+    let server = TestServer::start().await;
+    let (mut host, _) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    host.send_json(&ClientMessage::Host(HostAction::StartTimer))
+        .await;
+
+    match host.recv_raw_json::<ServerMessage>().await {
+        ServerMessage::GameStatePatch { seq, ops } => {
+            assert_eq!(seq, 2, "Patch should follow the initial snapshot");
+            assert!(ops.contains(&PatchOp::Replace {
+                path: "/timerRunning".to_string(),
+                value: serde_json::json!(true),
+            }));
+            assert!(
+                !ops.iter().any(|op| matches!(
+                    op,
+                    PatchOp::Replace { path, .. } if path == "/questions"
+                )),
+                "Unchanged questions should not be resent"
+            );
+        }
+        other => panic!("Expected GameStatePatch, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn team_receives_patches_after_joining() {
+    let server = TestServer::start().await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    let mut team = TestClient::connect(&server.ws_url()).await;
+    team.join_game(&game_code, "Test Team").await;
+    let _: ServerMessage = host.recv_json().await; // team joined
+
+    host.send_json(&ClientMessage::Host(HostAction::StartTimer))
+        .await;
+
+    match team.recv_raw_json::<ServerMessage>().await {
+        ServerMessage::TeamGameStatePatch { seq, .. } => {
+            assert_eq!(seq, 2, "Patch should follow the join snapshot");
+        }
+        other => panic!("Expected TeamGameStatePatch, got {other:?}"),
+    }
+
+    // Patched state matches what a full snapshot would contain
+    team.send_json(&ClientMessage::Team(TeamAction::Resync))
+        .await;
+    match team.recv_raw_json::<ServerMessage>().await {
+        ServerMessage::TeamGameState { seq, state } => {
+            assert_eq!(seq, 3);
+            assert!(state.timer_running);
+        }
+        other => panic!("Expected TeamGameState, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn host_resync_sends_full_snapshot() {
+    let server = TestServer::start().await;
+    let (mut host, game_code) = TestClient::connect_as_host_and_create_game(&server).await;
+
+    host.send_json(&ClientMessage::Host(HostAction::Resync))
+        .await;
+
+    match host.recv_raw_json::<ServerMessage>().await {
+        ServerMessage::GameState { seq, state } => {
+            assert_eq!(seq, 2);
+            assert_eq!(state.game_code, game_code);
+        }
+        other => panic!("Expected GameState, got {other:?}"),
+    }
+
+    // Later updates are patches against the resynced snapshot
+    host.send_json(&ClientMessage::Host(HostAction::StartTimer))
+        .await;
+    match host.recv_raw_json::<ServerMessage>().await {
+        ServerMessage::GameStatePatch { seq, .. } => assert_eq!(seq, 3),
+        other => panic!("Expected GameStatePatch, got {other:?}"),
+    }
+}
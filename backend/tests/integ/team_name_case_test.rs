@@ -61,7 +61,7 @@ async fn team_with_capital_letters_scores_correctly() {
 
     // Verify host sees the correct score
     match host_response {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             let team_data = state
                 .teams
                 .iter()
@@ -78,7 +78,7 @@ async fn team_with_capital_letters_scores_correctly() {
 
     // Verify team sees their answer in the score log (filter_for_team)
     match team_response {
-        ServerMessage::TeamGameState { state } => {
+        ServerMessage::TeamGameState { state, .. } => {
             assert_eq!(state.questions.len(), 1, "Should have one question");
 
             let question = &state.questions[0];
@@ -125,7 +125,7 @@ async fn duplicate_submission_blocked_regardless_of_case() {
 
     // Verify first answer was recorded
     let first_answer_count = match &host_state {
-        ServerMessage::GameState { state } => state.questions[0].answers.len(),
+        ServerMessage::GameState { state, .. } => state.questions[0].answers.len(),
         _ => panic!("Expected GameState"),
     };
     assert_eq!(first_answer_count, 1, "First answer should be recorded");
@@ -237,7 +237,7 @@ async fn team_total_score_accumulates_across_questions_with_capitals() {
 
     // Verify cumulative score (50 + 50 = 100 question points, 0 + 5 = 5 bonus)
     match host_response {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             let team_data = state
                 .teams
                 .iter()
@@ -257,7 +257,7 @@ async fn team_total_score_accumulates_across_questions_with_capitals() {
 
     // Verify team sees both answers in their score log
     match team_response {
-        ServerMessage::TeamGameState { state } => {
+        ServerMessage::TeamGameState { state, .. } => {
             assert_eq!(state.questions.len(), 2, "Should have two questions");
 
             // Both questions should show the team's answers
@@ -66,7 +66,7 @@ async fn team_reconnects_and_score_persists() {
     // Host should receive update about team disconnection
     let host_update: ServerMessage = host.recv_json().await;
     match host_update {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             assert_eq!(state.teams.len(), 1, "Should still have 1 team");
             assert_eq!(state.teams[0].team_name, "Test Team A");
             assert!(!state.teams[0].connected, "Team should be disconnected");
@@ -85,7 +85,7 @@ async fn team_reconnects_and_score_persists() {
     let response: ServerMessage = team_a_reconnected.recv_json().await;
 
     match response {
-        ServerMessage::TeamGameState { state } => {
+        ServerMessage::TeamGameState { state, .. } => {
             assert_eq!(state.game_code, game_code, "Game codes should match");
         }
         other => panic!("Expected TeamGameState message, got {other:?}"),
@@ -94,7 +94,7 @@ async fn team_reconnects_and_score_persists() {
     // Host should receive GameState showing team reconnected
     let host_update: ServerMessage = host.recv_json().await;
     match host_update {
-        ServerMessage::GameState { state } => {
+        ServerMessage::GameState { state, .. } => {
             assert_eq!(state.teams.len(), 1, "Should have exactly 1 team");
             let team = &state.teams[0];
             assert_eq!(team.team_name, "Test Team A");
@@ -331,6 +331,12 @@ export default function HostGame() {
                 speedBonusNumTeams: newSettings.speedBonusNumTeams,
                 speedBonusFirstPlacePoints:
                   newSettings.speedBonusFirstPlacePoints,
+                lateGraceSeconds: newSettings.lateGraceSeconds,
+                speedBonusMode: newSettings.speedBonusMode,
+                // The server rejects warnings longer than the timer
+                timerWarningSeconds: newSettings.timerWarningSeconds.filter(
+                  (seconds) => seconds <= newSettings.defaultTimerDuration,
+                ),
               },
             });
           }}
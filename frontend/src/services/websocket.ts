@@ -1,6 +1,11 @@
 import { fetchAuthSession } from "aws-amplify/auth";
 import { isLocalMode, wsUrl } from "../config";
-import type { ClientMessage, ServerMessage } from "../types";
+import {
+  PROTOCOL_VERSION,
+  type ClientKind,
+  type ClientMessage,
+  type ServerMessage,
+} from "../types";
 
 export type ConnectionState =
   | "disconnected"
@@ -12,16 +17,26 @@ export type ConnectionState =
 type MessageHandler = (message: ServerMessage) => void;
 type StateChangeHandler = (state: ConnectionState) => void;
 
-async function buildWsUrl(): Promise<string> {
+// The host's access token, sent in the hello rather than the URL so it stays out of
+// proxy and load balancer logs. forceRefresh fetches a new one, for reauthenticating
+// before the current one expires.
+export async function hostToken(
+  forceRefresh = false
+): Promise<string | undefined> {
   if (isLocalMode) {
-    return wsUrl;
+    return undefined;
   }
-  const session = await fetchAuthSession();
-  const token = session.tokens?.accessToken?.toString();
-  if (!token) {
-    return wsUrl;
-  }
-  return `${wsUrl}?token=${encodeURIComponent(token)}`;
+  const session = await fetchAuthSession({ forceRefresh });
+  return session.tokens?.accessToken?.toString();
+}
+
+// Hello announcing our protocol version, sent before a connection's first action.
+// Host connections also carry their token.
+async function helloFor(message: ClientMessage): Promise<ClientMessage> {
+  const clientKind: ClientKind =
+    "host" in message ? "host" : "team" in message ? "team" : "watcher";
+  const token = clientKind === "host" ? await hostToken() : undefined;
+  return { hello: { protocolVersion: PROTOCOL_VERSION, clientKind, token } };
 }
 
 class WebSocketService {
@@ -50,7 +65,8 @@ class WebSocketService {
     this.intentionalDisconnect = true;
     this.cancelReconnection();
     if (this.ws) {
-      this.ws.close();
+      // A normal close tells the server the client left, rather than dropped out
+      this.ws.close(1000);
       this.ws = null;
     }
     this.setConnectionState("disconnected");
@@ -69,7 +85,8 @@ class WebSocketService {
    * Atomically connect and send an initial message.
    * - Sets state to "connecting"
    * - Opens WebSocket, waits for onopen
-   * - Sends message immediately
+   * - Sends a hello with our protocol version and waits for the welcome
+   * - Sends message
    * - Waits for first response:
    *   - If `error` message → disconnect, throw error
    *   - If any other message → transition to "connected", resolve
@@ -95,8 +112,7 @@ class WebSocketService {
     this.setConnectionState("connecting");
 
     try {
-      const url = await buildWsUrl();
-      this.ws = new WebSocket(url);
+      this.ws = new WebSocket(wsUrl);
 
       // Wait for connection to open
       await new Promise<void>((resolve, reject) => {
@@ -151,7 +167,8 @@ class WebSocketService {
         console.error("WebSocket error:", error);
       };
 
-      // Now send message and await response
+      // Handshake, then send message and await response
+      await this.sendAndAwaitResponse(await helloFor(message));
       await this.sendAndAwaitResponse(message);
 
       console.log("WebSocket connected and initial message succeeded");
@@ -177,7 +194,7 @@ class WebSocketService {
       const handleMessage = (msg: ServerMessage) => {
         clearTimeout(timeout);
         unsubscribe();
-        if (msg.type === "error") {
+        if (msg.type === "error" || msg.type === "unsupportedProtocol") {
           reject(new Error(msg.message));
         } else {
           resolve();
@@ -251,8 +268,7 @@ class WebSocketService {
       );
 
       try {
-        const url = await buildWsUrl();
-        this.ws = new WebSocket(url);
+        this.ws = new WebSocket(wsUrl);
 
         await new Promise<void>((resolve, reject) => {
           const timeout = setTimeout(() => {
@@ -297,6 +313,9 @@ class WebSocketService {
             if (this.initialMessage) {
               try {
                 console.log("Replaying initial message for reconnection...");
+                await this.sendAndAwaitResponse(
+                  await helloFor(this.initialMessage)
+                );
                 await this.sendAndAwaitResponse(this.initialMessage);
                 console.log("Reconnection message replay succeeded");
               } catch (error) {
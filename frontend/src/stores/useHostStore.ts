@@ -1,5 +1,11 @@
 import { create } from "zustand";
-import { webSocketService } from "../services/websocket";
+import { hostToken, webSocketService } from "../services/websocket";
+import {
+  runCountdown,
+  updateOffsetFromClockSync,
+  updateOffsetFromServerTime,
+} from "../utils/countdown";
+import { PatchedState } from "../utils/statePatch";
 import type {
   GameState,
   GameSettings,
@@ -14,6 +20,7 @@ interface HostStore {
   currentQuestionNumber: number;
   timerRunning: boolean;
   timerSecondsRemaining: number | null;
+  timerDeadlineMs: number | null;
   gameSettings: GameSettings | null;
   questions: Question[];
   teams: TeamData[];
@@ -30,6 +37,7 @@ export const useHostStore = create<HostStore>((set) => ({
   currentQuestionNumber: 0,
   timerRunning: false,
   timerSecondsRemaining: null,
+  timerDeadlineMs: null,
   gameSettings: null,
   questions: [],
   teams: [],
@@ -41,6 +49,7 @@ export const useHostStore = create<HostStore>((set) => ({
       currentQuestionNumber: state.currentQuestionNumber,
       timerRunning: state.timerRunning,
       timerSecondsRemaining: state.timerSecondsRemaining,
+      timerDeadlineMs: state.timerRunning ? state.timerDeadlineMs : null,
       gameSettings: state.gameSettings,
       questions: state.questions,
       teams: state.teams,
@@ -57,6 +66,7 @@ export const useHostStore = create<HostStore>((set) => ({
       currentQuestionNumber: 0,
       timerRunning: false,
       timerSecondsRemaining: null,
+      timerDeadlineMs: null,
       gameSettings: null,
       questions: [],
       teams: [],
@@ -67,16 +77,52 @@ export const useHostStore = create<HostStore>((set) => ({
  * Subscribe to WebSocket messages relevant to the host.
  * Call in useEffect and return the unsubscribe function.
  */
+// Last server snapshot, kept separately so local countdown ticks don't affect patching
+const hostState = new PatchedState<GameState>();
+
+// Swap the connection's token for a fresh one, keeping (or regaining) control of the game
+async function reauthenticate() {
+  const token = await hostToken(true);
+  if (token) {
+    webSocketService.send({ host: { type: "reauthenticate", token } });
+  }
+}
+
 export function subscribeToHostMessages() {
-  return webSocketService.onMessage((message: ServerMessage) => {
-    const { setGameState, setTimerSecondsRemaining } = useHostStore.getState();
+  const stopCountdown = runCountdown(
+    () => useHostStore.getState().timerDeadlineMs,
+    (seconds) => useHostStore.getState().setTimerSecondsRemaining(seconds)
+  );
+
+  const unsubscribe = webSocketService.onMessage((message: ServerMessage) => {
+    const { setGameState } = useHostStore.getState();
 
     switch (message.type) {
       case "gameState":
+        hostState.setSnapshot(message.seq, message.state);
+        updateOffsetFromServerTime(message.state.serverTimeMs);
         setGameState(message.state);
         break;
-      case "timerTick":
-        setTimerSecondsRemaining(message.secondsRemaining);
+      case "gameStatePatch": {
+        const state = hostState.applyPatch(message.seq, message.ops);
+        if (state === null) {
+          // Missed an update - ask for a full snapshot
+          webSocketService.send({ host: { type: "resync" } });
+          break;
+        }
+        updateOffsetFromServerTime(state.serverTimeMs);
+        setGameState(state);
+        break;
+      }
+      case "authExpiring":
+      case "authExpired":
+        void reauthenticate();
+        break;
+      case "clockSync":
+        updateOffsetFromClockSync(message.clientTimeMs, message.serverTimeMs);
+        break;
+      case "timerEvent":
+        updateOffsetFromServerTime(message.serverTimeMs);
         break;
       case "error":
         // Rollback state if provided
@@ -86,4 +132,10 @@ export function subscribeToHostMessages() {
         break;
     }
   });
+
+  return () => {
+    stopCountdown();
+    unsubscribe();
+    hostState.clear();
+  };
 }
@@ -1,5 +1,11 @@
 import { create } from "zustand";
 import { webSocketService } from "../services/websocket";
+import {
+  runCountdown,
+  updateOffsetFromClockSync,
+  updateOffsetFromServerTime,
+} from "../utils/countdown";
+import { PatchedState } from "../utils/statePatch";
 import type { TeamGameState, ServerMessage } from "../types";
 import type { TeamColorOption } from "../utils/colors";
 
@@ -100,8 +106,19 @@ export const useTeamStore = create<TeamStore>((set) => ({
  * Subscribe to WebSocket messages relevant to the team.
  * Call in useEffect and return the unsubscribe function.
  */
+// Last server snapshot, kept separately so local countdown ticks don't affect patching
+const teamState = new PatchedState<TeamGameState>();
+
 export function subscribeToTeamMessages() {
-  return webSocketService.onMessage((message: ServerMessage) => {
+  const stopCountdown = runCountdown(
+    () => {
+      const gameState = useTeamStore.getState().teamGameState;
+      return gameState?.timerRunning ? gameState.timerDeadlineMs : null;
+    },
+    (seconds) => useTeamStore.getState().setTimerSecondsRemaining(seconds)
+  );
+
+  const unsubscribe = webSocketService.onMessage((message: ServerMessage) => {
     const state = useTeamStore.getState();
     const {
       step,
@@ -112,7 +129,6 @@ export function subscribeToTeamMessages() {
       setColor,
       setTeamMembers,
       setTeamGameState,
-      setTimerSecondsRemaining,
     } = state;
 
     switch (message.type) {
@@ -121,8 +137,11 @@ export function subscribeToTeamMessages() {
         setStep("members");
         break;
 
-      case "timerTick":
-        setTimerSecondsRemaining(message.secondsRemaining);
+      case "clockSync":
+        updateOffsetFromClockSync(message.clientTimeMs, message.serverTimeMs);
+        break;
+      case "timerEvent":
+        updateOffsetFromServerTime(message.serverTimeMs);
         break;
 
       case "error": {
@@ -130,7 +149,11 @@ export function subscribeToTeamMessages() {
         setIsValidating(false);
         const connectionState = webSocketService.connectionState;
 
-        if (step === "game" && connectionState !== "reconnecting") {
+        if (step === "game" && message.teamState) {
+          // A rejected action - revert to the server's state
+          setError(message.message);
+          setTeamGameState(message.teamState);
+        } else if (step === "game" && connectionState !== "reconnecting") {
           // Team is in game and this isn't from a failed reconnection attempt.
           // Show error but attempt to rejoin automatically.
           setError(message.message);
@@ -157,9 +180,29 @@ export function subscribeToTeamMessages() {
         }
 
         setIsValidating(false);
+        teamState.setSnapshot(message.seq, message.state);
+        updateOffsetFromServerTime(message.state.serverTimeMs);
         setTeamGameState(message.state);
         break;
       }
+
+      case "teamGameStatePatch": {
+        const patched = teamState.applyPatch(message.seq, message.ops);
+        if (patched === null) {
+          // Missed an update - ask for a full snapshot
+          webSocketService.send({ team: "resync" });
+          break;
+        }
+        updateOffsetFromServerTime(patched.serverTimeMs);
+        setTeamGameState(patched);
+        break;
+      }
     }
   });
+
+  return () => {
+    stopCountdown();
+    unsubscribe();
+    teamState.clear();
+  };
 }
@@ -100,6 +100,11 @@ export interface TeamQuestion {
   content: AnswerContent | null;
   questionKind: QuestionKind;
   questionConfig: QuestionConfig;
+  // Host only. Submitted during the grace window after the timer expired; unscored until
+  // accepted
+  late?: boolean;
+  // Host only. Milliseconds of timer running time before the answer arrived
+  answerLatencyMs?: number;
 }
 
 // The content of a team's answer, varying by question type.
@@ -144,10 +149,14 @@ export interface Question {
   questionConfig: QuestionConfig;
   answers: TeamQuestion[];
   speedBonusEnabled: boolean;
+  // The timer's actual run, adjustments included; time-curve bonuses decay over it
+  timerWindowMs?: number;
 }
 
 // === Game Settings ===
 
+export type SpeedBonusMode = "placement" | "timeCurve";
+
 export interface GameSettings {
   defaultTimerDuration: number;
   defaultQuestionPoints: number;
@@ -157,6 +166,9 @@ export interface GameSettings {
   speedBonusEnabled: boolean;
   speedBonusNumTeams: number;
   speedBonusFirstPlacePoints: number;
+  lateGraceSeconds: number;
+  speedBonusMode: SpeedBonusMode;
+  timerWarningSeconds: number[];
 }
 
 // === Team Types ===
@@ -174,6 +186,18 @@ export interface TeamData {
   connected: boolean;
 }
 
+// === Staff Types ===
+
+// coHost: full control except managing staff; scorekeeper: scoreAnswer and
+// overrideTeamScore only
+export type StaffRole = "coHost" | "scorekeeper";
+
+export interface StaffMember {
+  userId: string;
+  role: StaffRole;
+  connected: boolean;
+}
+
 // === GameState (Server → Host) ===
 
 export interface GameState {
@@ -181,9 +205,13 @@ export interface GameState {
   currentQuestionNumber: number;
   timerRunning: boolean;
   timerSecondsRemaining: number | null;
+  timerDeadlineMs: number | null;
+  serverTimeMs: number;
   teams: TeamData[];
   questions: Question[];
   gameSettings: GameSettings;
+  // Users the host invited to help run the game
+  staff: StaffMember[];
 }
 
 // === TeamGameState (Server → Team) ===
@@ -193,6 +221,8 @@ export interface TeamGameState {
   currentQuestionNumber: number;
   timerRunning: boolean;
   timerSecondsRemaining: number | null;
+  timerDeadlineMs: number | null;
+  serverTimeMs: number;
   team: TeamData;
   questions: TeamQuestion[];
 }
@@ -201,29 +231,166 @@ export interface TeamGameState {
 
 export interface GameStateMessage {
   type: "gameState";
+  seq: number;
   state: GameState;
 }
 
 export interface TeamGameStateMessage {
   type: "teamGameState";
+  seq: number;
   state: TeamGameState;
 }
 
-export interface TimerTickMessage {
-  type: "timerTick";
+// JSON Patch operation (RFC 6902 subset) used for incremental state updates
+export type PatchOp =
+  | { op: "add"; path: string; value: unknown }
+  | { op: "remove"; path: string }
+  | { op: "replace"; path: string; value: unknown };
+
+// Changes since the previous update; seq increments by one, a gap means "send resync"
+export interface GameStatePatchMessage {
+  type: "gameStatePatch";
+  seq: number;
+  ops: PatchOp[];
+}
+
+export interface TeamGameStatePatchMessage {
+  type: "teamGameStatePatch";
+  seq: number;
+  ops: PatchOp[];
+}
+
+export interface ClockSyncMessage {
+  type: "clockSync";
+  clientTimeMs: number;
+  serverTimeMs: number;
+}
+
+export type TimerEventKind =
+  | { kind: "started" }
+  | { kind: "resumed" }
+  | { kind: "paused" }
+  | { kind: "warning"; thresholdSeconds: number }
+  | { kind: "expired" }
+  | { kind: "adjusted"; deltaSeconds: number };
+
+export interface TimerEventMessage {
+  type: "timerEvent";
+  event: TimerEventKind;
   secondsRemaining: number;
+  deadlineMs?: number;
+  serverTimeMs: number;
+}
+
+export type ErrorCode =
+  | "invalidMessage"
+  | "unexpectedAction"
+  | "rateLimited"
+  | "messageTooLarge"
+  | "authenticationRequired"
+  | "authenticationExpired"
+  | "notAuthorized"
+  | "gameNotFound"
+  | "gameAlreadyHasHost"
+  | "alreadyConnected"
+  | "gameCodeTaken"
+  | "teamNameTaken"
+  | "teamNotFound"
+  | "staffNotFound"
+  | "questionNotFound"
+  | "answerNotFound"
+  | "answerAlreadySubmitted"
+  | "answerTooLong"
+  | "submissionsClosed"
+  | "alreadyAtFirstQuestion"
+  | "questionHasAnswers"
+  | "configDoesNotMatchQuestionType"
+  | "invalidSettings"
+  | "loadFailed"
+  | "saveFailed"
+  | "internal";
+
+// Sent when an action carrying a requestId succeeds
+export interface AckMessage {
+  type: "ack";
+  requestId: string;
 }
 
 export interface ErrorMessage {
   type: "error";
+  code: ErrorCode;
   message: string;
+  requestId?: string;
+  // Current state, for reverting optimistic updates
   state?: GameState;
+  teamState?: TeamGameState;
 }
 
 export interface JoinValidatedMessage {
   type: "joinValidated";
 }
 
+export interface WelcomeMessage {
+  type: "welcome";
+  protocolVersion: number;
+  supportedVersions: number[];
+  encoding: Encoding;
+}
+
+// The server no longer speaks our protocol version; the page needs a refresh
+export interface UnsupportedProtocolMessage {
+  type: "unsupportedProtocol";
+  message: string;
+  supportedVersions: number[];
+}
+
+// The server is shutting down; the connection will close and should be retried
+export interface ServerRestartingMessage {
+  type: "serverRestarting";
+  message: string;
+}
+
+// The host token expires soon; send reauthenticate with a fresh one before expiresAtMs
+export interface AuthExpiringMessage {
+  type: "authExpiring";
+  expiresAtMs: number;
+}
+
+// The host token expired; updates keep coming but actions fail until reauthenticate
+export interface AuthExpiredMessage {
+  type: "authExpired";
+}
+
+// Reply to Reauthenticate
+export interface ReauthenticatedMessage {
+  type: "reauthenticated";
+  expiresAtMs: number | null;
+}
+
+// Reply to ListGames, most recently played first
+export interface SavedGamesMessage {
+  type: "savedGames";
+  games: SavedGameSummary[];
+}
+
+// Reply to DeleteGame
+export interface GameDeletedMessage {
+  type: "gameDeleted";
+  gameCode: string;
+}
+
+export type GamePhase = "notStarted" | "inProgress";
+
+export interface SavedGameSummary {
+  gameCode: string;
+  createdAtMs: number | null;
+  lastPlayedMs: number | null;
+  teamCount: number;
+  questionCount: number;
+  currentQuestionNumber: number;
+  phase: GamePhase;
+}
+
 // === Scoreboard Data (for watchers) ===
 
 export interface ScoreboardData {
@@ -238,9 +405,21 @@ export interface ScoreboardDataMessage {
 export type ServerMessage =
   | GameStateMessage
   | TeamGameStateMessage
-  | TimerTickMessage
+  | GameStatePatchMessage
+  | TeamGameStatePatchMessage
+  | ClockSyncMessage
+  | TimerEventMessage
+  | AckMessage
   | ErrorMessage
   | JoinValidatedMessage
+  | WelcomeMessage
+  | UnsupportedProtocolMessage
+  | ServerRestartingMessage
+  | AuthExpiringMessage
+  | AuthExpiredMessage
+  | ReauthenticatedMessage
+  | SavedGamesMessage
+  | GameDeletedMessage
   | ScoreboardDataMessage;
 
 // === Client Messages ===
@@ -248,6 +427,38 @@ export type ServerMessage =
 export interface CreateGameAction {
   type: "createGame";
   gameCode?: string;
+  // Start with this saved game's settings and questions, without its teams or answers
+  copySettingsFrom?: string;
+}
+
+// Allowed before createGame
+export interface ListGamesAction {
+  type: "listGames";
+}
+
+// Allowed before createGame; games with a host connected can't be deleted
+export interface DeleteGameAction {
+  type: "deleteGame";
+  gameCode: string;
+}
+
+// Sent instead of createGame by a user invited to someone else's game
+export interface JoinAsStaffAction {
+  type: "joinAsStaff";
+  gameCode: string;
+}
+
+// Owner only; inviting someone already invited changes their role
+export interface InviteStaffAction {
+  type: "inviteStaff";
+  userId: string;
+  role: StaffRole;
+}
+
+// Owner only; disconnects the user if they're connected
+export interface RemoveStaffAction {
+  type: "removeStaff";
+  userId: string;
 }
 
 export interface StartTimerAction {
@@ -262,6 +473,26 @@ export interface ResetTimerAction {
   type: "resetTimer";
 }
 
+export interface AdjustTimerAction {
+  type: "adjustTimer";
+  deltaSeconds: number;
+}
+
+export interface SyncClockAction {
+  type: "syncClock";
+  clientTimeMs: number;
+}
+
+export interface ResyncAction {
+  type: "resync";
+}
+
+// Replace the connection's token with a fresh one for the same user
+export interface ReauthenticateAction {
+  type: "reauthenticate";
+  token: string;
+}
+
 export interface NextQuestionAction {
   type: "nextQuestion";
 }
@@ -277,6 +508,13 @@ export interface ScoreAnswerAction {
   score: ScoreData;
 }
 
+export interface ResolveLateAnswerAction {
+  type: "resolveLateAnswer";
+  questionNumber: number;
+  teamName: string;
+  accept: boolean;
+}
+
 export interface OverrideTeamScoreAction {
   type: "overrideTeamScore";
   teamName: string;
@@ -293,6 +531,9 @@ export interface UpdateGameSettingsAction {
   speedBonusEnabled: boolean;
   speedBonusNumTeams: number;
   speedBonusFirstPlacePoints: number;
+  lateGraceSeconds: number;
+  speedBonusMode: SpeedBonusMode;
+  timerWarningSeconds: number[];
 }
 
 export interface UpdateQuestionSettingsAction {
@@ -315,12 +556,22 @@ export interface UpdateTypeSpecificSettingsAction {
 
 export type HostAction =
   | CreateGameAction
+  | ListGamesAction
+  | DeleteGameAction
+  | JoinAsStaffAction
+  | InviteStaffAction
+  | RemoveStaffAction
   | StartTimerAction
   | PauseTimerAction
   | ResetTimerAction
+  | AdjustTimerAction
+  | SyncClockAction
+  | ResyncAction
+  | ReauthenticateAction
   | NextQuestionAction
   | PrevQuestionAction
   | ScoreAnswerAction
+  | ResolveLateAnswerAction
   | OverrideTeamScoreAction
   | UpdateGameSettingsAction
   | UpdateQuestionSettingsAction
@@ -345,10 +596,16 @@ export interface SubmitAnswerData {
   answer: string;
 }
 
+export interface SyncClockData {
+  clientTimeMs: number;
+}
+
 export type TeamAction =
   | { validateJoin: ValidateJoinData }
   | { joinGame: JoinGameData }
-  | { submitAnswer: SubmitAnswerData };
+  | { submitAnswer: SubmitAnswerData }
+  | { syncClock: SyncClockData }
+  | "resync";
 
 export interface HostClientMessage {
   host: HostAction;
@@ -369,4 +626,30 @@ export interface WatcherClientMessage {
   watcher: WatcherAction;
 }
 
-export type ClientMessage = HostClientMessage | TeamClientMessage | WatcherClientMessage;
+// Protocol version this client speaks, announced in the opening hello
+export const PROTOCOL_VERSION = 2;
+
+export type ClientKind = "host" | "team" | "watcher";
+
+// Encoding of server messages after the welcome. "messagePack" arrives as binary frames.
+export type Encoding = "json" | "messagePack";
+
+export interface HelloData {
+  protocolVersion: number;
+  clientKind: ClientKind;
+  encoding?: Encoding;
+  // Host access token; kept out of the URL so it isn't logged by proxies
+  token?: string;
+}
+
+export interface HelloClientMessage {
+  hello: HelloData;
+}
+
+// Any message may carry a requestId, echoed in the ack or error that answers it
+export type ClientMessage = (
+  | HelloClientMessage
+  | HostClientMessage
+  | TeamClientMessage
+  | WatcherClientMessage
+) & { requestId?: string };
@@ -0,0 +1,42 @@
+// Local timer countdown driven by the server's absolute deadline.
+// The server only sends the deadline on start/pause/adjust; clients tick on their own.
+
+const TICK_INTERVAL_MS = 250;
+
+// Estimated (server clock - local clock), refined by clock sync replies
+let clockOffsetMs = 0;
+
+export function updateOffsetFromServerTime(serverTimeMs: number): void {
+  clockOffsetMs = serverTimeMs - Date.now();
+}
+
+export function updateOffsetFromClockSync(
+  clientTimeMs: number,
+  serverTimeMs: number
+): void {
+  const now = Date.now();
+  const roundTripMs = now - clientTimeMs;
+  clockOffsetMs = serverTimeMs + roundTripMs / 2 - now;
+}
+
+export function secondsUntil(deadlineMs: number): number {
+  const remainingMs = deadlineMs - (Date.now() + clockOffsetMs);
+  return Math.max(0, Math.ceil(remainingMs / 1000));
+}
+
+/**
+ * Call onTick with the seconds remaining whenever a deadline is set.
+ * Returns a function that stops the countdown.
+ */
+export function runCountdown(
+  getDeadline: () => number | null,
+  onTick: (secondsRemaining: number) => void
+): () => void {
+  const id = setInterval(() => {
+    const deadline = getDeadline();
+    if (deadline !== null) {
+      onTick(secondsUntil(deadline));
+    }
+  }, TICK_INTERVAL_MS);
+  return () => clearInterval(id);
+}
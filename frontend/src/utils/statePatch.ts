@@ -0,0 +1,90 @@
+// Incremental state updates: the server sends full snapshots on connect/resync and
+// JSON Patch operations (add/remove/replace) with a sequence number after that.
+import type { PatchOp } from "../types";
+
+function decodePointer(path: string): string[] {
+  if (path === "") return [];
+  return path
+    .slice(1)
+    .split("/")
+    .map((token) => token.replace(/~1/g, "/").replace(/~0/g, "~"));
+}
+
+/**
+ * Apply patch operations to a copy of `state`.
+ * Throws if a path doesn't exist; callers should request a resync.
+ */
+export function applyPatch<T>(state: T, ops: PatchOp[]): T {
+  let root: unknown = structuredClone(state);
+
+  for (const op of ops) {
+    const tokens = decodePointer(op.path);
+    const last = tokens.pop();
+    if (last === undefined) {
+      if (op.op === "remove") throw new Error("Cannot remove the root");
+      root = op.value;
+      continue;
+    }
+
+    let parent = root as Record<string, unknown> | unknown[];
+    for (const token of tokens) {
+      const next = Array.isArray(parent) ? parent[Number(token)] : parent[token];
+      if (next === null || typeof next !== "object") {
+        throw new Error(`Patch path not found: ${op.path}`);
+      }
+      parent = next as Record<string, unknown> | unknown[];
+    }
+
+    if (Array.isArray(parent)) {
+      const index = last === "-" ? parent.length : Number(last);
+      if (op.op === "add") {
+        parent.splice(index, 0, op.value);
+      } else if (index >= parent.length) {
+        throw new Error(`Patch path not found: ${op.path}`);
+      } else if (op.op === "replace") {
+        parent[index] = op.value;
+      } else {
+        parent.splice(index, 1);
+      }
+    } else if (op.op === "remove") {
+      delete parent[last];
+    } else {
+      parent[last] = op.value;
+    }
+  }
+
+  return root as T;
+}
+
+/**
+ * Tracks the last snapshot on one state stream and applies patches to it.
+ * Returns the new state, or null if a sequence gap or bad patch means a resync is needed.
+ */
+export class PatchedState<T> {
+  private seq: number | null = null;
+  private state: T | null = null;
+
+  setSnapshot(seq: number, state: T): void {
+    this.seq = seq;
+    this.state = state;
+  }
+
+  applyPatch(seq: number, ops: PatchOp[]): T | null {
+    if (this.state === null || this.seq === null || seq !== this.seq + 1) {
+      return null;
+    }
+    try {
+      this.state = applyPatch(this.state, ops);
+      this.seq = seq;
+      return this.state;
+    } catch (e) {
+      console.error("Failed to apply state patch:", e);
+      return null;
+    }
+  }
+
+  clear(): void {
+    this.seq = null;
+    this.state = null;
+  }
+}